@@ -0,0 +1,320 @@
+//! Role-based access control shared by `icp_swap`, `tokenomics`, `logs` and `lbry_fun`.
+//!
+//! Each canister keeps a [`RoleStore`] in two of its stable memories: the roles granted
+//! to each principal, and an append-only trail of every grant and revoke. A canister's
+//! own controllers always hold [`Role::Controller`]; every other role is granted by a
+//! controller through the canister's `grant_role` and `revoke_role` endpoints.
+//!
+//! Canisters get their guards from [`role_guards!`] and their role endpoints from
+//! [`role_endpoints!`], both pointed at the thread-local holding the store. The macros
+//! name `access_control`, `candid` and `ic_cdk` by path, as `export_candid!` reads the
+//! endpoint signatures as written. [`PausableOp`] names what icp_swap lets operators and
+//! lbry_fun pause.
+
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Encode, Principal};
+use ic_stable_structures::{storable::Bound, Memory, StableBTreeMap, Storable};
+use serde::Deserialize;
+
+/// Most audit entries returned by one [`RoleStore::audit_trail`] call.
+pub const MAX_AUDIT_PAGE_SIZE: u64 = 500;
+/// Audit entries `get_role_audit_trail` returns when no limit is given.
+pub const DEFAULT_AUDIT_PAGE_SIZE: u64 = 100;
+
+/// Checks that `canister_id`, configured as `field`, can be another canister this one
+/// calls.
+pub fn validate_external_canister(field: &str, canister_id: Principal) -> Result<(), String> {
+    if canister_id == Principal::anonymous() || canister_id == Principal::management_canister() {
+        return Err(format!("{} cannot be {}", field, canister_id));
+    }
+    if canister_id == ic_cdk::api::id() {
+        return Err(format!("{} cannot be this canister", field));
+    }
+    // Canister ids are 10 bytes long; anything else is a user or derived principal.
+    if canister_id.as_slice().len() != 10 {
+        return Err(format!("{} {} is not a canister id", field, canister_id));
+    }
+    Ok(())
+}
+
+/// Defines the `has_role`, `is_controller_role` and `is_auditor` guards over the
+/// `RefCell<RoleStore>` thread-local `$roles`.
+#[macro_export]
+macro_rules! role_guards {
+    ($roles:ident) => {
+        pub fn has_role(
+            principal: candid::Principal,
+            role: access_control::Role,
+        ) -> bool {
+            $roles.with(|roles| roles.borrow().has_role(principal, role))
+        }
+
+        pub fn is_controller_role() -> Result<(), String> {
+            $roles.with(|roles| {
+                roles
+                    .borrow()
+                    .require(ic_cdk::api::caller(), access_control::Role::Controller)
+            })
+        }
+
+        pub fn is_auditor() -> Result<(), String> {
+            $roles.with(|roles| {
+                roles
+                    .borrow()
+                    .require(ic_cdk::api::caller(), access_control::Role::Auditor)
+            })
+        }
+    };
+}
+
+/// Defines the `get_roles`, `get_role_members` and `get_role_audit_trail` queries over
+/// the `RefCell<RoleStore>` thread-local `$roles`. Expects the [`role_guards!`] guards
+/// in scope.
+#[macro_export]
+macro_rules! role_query_endpoints {
+    ($roles:ident) => {
+        #[ic_cdk::query]
+        fn get_roles(principal: candid::Principal) -> Vec<access_control::Role> {
+            $roles.with(|roles| roles.borrow().roles_of(principal))
+        }
+
+        #[ic_cdk::query]
+        fn get_role_members(role: access_control::Role) -> Vec<candid::Principal> {
+            $roles.with(|roles| roles.borrow().members(role))
+        }
+
+        /// Role grants and revokes from `start` on, oldest first.
+        #[ic_cdk::query(guard = "is_auditor")]
+        fn get_role_audit_trail(
+            start: Option<u64>,
+            limit: Option<u64>,
+        ) -> Vec<access_control::RoleChange> {
+            $roles.with(|roles| {
+                roles.borrow().audit_trail(
+                    start.unwrap_or(0),
+                    limit.unwrap_or($crate::DEFAULT_AUDIT_PAGE_SIZE),
+                )
+            })
+        }
+    };
+}
+
+/// Defines `grant_role` and `revoke_role`, returning `String` errors, along with the
+/// [`role_query_endpoints!`], over the `RefCell<RoleStore>` thread-local `$roles`.
+/// Expects the [`role_guards!`] guards in scope.
+#[macro_export]
+macro_rules! role_endpoints {
+    ($roles:ident) => {
+        #[ic_cdk::update(guard = "is_controller_role")]
+        fn grant_role(
+            principal: candid::Principal,
+            role: access_control::Role,
+        ) -> Result<access_control::RoleChange, String> {
+            let caller = ic_cdk::api::caller();
+            $roles.with(|roles| roles.borrow_mut().grant(caller, principal, role))
+        }
+
+        #[ic_cdk::update(guard = "is_controller_role")]
+        fn revoke_role(
+            principal: candid::Principal,
+            role: access_control::Role,
+        ) -> Result<access_control::RoleChange, String> {
+            let caller = ic_cdk::api::caller();
+            $roles.with(|roles| roles.borrow_mut().revoke(caller, principal, role))
+        }
+
+        $crate::role_query_endpoints!($roles);
+    };
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// Grants and revokes roles and changes canister settings.
+    Controller,
+    /// Runs day-to-day operations, such as pausing and resuming.
+    Operator,
+    /// Creator-only actions, such as claiming creator revenue. A token's swap canister
+    /// grants it to the creator it launched with; payouts stay theirs to set, so granting
+    /// it to anyone else never redirects revenue.
+    Creator,
+    /// Reads the audit trail and other records kept for review.
+    Auditor,
+}
+
+impl Role {
+    /// Whether holding `self` is enough where `required` is asked for. Controllers can
+    /// do what operators and auditors do, but never act for a creator.
+    pub fn covers(self, required: Role) -> bool {
+        self == required
+            || (self == Role::Controller && matches!(required, Role::Operator | Role::Auditor))
+    }
+}
+
+/// icp_swap operations an operator, lbry_fun or the circuit breaker can pause.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PausableOp {
+    Swap,
+    BurnSecondary,
+    StakePrimary,
+    DistributeReward,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoleAction {
+    Granted,
+    Revoked,
+}
+
+/// One entry of the audit trail. `actor` made the change, at `time` in nanoseconds.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct RoleChange {
+    pub id: u64,
+    pub time: u64,
+    pub actor: Principal,
+    pub principal: Principal,
+    pub role: Role,
+    pub action: RoleAction,
+}
+
+impl Storable for RoleChange {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+struct GrantedRoles(Vec<Role>);
+
+impl Storable for GrantedRoles {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Granted roles and their audit trail. Authorizing a change is up to the endpoint
+/// making it; the store only records it.
+pub struct RoleStore<M: Memory> {
+    roles: StableBTreeMap<Principal, GrantedRoles, M>,
+    audit_trail: StableBTreeMap<u64, RoleChange, M>,
+}
+
+impl<M: Memory> RoleStore<M> {
+    pub fn init(roles_memory: M, audit_memory: M) -> Self {
+        RoleStore {
+            roles: StableBTreeMap::init(roles_memory),
+            audit_trail: StableBTreeMap::init(audit_memory),
+        }
+    }
+
+    /// Roles `principal` holds, including [`Role::Controller`] for canister controllers.
+    pub fn roles_of(&self, principal: Principal) -> Vec<Role> {
+        let mut roles = self.roles.get(&principal).unwrap_or_default().0;
+        if ic_cdk::api::is_controller(&principal) && !roles.contains(&Role::Controller) {
+            roles.insert(0, Role::Controller);
+        }
+        roles
+    }
+
+    pub fn has_role(&self, principal: Principal, role: Role) -> bool {
+        self.roles_of(principal).into_iter().any(|held| held.covers(role))
+    }
+
+    /// The error guards return when `principal` lacks `role`.
+    pub fn require(&self, principal: Principal, role: Role) -> Result<(), String> {
+        if self.has_role(principal, role) {
+            Ok(())
+        } else {
+            Err(format!("The {:?} role is required to call this method.", role))
+        }
+    }
+
+    /// Principals `role` was granted to. Canister controllers aren't listed.
+    pub fn members(&self, role: Role) -> Vec<Principal> {
+        self.roles
+            .iter()
+            .filter(|(_, granted)| granted.0.contains(&role))
+            .map(|(principal, _)| principal)
+            .collect()
+    }
+
+    pub fn grant(
+        &mut self,
+        actor: Principal,
+        principal: Principal,
+        role: Role,
+    ) -> Result<RoleChange, String> {
+        if principal == Principal::anonymous() {
+            return Err("Roles cannot be granted to the anonymous principal.".to_string());
+        }
+        let mut granted = self.roles.get(&principal).unwrap_or_default();
+        if granted.0.contains(&role) {
+            return Err(format!("{} already holds the {:?} role.", principal, role));
+        }
+        granted.0.push(role);
+        self.roles.insert(principal, granted);
+        Ok(self.record(actor, principal, role, RoleAction::Granted))
+    }
+
+    /// Controllers keep [`Role::Controller`] however it is revoked; only the canister's
+    /// controller list takes it away.
+    pub fn revoke(
+        &mut self,
+        actor: Principal,
+        principal: Principal,
+        role: Role,
+    ) -> Result<RoleChange, String> {
+        let mut granted = self.roles.get(&principal).unwrap_or_default();
+        if !granted.0.contains(&role) {
+            return Err(format!("{} does not hold the {:?} role.", principal, role));
+        }
+        granted.0.retain(|held| *held != role);
+        if granted.0.is_empty() {
+            self.roles.remove(&principal);
+        } else {
+            self.roles.insert(principal, granted);
+        }
+        Ok(self.record(actor, principal, role, RoleAction::Revoked))
+    }
+
+    /// Role changes from `start` on, oldest first.
+    pub fn audit_trail(&self, start: u64, limit: u64) -> Vec<RoleChange> {
+        self.audit_trail
+            .range(start..)
+            .take(limit.min(MAX_AUDIT_PAGE_SIZE) as usize)
+            .map(|(_, change)| change)
+            .collect()
+    }
+
+    fn record(
+        &mut self,
+        actor: Principal,
+        principal: Principal,
+        role: Role,
+        action: RoleAction,
+    ) -> RoleChange {
+        let id = self.audit_trail.last_key_value().map_or(0, |(id, _)| id + 1);
+        let change = RoleChange {
+            id,
+            time: ic_cdk::api::time(),
+            actor,
+            principal,
+            role,
+            action,
+        };
+        self.audit_trail.insert(id, change.clone());
+        change
+    }
+}
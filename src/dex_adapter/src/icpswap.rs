@@ -0,0 +1,352 @@
+use candid::{CandidType, Int, Nat, Principal};
+use serde::Deserialize;
+
+use crate::{approve, icrc1_fee, DexAdapter, LiquidityAdded};
+
+pub const TOKEN_STANDARD: &str = "ICRC2";
+/// 0.3% pools, the ICPSwap default for new pairs.
+pub const POOL_FEE: u64 = 3_000;
+/// Widest tick range allowed for the 0.3% tier (tick spacing 60).
+pub const MIN_TICK: i64 = -887_220;
+pub const MAX_TICK: i64 = 887_220;
+
+// CandidType structs for ICPSwap factory and pool calls.
+
+#[derive(CandidType, Deserialize, Debug)]
+pub enum IcpSwapError {
+    CommonError,
+    InternalError(String),
+    UnsupportedToken(String),
+    InsufficientFunds,
+}
+
+/// ICPSwap uses lower-case `ok`/`err` result variants.
+#[derive(CandidType, Deserialize, Debug)]
+pub enum IcpSwapResult<T> {
+    #[serde(rename = "ok")]
+    Ok(T),
+    #[serde(rename = "err")]
+    Err(IcpSwapError),
+}
+
+impl<T> IcpSwapResult<T> {
+    fn into_result(self, method: &str) -> Result<T, String> {
+        match self {
+            IcpSwapResult::Ok(value) => Ok(value),
+            IcpSwapResult::Err(e) => Err(format!("ICPSwap {} failed: {:?}", method, e)),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct Token {
+    pub address: String,
+    pub standard: String,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct GetPoolArgs {
+    pub token0: Token,
+    pub token1: Token,
+    pub fee: Nat,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct CreatePoolArgs {
+    pub token0: Token,
+    pub token1: Token,
+    pub fee: Nat,
+    #[serde(rename = "sqrtPriceX96")]
+    pub sqrt_price_x96: String,
+    pub subnet: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct PoolData {
+    pub key: String,
+    pub token0: Token,
+    pub token1: Token,
+    pub fee: Nat,
+    #[serde(rename = "canisterId")]
+    pub canister_id: Principal,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct QuoteArgs {
+    #[serde(rename = "amountIn")]
+    pub amount_in: String,
+    #[serde(rename = "zeroForOne")]
+    pub zero_for_one: bool,
+    #[serde(rename = "amountOutMinimum")]
+    pub amount_out_minimum: String,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct DepositAndSwapArgs {
+    #[serde(rename = "zeroForOne")]
+    pub zero_for_one: bool,
+    #[serde(rename = "tokenInFee")]
+    pub token_in_fee: Nat,
+    #[serde(rename = "tokenOutFee")]
+    pub token_out_fee: Nat,
+    #[serde(rename = "amountIn")]
+    pub amount_in: String,
+    #[serde(rename = "amountOutMinimum")]
+    pub amount_out_minimum: String,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct DepositArgs {
+    pub token: String,
+    pub amount: Nat,
+    pub fee: Nat,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct WithdrawArgs {
+    pub token: String,
+    pub amount: Nat,
+    pub fee: Nat,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct MintArgs {
+    pub fee: Nat,
+    #[serde(rename = "tickUpper")]
+    pub tick_upper: Int,
+    #[serde(rename = "tickLower")]
+    pub tick_lower: Int,
+    pub token0: String,
+    pub token1: String,
+    #[serde(rename = "amount0Desired")]
+    pub amount0_desired: String,
+    #[serde(rename = "amount1Desired")]
+    pub amount1_desired: String,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct UnusedBalance {
+    pub balance0: Nat,
+    pub balance1: Nat,
+}
+
+/// ICPSwap, addressed through its swap factory. Each pair lives in its own pool canister.
+#[derive(Clone, Debug)]
+pub struct IcpSwap {
+    factory: Principal,
+}
+
+impl IcpSwap {
+    pub fn new(factory: Principal) -> Self {
+        Self { factory }
+    }
+
+    fn token(ledger_id: Principal) -> Token {
+        Token {
+            address: ledger_id.to_text(),
+            standard: TOKEN_STANDARD.to_string(),
+        }
+    }
+
+    async fn get_pool(&self, token_0: Principal, token_1: Principal) -> Result<PoolData, String> {
+        let args = GetPoolArgs {
+            token0: Self::token(token_0),
+            token1: Self::token(token_1),
+            fee: Nat::from(POOL_FEE),
+        };
+        let (result,): (IcpSwapResult<PoolData>,) =
+            ic_cdk::call(self.factory, "getPool", (args,))
+                .await
+                .map_err(|e| format!("Failed to call getPool: {:?}", e))?;
+        result.into_result("getPool")
+    }
+
+    async fn deposit(&self, pool: &PoolData, ledger_id: Principal, amount: Nat) -> Result<(), String> {
+        let fee = icrc1_fee(ledger_id).await?;
+        approve(ledger_id, pool.canister_id, amount.clone()).await?;
+        let args = DepositArgs {
+            token: ledger_id.to_text(),
+            amount,
+            fee,
+        };
+        let (result,): (IcpSwapResult<Nat>,) =
+            ic_cdk::call(pool.canister_id, "depositFrom", (args,))
+                .await
+                .map_err(|e| format!("Failed to call depositFrom: {:?}", e))?;
+        result.into_result("depositFrom").map(|_| ())
+    }
+
+    async fn withdraw(&self, pool: &PoolData, ledger_id: Principal, amount: Nat) -> Result<(), String> {
+        let fee = icrc1_fee(ledger_id).await?;
+        if amount <= fee {
+            return Ok(());
+        }
+        let args = WithdrawArgs {
+            token: ledger_id.to_text(),
+            amount,
+            fee,
+        };
+        let (result,): (IcpSwapResult<Nat>,) =
+            ic_cdk::call(pool.canister_id, "withdraw", (args,))
+                .await
+                .map_err(|e| format!("Failed to call withdraw: {:?}", e))?;
+        result.into_result("withdraw").map(|_| ())
+    }
+}
+
+impl DexAdapter for IcpSwap {
+    async fn quote(
+        &self,
+        pay_token: Principal,
+        pay_amount: Nat,
+        receive_token: Principal,
+    ) -> Result<Nat, String> {
+        let pool = self.get_pool(pay_token, receive_token).await?;
+        let args = QuoteArgs {
+            amount_in: pay_amount.0.to_string(),
+            zero_for_one: pool.token0.address == pay_token.to_text(),
+            amount_out_minimum: "0".to_string(),
+        };
+        let (result,): (IcpSwapResult<Nat>,) = ic_cdk::call(pool.canister_id, "quote", (args,))
+            .await
+            .map_err(|e| format!("Failed to call quote: {:?}", e))?;
+        result.into_result("quote")
+    }
+
+    async fn swap(
+        &self,
+        pay_token: Principal,
+        pay_amount: Nat,
+        receive_token: Principal,
+        min_receive_amount: Nat,
+    ) -> Result<Nat, String> {
+        let pool = self.get_pool(pay_token, receive_token).await?;
+        approve(pay_token, pool.canister_id, pay_amount.clone()).await?;
+
+        let args = DepositAndSwapArgs {
+            zero_for_one: pool.token0.address == pay_token.to_text(),
+            token_in_fee: icrc1_fee(pay_token).await?,
+            token_out_fee: icrc1_fee(receive_token).await?,
+            amount_in: pay_amount.0.to_string(),
+            amount_out_minimum: min_receive_amount.0.to_string(),
+        };
+        let (result,): (IcpSwapResult<Nat>,) =
+            ic_cdk::call(pool.canister_id, "depositFromAndSwap", (args,))
+                .await
+                .map_err(|e| format!("Failed to call depositFromAndSwap: {:?}", e))?;
+        result.into_result("depositFromAndSwap")
+    }
+
+    async fn add_liquidity(
+        &self,
+        token_0: Principal,
+        amount_0: Nat,
+        token_1: Principal,
+        amount_1: Nat,
+    ) -> Result<LiquidityAdded, String> {
+        let pool = self.get_pool(token_0, token_1).await?;
+        // The pool orders its tokens itself; line our amounts up with that order.
+        let (amount0, amount1) = if pool.token0.address == token_0.to_text() {
+            (amount_0.clone(), amount_1.clone())
+        } else {
+            (amount_1.clone(), amount_0.clone())
+        };
+
+        self.deposit(&pool, token_0, amount_0.clone()).await?;
+        self.deposit(&pool, token_1, amount_1.clone()).await?;
+
+        let args = MintArgs {
+            fee: Nat::from(POOL_FEE),
+            tick_upper: Int::from(MAX_TICK),
+            tick_lower: Int::from(MIN_TICK),
+            token0: pool.token0.address.clone(),
+            token1: pool.token1.address.clone(),
+            amount0_desired: amount0.0.to_string(),
+            amount1_desired: amount1.0.to_string(),
+        };
+        let (result,): (IcpSwapResult<Nat>,) = ic_cdk::call(pool.canister_id, "mint", (args,))
+            .await
+            .map_err(|e| format!("Failed to call mint: {:?}", e))?;
+        let position_id = result.into_result("mint")?;
+
+        // Whatever the position did not take stays deposited in the pool; pull it back.
+        let (unused,): (IcpSwapResult<UnusedBalance>,) = ic_cdk::call(
+            pool.canister_id,
+            "getUserUnusedBalance",
+            (ic_cdk::api::id(),),
+        )
+        .await
+        .map_err(|e| format!("Failed to call getUserUnusedBalance: {:?}", e))?;
+        let unused = unused.into_result("getUserUnusedBalance")?;
+        let (unused_0, unused_1) = if pool.token0.address == token_0.to_text() {
+            (unused.balance0, unused.balance1)
+        } else {
+            (unused.balance1, unused.balance0)
+        };
+        self.withdraw(&pool, token_0, unused_0.clone()).await?;
+        self.withdraw(&pool, token_1, unused_1.clone()).await?;
+
+        Ok(LiquidityAdded {
+            amount_0: amount_0.clone() - unused_0.min(amount_0),
+            amount_1: amount_1.clone() - unused_1.min(amount_1),
+            receipt: format!("ICPSwap position #{}", position_id),
+        })
+    }
+
+    /// ICPSwap has no token registry to join; any ICRC-2 ledger can be paired.
+    async fn add_token(&self, _token: Principal) -> Result<(), String> {
+        Ok(())
+    }
+
+    async fn create_pool(
+        &self,
+        token_0: Principal,
+        amount_0: Nat,
+        token_1: Principal,
+        amount_1: Nat,
+    ) -> Result<(), String> {
+        if self.get_pool(token_0, token_1).await.is_err() {
+            // The initial price is token1 per token0, in the factory's token order.
+            let ((pool_token_0, pool_amount_0), (pool_token_1, pool_amount_1)) =
+                pool_order((token_0, &amount_0), (token_1, &amount_1));
+            let sqrt_price_x96 = sqrt_price_x96(pool_amount_0, pool_amount_1)?;
+
+            let args = CreatePoolArgs {
+                token0: Self::token(pool_token_0),
+                token1: Self::token(pool_token_1),
+                fee: Nat::from(POOL_FEE),
+                sqrt_price_x96: sqrt_price_x96.0.to_string(),
+                subnet: None,
+            };
+            let (result,): (IcpSwapResult<PoolData>,) =
+                ic_cdk::call(self.factory, "createPool", (args,))
+                    .await
+                    .map_err(|e| format!("Failed to call createPool: {:?}", e))?;
+            result.into_result("createPool")?;
+        }
+
+        self.add_liquidity(token_0, amount_0, token_1, amount_1)
+            .await
+            .map(|_| ())
+    }
+}
+
+/// Puts a pair in the order the factory keeps pool tokens in: by ledger id text, the
+/// way Motoko compares `Text`. Values travel with their token.
+pub fn pool_order<T>(a: (Principal, T), b: (Principal, T)) -> ((Principal, T), (Principal, T)) {
+    if a.0.to_text() <= b.0.to_text() {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// `sqrt(amount_1 / amount_0) * 2^96`, rounded down: the Q64.96 square root price of a
+/// pool opened with these amounts of its token0 and token1.
+pub fn sqrt_price_x96(amount_0: &Nat, amount_1: &Nat) -> Result<Nat, String> {
+    if amount_0.0 == 0u32.into() {
+        return Err("Cannot price a pool without any token0".to_string());
+    }
+    let ratio_x192 = (amount_1.0.clone() << 192usize) / &amount_0.0;
+    Ok(Nat(ratio_x192.sqrt()))
+}
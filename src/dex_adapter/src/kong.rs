@@ -0,0 +1,284 @@
+use candid::{CandidType, Nat, Principal};
+use serde::{Deserialize, Serialize};
+
+use crate::{approve, DexAdapter, LiquidityAdded};
+
+pub const CHAIN_ID: &str = "IC";
+/// Maximum slippage, in percent, accepted on top of the minimum receive amount.
+pub const MAX_SLIPPAGE_PERCENT: f64 = 0.5;
+
+// CandidType structs for KongSwap calls. Kong returns more fields than listed here;
+// candid drops the ones we do not read.
+
+#[derive(CandidType, Debug, Deserialize, Serialize)]
+pub struct SwapAmountsReply {
+    pub pay_symbol: String,
+    pub pay_amount: Nat,
+    pub receive_symbol: String,
+    pub receive_amount: Nat,
+    pub mid_price: f64,
+    pub price: f64,
+    pub slippage: f64,
+}
+
+#[derive(CandidType, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TxId {
+    BlockIndex(Nat),
+    TransactionHash(String),
+}
+
+#[derive(CandidType, Debug, Clone, Serialize, Deserialize)]
+pub struct SwapArgs {
+    pub pay_token: String,
+    pub pay_amount: Nat,
+    pub pay_tx_id: Option<TxId>,
+    pub receive_token: String,
+    pub receive_amount: Option<Nat>,
+    pub receive_address: Option<String>,
+    pub max_slippage: Option<f64>,
+    pub referred_by: Option<String>,
+}
+
+#[derive(CandidType, Debug, Clone, Serialize, Deserialize)]
+pub struct SwapReply {
+    pub request_id: u64,
+    pub status: String,
+    pub pay_amount: Nat,
+    pub pay_symbol: String,
+    pub receive_amount: Nat,
+    pub receive_symbol: String,
+    pub price: f64,
+    pub slippage: f64,
+}
+
+#[derive(CandidType, Debug, Clone, Serialize, Deserialize)]
+pub struct AddLiquidityArgs {
+    pub token_0: String,
+    pub amount_0: Nat,
+    pub tx_id_0: Option<TxId>,
+    pub token_1: String,
+    pub amount_1: Nat,
+    pub tx_id_1: Option<TxId>,
+}
+
+#[derive(CandidType, Debug, Clone, Serialize, Deserialize)]
+pub struct AddLiquidityReply {
+    pub status: String,
+    pub symbol: String,
+    pub amount_0: Nat,
+    pub amount_1: Nat,
+    pub add_lp_token_amount: Nat,
+}
+
+#[derive(CandidType, Debug, Deserialize)]
+pub struct AddTokenArgs {
+    pub token: String,
+}
+
+#[derive(CandidType, Debug, Deserialize)]
+pub enum AddTokenReply {
+    IC(ICTokenReply),
+}
+
+#[derive(CandidType, Debug, Deserialize)]
+pub struct ICTokenReply {
+    pub token_id: u32,
+    pub chain: String,
+    pub canister_id: String,
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub fee: Nat,
+    pub icrc1: bool,
+    pub icrc2: bool,
+    pub icrc3: bool,
+    pub is_removed: bool,
+}
+
+/// A `tokens` entry. LP tokens are never looked up, so their details are skipped.
+#[derive(CandidType, Debug, Deserialize)]
+pub enum TokensReply {
+    IC(ICTokenReply),
+    LP(candid::Reserved),
+}
+
+#[derive(CandidType, Debug, Clone, Deserialize)]
+pub struct AddPoolArgs {
+    pub token_0: String,
+    pub amount_0: Nat,
+    pub token_1: String,
+    pub amount_1: Nat,
+    pub on_kong: bool,
+}
+
+#[derive(CandidType, Debug, Deserialize)]
+pub struct AddPoolReply {
+    pub pool_id: u32,
+    pub status: String,
+    pub symbol: String,
+    pub add_lp_token_amount: Nat,
+}
+
+/// KongSwap backend. Tokens are addressed as `IC.<ledger id>`.
+#[derive(Clone, Debug)]
+pub struct KongSwap {
+    backend: Principal,
+}
+
+impl KongSwap {
+    pub fn new(backend: Principal) -> Self {
+        Self { backend }
+    }
+
+    fn token(ledger_id: Principal) -> String {
+        format!("{}.{}", CHAIN_ID, ledger_id)
+    }
+
+    /// Whether Kong already lists the token. Kong answers a lookup that matches nothing
+    /// with an error, which reads as not listed.
+    async fn is_listed(&self, token: Principal) -> Result<bool, String> {
+        let (result,): (Result<Vec<TokensReply>, String>,) =
+            ic_cdk::call(self.backend, "tokens", (Some(Self::token(token)),))
+                .await
+                .map_err(|e| format!("Failed to call tokens: {:?}", e))?;
+        let canister_id = token.to_text();
+        Ok(result.unwrap_or_default().iter().any(|reply| {
+            matches!(reply, TokensReply::IC(info) if info.canister_id == canister_id)
+        }))
+    }
+}
+
+impl DexAdapter for KongSwap {
+    async fn quote(
+        &self,
+        pay_token: Principal,
+        pay_amount: Nat,
+        receive_token: Principal,
+    ) -> Result<Nat, String> {
+        let args = (Self::token(pay_token), pay_amount, Self::token(receive_token));
+        let (result,): (Result<SwapAmountsReply, String>,) =
+            ic_cdk::call(self.backend, "swap_amounts", args)
+                .await
+                .map_err(|e| format!("Failed to call swap_amounts: {:?}", e))?;
+        result
+            .map(|reply| reply.receive_amount)
+            .map_err(|e| format!("swap_amounts failed: {}", e))
+    }
+
+    async fn swap(
+        &self,
+        pay_token: Principal,
+        pay_amount: Nat,
+        receive_token: Principal,
+        min_receive_amount: Nat,
+    ) -> Result<Nat, String> {
+        approve(pay_token, self.backend, pay_amount.clone()).await?;
+
+        let swap_args = SwapArgs {
+            pay_token: Self::token(pay_token),
+            pay_amount,
+            pay_tx_id: None, // Not used in the icrc2_approve flow
+            receive_token: Self::token(receive_token),
+            receive_amount: Some(min_receive_amount),
+            receive_address: None, // Defaults to caller (this canister)
+            max_slippage: Some(MAX_SLIPPAGE_PERCENT),
+            referred_by: None,
+        };
+
+        let (result,): (Result<SwapReply, String>,) =
+            ic_cdk::call(self.backend, "swap", (swap_args,))
+                .await
+                .map_err(|e| format!("Failed to call swap on DEX: {:?}", e))?;
+
+        match result {
+            Ok(reply) if reply.status == "Success" => Ok(reply.receive_amount),
+            Ok(reply) => Err(format!("Swap on DEX failed with status: '{}'", reply.status)),
+            Err(e) => Err(format!("Swap on DEX failed: {}", e)),
+        }
+    }
+
+    async fn add_liquidity(
+        &self,
+        token_0: Principal,
+        amount_0: Nat,
+        token_1: Principal,
+        amount_1: Nat,
+    ) -> Result<LiquidityAdded, String> {
+        // We approve the full amounts; the DEX only takes what it needs for the current ratio.
+        approve(token_0, self.backend, amount_0.clone()).await?;
+        approve(token_1, self.backend, amount_1.clone()).await?;
+
+        let args = AddLiquidityArgs {
+            token_0: Self::token(token_0),
+            amount_0,
+            tx_id_0: None,
+            token_1: Self::token(token_1),
+            amount_1,
+            tx_id_1: None,
+        };
+
+        let (result,): (Result<AddLiquidityReply, String>,) =
+            ic_cdk::call(self.backend, "add_liquidity", (args,))
+                .await
+                .map_err(|e| format!("Failed to call add_liquidity: {:?}", e))?;
+
+        let reply = result.map_err(|e| format!("add_liquidity failed: {}", e))?;
+        Ok(LiquidityAdded {
+            amount_0: reply.amount_0,
+            amount_1: reply.amount_1,
+            receipt: format!("{} {}", reply.add_lp_token_amount, reply.symbol),
+        })
+    }
+
+    async fn add_token(&self, token: Principal) -> Result<(), String> {
+        if self.is_listed(token).await? {
+            return Ok(());
+        }
+        let args = AddTokenArgs {
+            token: Self::token(token),
+        };
+
+        let (result,): (Result<AddTokenReply, String>,) =
+            ic_cdk::call(self.backend, "add_token", (args,))
+                .await
+                .map_err(|e| format!("Failed to call add_token: {:?}", e))?;
+
+        match result {
+            Ok(AddTokenReply::IC(token_info)) => {
+                ic_cdk::println!("Token added: {:?}", token_info);
+                Ok(())
+            }
+            // Someone else may have listed it since the lookup.
+            Err(_) if self.is_listed(token).await? => Ok(()),
+            Err(e) => Err(format!("Add token failed: {}", e)),
+        }
+    }
+
+    async fn create_pool(
+        &self,
+        token_0: Principal,
+        amount_0: Nat,
+        token_1: Principal,
+        amount_1: Nat,
+    ) -> Result<(), String> {
+        approve(token_0, self.backend, amount_0.clone()).await?;
+        approve(token_1, self.backend, amount_1.clone()).await?;
+
+        let args = AddPoolArgs {
+            token_0: Self::token(token_0),
+            amount_0,
+            token_1: Self::token(token_1),
+            amount_1,
+            on_kong: true,
+        };
+
+        let (result,): (Result<AddPoolReply, String>,) =
+            ic_cdk::call(self.backend, "add_pool", (args,))
+                .await
+                .map_err(|e| format!("Call failed: {:?}", e))?;
+
+        result
+            .map(|_| ())
+            .map_err(|e| format!("Pool creation failed: {}", e))
+    }
+}
@@ -0,0 +1,210 @@
+//! DEX integrations shared by `icp_swap` and `lbry_fun`.
+//!
+//! Every supported DEX implements [`DexAdapter`]. Canisters store a [`DexKind`] per
+//! token and turn it into a [`Dex`] when they need to talk to the exchange.
+
+use candid::{CandidType, Nat, Principal};
+use icrc_ledger_types::{icrc1::account::Account, icrc2::approve::{ApproveArgs, ApproveError}};
+use serde::Deserialize;
+
+pub mod icpswap;
+pub mod kong;
+
+pub use icpswap::IcpSwap;
+pub use kong::KongSwap;
+
+pub const KONG_BACKEND_CANISTER_ID: &str = "2ipq2-uqaaa-aaaar-qailq-cai";
+pub const ICPSWAP_FACTORY_CANISTER_ID: &str = "4mmnk-kiaaa-aaaag-qbllq-cai";
+
+/// The DEX a launched token is listed on. Chosen once, at token creation.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DexKind {
+    #[default]
+    KongSwap,
+    IcpSwap,
+}
+
+impl DexKind {
+    /// Kong backend or ICPSwap factory on mainnet.
+    pub fn mainnet_canister_id(&self) -> Principal {
+        let canister_id = match self {
+            DexKind::KongSwap => KONG_BACKEND_CANISTER_ID,
+            DexKind::IcpSwap => ICPSWAP_FACTORY_CANISTER_ID,
+        };
+        Principal::from_text(canister_id).unwrap()
+    }
+}
+
+/// Amounts actually taken by the DEX when adding liquidity.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct LiquidityAdded {
+    pub amount_0: Nat,
+    pub amount_1: Nat,
+    /// Human readable description of what was received for the liquidity.
+    pub receipt: String,
+}
+
+/// Operations the canisters need from a DEX. Tokens are always given by ledger id.
+#[allow(async_fn_in_trait)]
+pub trait DexAdapter {
+    /// How much `receive_token` a swap of `pay_amount` would currently return.
+    async fn quote(
+        &self,
+        pay_token: Principal,
+        pay_amount: Nat,
+        receive_token: Principal,
+    ) -> Result<Nat, String>;
+
+    /// Swaps from this canister's balance and returns the amount received.
+    async fn swap(
+        &self,
+        pay_token: Principal,
+        pay_amount: Nat,
+        receive_token: Principal,
+        min_receive_amount: Nat,
+    ) -> Result<Nat, String>;
+
+    async fn add_liquidity(
+        &self,
+        token_0: Principal,
+        amount_0: Nat,
+        token_1: Principal,
+        amount_1: Nat,
+    ) -> Result<LiquidityAdded, String>;
+
+    /// Registers a token with the DEX. Registering a known token is not an error.
+    async fn add_token(&self, token: Principal) -> Result<(), String>;
+
+    /// Creates the `token_0`/`token_1` pool and seeds it with the given amounts.
+    async fn create_pool(
+        &self,
+        token_0: Principal,
+        amount_0: Nat,
+        token_1: Principal,
+        amount_1: Nat,
+    ) -> Result<(), String>;
+}
+
+/// Enum dispatch over the supported adapters.
+#[derive(Clone, Debug)]
+pub enum Dex {
+    KongSwap(KongSwap),
+    IcpSwap(IcpSwap),
+}
+
+impl Dex {
+    pub fn new(kind: DexKind, canister_id: Principal) -> Self {
+        match kind {
+            DexKind::KongSwap => Dex::KongSwap(KongSwap::new(canister_id)),
+            DexKind::IcpSwap => Dex::IcpSwap(IcpSwap::new(canister_id)),
+        }
+    }
+
+    /// The adapter for `kind` talking to the mainnet deployment of that DEX.
+    pub fn mainnet(kind: DexKind) -> Self {
+        Self::new(kind, kind.mainnet_canister_id())
+    }
+}
+
+impl DexAdapter for Dex {
+    async fn quote(
+        &self,
+        pay_token: Principal,
+        pay_amount: Nat,
+        receive_token: Principal,
+    ) -> Result<Nat, String> {
+        match self {
+            Dex::KongSwap(dex) => dex.quote(pay_token, pay_amount, receive_token).await,
+            Dex::IcpSwap(dex) => dex.quote(pay_token, pay_amount, receive_token).await,
+        }
+    }
+
+    async fn swap(
+        &self,
+        pay_token: Principal,
+        pay_amount: Nat,
+        receive_token: Principal,
+        min_receive_amount: Nat,
+    ) -> Result<Nat, String> {
+        match self {
+            Dex::KongSwap(dex) => {
+                dex.swap(pay_token, pay_amount, receive_token, min_receive_amount)
+                    .await
+            }
+            Dex::IcpSwap(dex) => {
+                dex.swap(pay_token, pay_amount, receive_token, min_receive_amount)
+                    .await
+            }
+        }
+    }
+
+    async fn add_liquidity(
+        &self,
+        token_0: Principal,
+        amount_0: Nat,
+        token_1: Principal,
+        amount_1: Nat,
+    ) -> Result<LiquidityAdded, String> {
+        match self {
+            Dex::KongSwap(dex) => dex.add_liquidity(token_0, amount_0, token_1, amount_1).await,
+            Dex::IcpSwap(dex) => dex.add_liquidity(token_0, amount_0, token_1, amount_1).await,
+        }
+    }
+
+    async fn add_token(&self, token: Principal) -> Result<(), String> {
+        match self {
+            Dex::KongSwap(dex) => dex.add_token(token).await,
+            Dex::IcpSwap(dex) => dex.add_token(token).await,
+        }
+    }
+
+    async fn create_pool(
+        &self,
+        token_0: Principal,
+        amount_0: Nat,
+        token_1: Principal,
+        amount_1: Nat,
+    ) -> Result<(), String> {
+        match self {
+            Dex::KongSwap(dex) => dex.create_pool(token_0, amount_0, token_1, amount_1).await,
+            Dex::IcpSwap(dex) => dex.create_pool(token_0, amount_0, token_1, amount_1).await,
+        }
+    }
+}
+
+pub(crate) async fn icrc1_fee(ledger_id: Principal) -> Result<Nat, String> {
+    let (fee,): (Nat,) = ic_cdk::call(ledger_id, "icrc1_fee", ())
+        .await
+        .map_err(|e| format!("Failed to call icrc1_fee on {}: {:?}", ledger_id, e))?;
+    Ok(fee)
+}
+
+/// Lets `spender` pull `amount` plus the ledger fee from this canister.
+pub(crate) async fn approve(
+    ledger_id: Principal,
+    spender: Principal,
+    amount: Nat,
+) -> Result<(), String> {
+    let fee = icrc1_fee(ledger_id).await?;
+    let args = ApproveArgs {
+        from_subaccount: None,
+        spender: Account {
+            owner: spender,
+            subaccount: None,
+        },
+        amount: amount + fee,
+        expected_allowance: None,
+        expires_at: None,
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    };
+
+    let (result,): (Result<Nat, ApproveError>,) =
+        ic_cdk::call(ledger_id, "icrc2_approve", (args,))
+            .await
+            .map_err(|e| format!("Failed to call icrc2_approve on {}: {:?}", ledger_id, e))?;
+    result
+        .map(|_| ())
+        .map_err(|e| format!("Approval on {} failed: {:?}", ledger_id, e))
+}
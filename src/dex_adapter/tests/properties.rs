@@ -0,0 +1,46 @@
+use candid::{Nat, Principal};
+use dex_adapter::icpswap::{pool_order, sqrt_price_x96};
+use proptest::prelude::*;
+
+fn principal() -> impl Strategy<Value = Principal> {
+    prop::collection::vec(any::<u8>(), 0..=29).prop_map(|bytes| Principal::from_slice(&bytes))
+}
+
+#[test]
+fn pool_order_matches_icpswap_pools() {
+    // The ckBTC/ICP pool lists ckBTC as token0.
+    let icp = Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap();
+    let ckbtc = Principal::from_text("mxzaz-hqaaa-aaaar-qaada-cai").unwrap();
+    assert_eq!(pool_order((icp, 1), (ckbtc, 2)), ((ckbtc, 2), (icp, 1)));
+    assert_eq!(pool_order((ckbtc, 2), (icp, 1)), ((ckbtc, 2), (icp, 1)));
+}
+
+#[test]
+fn sqrt_price_x96_of_simple_ratios() {
+    let x96 = Nat::from(1u128 << 96);
+    assert_eq!(sqrt_price_x96(&Nat::from(5u64), &Nat::from(5u64)), Ok(x96.clone()));
+    assert_eq!(sqrt_price_x96(&Nat::from(1u64), &Nat::from(4u64)), Ok(x96.clone() * 2u32));
+    assert_eq!(sqrt_price_x96(&Nat::from(4u64), &Nat::from(1u64)), Ok(Nat::from(1u128 << 95)));
+    assert!(sqrt_price_x96(&Nat::from(0u64), &Nat::from(1u64)).is_err());
+}
+
+proptest! {
+    #[test]
+    fn pool_order_ignores_argument_order(a in principal(), b in principal()) {
+        let ordered = pool_order((a, 0), (b, 1));
+        prop_assert!(ordered.0.0.to_text() <= ordered.1.0.to_text());
+        if a != b {
+            let (first, second) = pool_order((b, 1), (a, 0));
+            prop_assert_eq!((first, second), ordered);
+        }
+    }
+
+    #[test]
+    fn sqrt_price_x96_is_the_floored_root(amount_0 in 1..=u128::MAX, amount_1 in 0..=u128::MAX) {
+        let root = sqrt_price_x96(&Nat::from(amount_0), &Nat::from(amount_1)).unwrap().0;
+        let ratio_x192 = (Nat::from(amount_1).0 << 192usize) / Nat::from(amount_0).0;
+        let next = root.clone() + 1u32;
+        prop_assert!(root.clone() * &root <= ratio_x192);
+        prop_assert!(next.clone() * &next > ratio_x192);
+    }
+}
@@ -1,6 +1,7 @@
-pub const KONG_BACKEND_CANISTER_ID: &str = "2ipq2-uqaaa-aaaar-qailq-cai";
+// Mainnet canisters, used whenever the matching `Configs` field is unset.
 pub const LBRY_FUN_CANISTER_ID: &str = "j362g-ziaaa-aaaap-qkt7q-cai";
 pub const ICP_LEDGER_CANISTER_ID: &str = "ryjl3-tyaaa-aaaaa-aaaba-cai";
+pub const XRC_CANISTER_ID: &str = "uf6dk-hyaaa-aaaaq-qaaaq-cai";
 pub const LIQUIDITY_DEPLOYMENT_CAP_E8S: u64 = 10 * 100_000_000; // 10 ICP
 pub const LIQUIDITY_FEE_PERCENT: u64 = 1; 
 
@@ -8,4 +9,10 @@ pub const LIQUIDITY_FEE_PERCENT: u64 = 1;
 pub const MIN_ICP_FOR_PROVISION_E8S: u64 = 1 * 100_000_000; // 1 ICP
 pub const COOLDOWN_PERIOD_NS: u64 = 1 * 60 * 60 * 1_000_000_000; // 1 hour (for failed checks)
 pub const MIN_PROVISION_INTERVAL_NS: u64 = 1 * 60 * 60 * 1_000_000_000; // 1 hour
-pub const MAX_PROVISION_INTERVAL_NS: u64 = 3 * 60 * 60 * 1_000_000_000; // 3 hours 
\ No newline at end of file
+pub const MAX_PROVISION_INTERVAL_NS: u64 = 3 * 60 * 60 * 1_000_000_000; // 3 hours 
+// Auto-compounding
+pub const MIN_COMPOUND_ICP_E8S: u64 = 1_000_000; // 0.01 ICP, the claim minimum
+
+// Reward distribution
+pub const DISTRIBUTION_CHUNK_SIZE: usize = 500; // stakes settled per message
+pub const EXPIRED_LOCKS_PER_CALL: usize = 500; // expired locks settled per message
@@ -136,6 +136,28 @@ pub enum ExecutionError {
     RateLookupFailed {
         details: String,
     },
+    SlippageExceeded {
+        token: String,
+        min_amount_out: u64,
+        amount_out: u64,
+    },
+    DeadlineExceeded {
+        deadline: u64,
+        now: u64,
+    },
+    // Launch protection caps what each principal can swap or burn early on.
+    LaunchCapExceeded {
+        token: String,
+        cap: u64,
+        used: u64,
+        requested: u64,
+        window_ends_at: Option<u64>,
+    },
+    // The operation is paused, by hand or by the circuit breaker.
+    Paused {
+        operation: String,
+        reason: String,
+    },
 
     // General errors
     StateError(String),
@@ -230,6 +252,32 @@ impl fmt::Display for ExecutionError {
             ExecutionError::RateLookupFailed { details } => {
                 write!(f, "Exchange rate lookup failed: {}", details)
             }
+            ExecutionError::SlippageExceeded { token, min_amount_out, amount_out } => {
+                write!(
+                    f,
+                    "Would receive {} {}, below the minimum of {}",
+                    amount_out,
+                    token,
+                    min_amount_out
+                )
+            }
+            ExecutionError::DeadlineExceeded { deadline, now } => {
+                write!(f, "Deadline {} passed at {}", deadline, now)
+            }
+            ExecutionError::LaunchCapExceeded { token, cap, used, requested, window_ends_at } => {
+                write!(
+                    f,
+                    "Launch cap of {} {} exceeded: {} used, {} requested (window ends at {:?})",
+                    cap,
+                    token,
+                    used,
+                    requested,
+                    window_ends_at
+                )
+            }
+            ExecutionError::Paused { operation, reason } => {
+                write!(f, "{} is paused: {}", operation, reason)
+            }
             ExecutionError::StateError(msg) => { write!(f, "State error: {}", msg) }
             ExecutionError::Unauthorized(msg) => { write!(f, "Unauthorized: {}", msg) }
         }
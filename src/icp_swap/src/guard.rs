@@ -1,6 +1,8 @@
 use candid::Principal;
 
-use crate::STATE;
+use crate::{ROLES, STATE};
+
+access_control::role_guards!(ROLES);
 
 pub struct CallerGuard {
     principal: Principal,
@@ -13,6 +13,9 @@ pub use queries::{*};
 pub mod guard;
 pub use guard::{*};
 
+pub mod roles;
+pub use roles::{*};
+
 pub mod script;
 pub use script::{*};
 pub mod utils;
@@ -1,13 +1,17 @@
 use crate::{
+    quote_primary_mint, simulate_primary_mint,
     storage::*,
     utils::{
-        principal_to_subaccount, DEFAULT_SECONDARY_RATIO, SCALING_FACTOR, STAKING_REWARD_PERCENTAGE,
+        get_fresh_secondary_ratio, launch_usage_of, principal_to_subaccount, DEFAULT_SECONDARY_RATIO, E8S,
+        LOCK_MULTIPLIER_BASE, SCALING_FACTOR,
     },
+    ExecutionError, DEFAULT_MULTIPLICATION_OVERFLOW_ERROR,
 };
 use candid::{CandidType, Principal};
 use ic_cdk::{api::caller, query};
 use ic_ledger_types::AccountIdentifier;
 use serde::Deserialize;
+pub use tokenomics_core::BurnSimulation;
 //swap
 #[query]
 pub async fn caller_subaccount() -> String {
@@ -25,11 +29,17 @@ pub fn get_all_stakes() -> Vec<(Principal, Stake)> {
         // return a Vec of tuples
         stakes_map
             .iter()
-            .map(|(principal, stake)| (principal.clone(), stake.clone())) // Clone to ensure ownership
+            .map(|(principal, stake)| (principal.clone(), with_pending_reward(stake))) // Clone to ensure ownership
             .collect()
     })
 }
 
+/// What `principal` swapped and burned under launch protection.
+#[query]
+pub fn get_launch_usage(principal: Principal) -> LaunchUsage {
+    launch_usage_of(principal)
+}
+
 #[query]
 pub fn get_stakers_count() -> u64 {
     STAKES.with(|stakes| {
@@ -43,6 +53,12 @@ pub fn get_stake(principal: Principal) -> Option<Stake> {
         let stakes_map = stakes.borrow();
         stakes_map.get(&principal)
     })
+    .map(with_pending_reward)
+}
+
+#[query]
+pub fn get_stake_positions(principal: Principal) -> Vec<StakePosition> {
+    get_stake_positions_of(principal)
 }
 
 #[query]
@@ -53,7 +69,7 @@ pub fn get_total_unclaimed_icp_reward() -> u64 {
 
 #[query]
 pub fn get_current_staking_reward_percentage() -> String {
-    format!("Staking percentage {}", STAKING_REWARD_PERCENTAGE / 100)
+    format!("Staking percentage {}", get_config().fee_schedule().release_rate_bps / 100)
 }
 
 #[query]
@@ -66,6 +82,64 @@ pub fn get_current_secondary_ratio() -> u64 {
     }
 }
 
+/// What a burn pays out; also the per-token minimum `burn_secondary` accepts.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct BurnOutput {
+    pub icp: u64,
+    pub primary: u64,
+}
+
+/// Secondary (e8s) `swap` would mint for `amount_icp` now.
+#[query]
+pub fn quote_swap(amount_icp: u64) -> Result<u64, ExecutionError> {
+    let ratio = get_fresh_secondary_ratio(ic_cdk::api::time() / 1_000_000_000)?;
+    amount_icp.checked_mul(ratio).ok_or_else(|| ExecutionError::MultiplicationOverflow {
+        operation: DEFAULT_MULTIPLICATION_OVERFLOW_ERROR.to_string(),
+        details: format!("amount_icp: {} with ratio: {}", amount_icp, ratio),
+    })
+}
+
+/// ICP (e8s) and primary `burn_secondary` would pay out for `amount_secondary` now.
+#[query(composite = true)]
+pub async fn quote_burn_secondary(amount_secondary: u64) -> Result<BurnOutput, ExecutionError> {
+    let icp = icp_for_secondary_burn(amount_secondary)?;
+    let primary = quote_primary_mint(amount_secondary).await?;
+    Ok(BurnOutput { icp, primary })
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct BurnSecondarySimulation {
+    pub icp: u64,
+    pub primary: BurnSimulation,
+}
+
+/// ICP `burn_secondary` would return for `amount_secondary` now, with the
+/// per-threshold breakdown of the primary tokenomics would mint.
+#[query(composite = true)]
+pub async fn simulate_burn_secondary(
+    amount_secondary: u64,
+) -> Result<BurnSecondarySimulation, ExecutionError> {
+    let icp = icp_for_secondary_burn(amount_secondary)?;
+    let primary = simulate_primary_mint(amount_secondary).await?;
+    Ok(BurnSecondarySimulation { icp, primary })
+}
+
+fn icp_for_secondary_burn(amount_secondary: u64) -> Result<u64, ExecutionError> {
+    amount_secondary
+        .checked_mul(E8S)
+        .and_then(|amount| amount.checked_div(get_current_secondary_ratio().checked_mul(2)?))
+        .ok_or_else(|| ExecutionError::MultiplicationOverflow {
+            operation: DEFAULT_MULTIPLICATION_OVERFLOW_ERROR.to_string(),
+            details: format!("amount_secondary: {} with {}", amount_secondary, E8S),
+        })
+}
+
+/// XRC readings behind the current secondary ratio, oldest first.
+#[query]
+pub fn get_ratio_history() -> Vec<PriceReading> {
+    get_price_readings()
+}
+
 #[query]
 pub fn get_user_archive_balance(principal: Principal) -> Option<ArchiveBalance> {
     ARCHIVED_TRANSACTION_LOG.with(|trx| {
@@ -115,6 +189,35 @@ pub fn get_all_apy_values() -> Vec<(u32, u128)> {
         values
     })
 }
+/// `get_all_apy_values` scaled by each lock period's multiplier, i.e. the reward
+/// per primary staked with that lock.
+#[query]
+pub fn get_all_apy_values_by_lock_period() -> Vec<(LockPeriod, Vec<(u32, u128)>)> {
+    let config = get_config();
+    let values = get_all_apy_values();
+    LockPeriod::ALL
+        .iter()
+        .map(|lock_period| {
+            let multiplier = config.lock_multiplier(Some(*lock_period)) as u128;
+            let scaled = values
+                .iter()
+                .map(|(day, value)| (*day, value * multiplier / LOCK_MULTIPLIER_BASE as u128))
+                .collect();
+            (*lock_period, scaled)
+        })
+        .collect()
+}
+
+#[query]
+pub fn get_distribution_progress() -> DistributionRound {
+    get_distribution_round()
+}
+
+#[query]
+pub fn get_reward_index_state() -> RewardIndex {
+    get_reward_index()
+}
+
 #[query]
 pub fn get_scaling_factor() -> u128 {
     return SCALING_FACTOR;
@@ -125,6 +228,24 @@ pub fn get_lp_treasury_balance() -> u64 {
     LP_TREASURY.with(|cell| *cell.borrow().get())
 }
 
+/// Which operations are paused and why.
+#[query]
+pub fn get_paused_operations() -> PauseState {
+    get_pause_state()
+}
+
+/// Compounded rewards held after a failed swap, waiting for a controller to resolve them.
+#[query]
+pub fn get_pending_compounds() -> Vec<PendingCompound> {
+    PENDING_COMPOUNDS.with(|pending| pending.borrow().iter().map(|(_, p)| p).collect())
+}
+
+/// The creator share credited by reward runs, and how much of it was claimed.
+#[query]
+pub fn get_creator_revenue() -> CreatorRevenue {
+    creator_revenue()
+}
+
 #[derive(CandidType, Deserialize)]
 pub struct PaginatedLogs {
     logs: Vec<Log>,
@@ -0,0 +1,27 @@
+pub use access_control::{Role, RoleAction, RoleChange};
+use candid::Principal;
+use ic_cdk::{caller, update};
+
+use crate::{guard::*, utils::register_info_log, ExecutionError, ROLES};
+
+#[update(guard = "is_controller_role")]
+fn grant_role(principal: Principal, role: Role) -> Result<RoleChange, ExecutionError> {
+    let caller = caller();
+    let change = ROLES
+        .with(|roles| roles.borrow_mut().grant(caller, principal, role))
+        .map_err(|e| ExecutionError::new_with_log(caller, "grant_role", ExecutionError::StateError(e)))?;
+    register_info_log(caller, "grant_role", &format!("Granted {:?} to {}", role, principal));
+    Ok(change)
+}
+
+#[update(guard = "is_controller_role")]
+fn revoke_role(principal: Principal, role: Role) -> Result<RoleChange, ExecutionError> {
+    let caller = caller();
+    let change = ROLES
+        .with(|roles| roles.borrow_mut().revoke(caller, principal, role))
+        .map_err(|e| ExecutionError::new_with_log(caller, "revoke_role", ExecutionError::StateError(e)))?;
+    register_info_log(caller, "revoke_role", &format!("Revoked {:?} from {}", role, principal));
+    Ok(change)
+}
+
+access_control::role_query_endpoints!(ROLES);
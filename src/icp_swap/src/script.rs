@@ -1,13 +1,18 @@
 use candid::{CandidType, Principal};
+use dex_adapter::DexKind;
 use ic_cdk::{self, caller, init, post_upgrade, update};
 use serde::Deserialize;
 use std::time::Duration;
+use tokenomics_core::{FeeSchedule, LaunchProtection};
+
+use access_control::{validate_external_canister, Role};
 
 use crate::{
-    distribute_reward, get_icp_rate_in_cents, schedule_liquidity_provision, utils::register_info_log, ArchiveBalance, Configs, DailyValues, SecondaryRatio, Stake, APY, ARCHIVED_TRANSACTION_LOG, CONFIGS, DISTRIBUTION_INTERVALS, SECONDARY_RATIO, STAKES, TOTAL_ARCHIVED_BALANCE, TOTAL_UNCLAIMED_ICP_REWARD
+    compound_rewards, has_role, migrate_creator_role, migrate_installed_at, constants::ICP_LEDGER_CANISTER_ID, distribute_reward, get_config, get_distribution_round, get_icp_rate_in_cents, migrate_reward_checkpoints, migrate_stake_positions, schedule_liquidity_provision, utils::{pause_operations, register_info_log, BASIS_POINTS, LOCK_MULTIPLIER_BASE, MAX_LOCK_MULTIPLIER, MAX_ORACLE_WINDOW_SIZE}, get_pause_state, set_pause_state, ArchiveBalance, CircuitBreakerConfig, Configs, PausableOp, PauseReason, PauseState, LockMultipliers, OracleConfig, DailyValues, ExecutionError, SecondaryRatio, Stake, APY, ARCHIVED_TRANSACTION_LOG, CONFIGS, DEFAULT_UNAUTHORIZED_ERROR, DISTRIBUTION_INTERVALS, SECONDARY_RATIO, STAKES, TOTAL_ARCHIVED_BALANCE, TOTAL_UNCLAIMED_ICP_REWARD
 };
 
 pub const REWARD_DISTRIBUTION_INTERVAL: Duration = Duration::from_secs(60 * 60); // 1 hour.
+pub const DISTRIBUTION_CHUNK_INTERVAL: Duration = Duration::from_secs(1);
 pub const PRICE_FETCH_INTERVAL: Duration = Duration::from_secs(1 * 24 * 60 * 60); // 1 days in seconds
 
 #[derive(CandidType, Deserialize, Clone, Default)]
@@ -23,6 +28,15 @@ pub struct InitArgs {
     pub secondary_token_id: Option<Principal>,
     pub tokenomics_canister_id: Option<Principal>,
     pub icp_ledger_id: Option<Principal>,
+    pub dex: Option<DexKind>,
+    pub dex_canister_id: Option<Principal>,
+    pub lbry_fun_canister_id: Option<Principal>,
+    pub xrc_canister_id: Option<Principal>,
+    pub lock_multipliers: Option<LockMultipliers>,
+    pub oracle: Option<OracleConfig>,
+    pub launch_protection: Option<LaunchProtection>,
+    pub fee_schedule: Option<FeeSchedule>,
+    pub creator: Option<Principal>,
 }
 
 // Function to initialize global states from InitArgs.
@@ -35,6 +49,8 @@ fn initialize_globals(args: InitArgs) {
                 stakes_map.insert(principal, stake);
             }
         });
+        migrate_stake_positions();
+        migrate_reward_checkpoints();
     }
 
     if let Some(total_unclaimed_icp_reward) = args.total_unclaimed_icp_reward {
@@ -80,7 +96,7 @@ fn initialize_globals(args: InitArgs) {
     }
     // Set ICP ledger ID (defaults to our standard ledger if not provided)
     let icp_ledger_id = args.icp_ledger_id
-        .unwrap_or_else(|| Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap());
+        .unwrap_or_else(|| Principal::from_text(ICP_LEDGER_CANISTER_ID).unwrap());
 
     CONFIGS.with(|c| {
         let mut config = c.borrow_mut();
@@ -89,8 +105,22 @@ fn initialize_globals(args: InitArgs) {
             secondary_token_id: args.secondary_token_id.unwrap_or(Principal::anonymous()),
             tokenomics_cansiter_id: args.tokenomics_canister_id.unwrap_or(Principal::anonymous()),
             icp_ledger_id,
+            dex: args.dex,
+            dex_canister_id: args.dex_canister_id,
+            lbry_fun_canister_id: args.lbry_fun_canister_id,
+            xrc_canister_id: args.xrc_canister_id,
+            lock_multipliers: args.lock_multipliers,
+            oracle: args.oracle,
+            launch_protection: args.launch_protection,
+            liquidity_provided_at: None,
+            installed_at: Some(ic_cdk::api::time()),
+            fee_schedule: args.fee_schedule,
+            creator: args.creator,
+            creator_payout: None,
+            circuit_breaker: None,
         }).unwrap();
-    })
+    });
+    migrate_creator_role();
 }
 
 #[init]
@@ -142,6 +172,19 @@ fn init(args: Option<InitArgs>) {
                     "primary_token_id, secondary_token_id and tokenomics_canister_id  is required",
                 );
             }
+            if let Some(protection) = &init_args.launch_protection {
+                if let Err(e) = protection.validate() {
+                    ic_cdk::trap(&e);
+                }
+            }
+            if let Some(fee_schedule) = &init_args.fee_schedule {
+                if let Err(e) = fee_schedule.validate() {
+                    ic_cdk::trap(&e);
+                }
+                if fee_schedule.creator_share_bps > 0 && init_args.creator.is_none() {
+                    ic_cdk::trap("A creator share needs a creator to pay");
+                }
+            }
             register_info_log(
                 caller(),
                 "init",
@@ -166,8 +209,311 @@ fn init(args: Option<InitArgs>) {
     register_info_log(caller(), "init", "Initialization process completed");
 }
 
+/// External canisters a controller can repoint, e.g. at a local replica or PocketIC mocks.
+/// Unset fields are left as they are.
+#[derive(CandidType, Deserialize, Clone, Default)]
+pub struct ExternalCanisters {
+    pub icp_ledger_id: Option<Principal>,
+    pub dex: Option<DexKind>,
+    pub dex_canister_id: Option<Principal>,
+    pub lbry_fun_canister_id: Option<Principal>,
+    pub xrc_canister_id: Option<Principal>,
+}
+
+#[update]
+fn update_external_canisters(args: ExternalCanisters) -> Result<Configs, ExecutionError> {
+    let caller = caller();
+    if !has_role(caller, Role::Controller) {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "update_external_canisters",
+            ExecutionError::Unauthorized(DEFAULT_UNAUTHORIZED_ERROR.to_string()),
+        ));
+    }
+
+    for (field, canister_id) in [
+        ("icp_ledger_id", args.icp_ledger_id),
+        ("dex_canister_id", args.dex_canister_id),
+        ("lbry_fun_canister_id", args.lbry_fun_canister_id),
+        ("xrc_canister_id", args.xrc_canister_id),
+    ] {
+        if let Some(canister_id) = canister_id {
+            validate_external_canister(field, canister_id).map_err(|e| {
+                ExecutionError::new_with_log(caller, "update_external_canisters", ExecutionError::StateError(e))
+            })?;
+        }
+    }
+
+    let config = CONFIGS.with(|c| {
+        let mut config = c.borrow().get().clone();
+        if let Some(icp_ledger_id) = args.icp_ledger_id {
+            config.icp_ledger_id = icp_ledger_id;
+        }
+        if args.dex.is_some() {
+            config.dex = args.dex;
+        }
+        if args.dex_canister_id.is_some() {
+            config.dex_canister_id = args.dex_canister_id;
+        }
+        if args.lbry_fun_canister_id.is_some() {
+            config.lbry_fun_canister_id = args.lbry_fun_canister_id;
+        }
+        if args.xrc_canister_id.is_some() {
+            config.xrc_canister_id = args.xrc_canister_id;
+        }
+        c.borrow_mut().set(config.clone()).map(|_| config).map_err(|_| {
+            ExecutionError::StateError("Failed to update configs".to_string())
+        })
+    })?;
+
+    register_info_log(
+        caller,
+        "update_external_canisters",
+        &format!("External canisters updated: {:?}", config),
+    );
+    Ok(config)
+}
+
+
+/// Starts the launch-protection window. Called by lbry_fun once the token's pool is live,
+/// or by an operator. The window can only be started once.
+#[update]
+fn set_liquidity_provided_at(time: u64) -> Result<Configs, ExecutionError> {
+    let caller = caller();
+    if caller != get_config().lbry_fun_canister_id() && !has_role(caller, Role::Operator) {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "set_liquidity_provided_at",
+            ExecutionError::Unauthorized(DEFAULT_UNAUTHORIZED_ERROR.to_string()),
+        ));
+    }
+
+    let config = CONFIGS.with(|c| {
+        let mut config = c.borrow().get().clone();
+        // Setting it again would restart the window. A retry of the same call is fine.
+        match config.liquidity_provided_at {
+            Some(provided_at) if provided_at == time => return Ok(config),
+            Some(provided_at) => {
+                return Err(ExecutionError::StateError(format!(
+                    "Liquidity was already provided at {}",
+                    provided_at
+                )))
+            }
+            None => {}
+        }
+        config.liquidity_provided_at = Some(time);
+        c.borrow_mut().set(config.clone()).map(|_| config).map_err(|_| {
+            ExecutionError::StateError("Failed to update configs".to_string())
+        })
+    })?;
+
+    register_info_log(
+        caller,
+        "set_liquidity_provided_at",
+        &format!("Liquidity provided at {}", time),
+    );
+    Ok(config)
+}
+
+/// Sets where `claim_creator_revenue` pays out, or back to the creator with `None`.
+/// Only the creator the token launched with can call this, and only while they hold the
+/// Creator role: a controller can take it away, but granting it never redirects revenue.
+#[update]
+fn set_creator_payout(payout: Option<Principal>) -> Result<Configs, ExecutionError> {
+    let caller = caller();
+    if get_config().creator != Some(caller) || !has_role(caller, Role::Creator) {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "set_creator_payout",
+            ExecutionError::Unauthorized(DEFAULT_UNAUTHORIZED_ERROR.to_string()),
+        ));
+    }
+    if payout == Some(Principal::anonymous()) {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "set_creator_payout",
+            ExecutionError::StateError("Payout principal cannot be anonymous".to_string()),
+        ));
+    }
+
+    let config = CONFIGS.with(|c| {
+        let mut config = c.borrow().get().clone();
+        config.creator_payout = payout;
+        c.borrow_mut().set(config.clone()).map(|_| config).map_err(|_| {
+            ExecutionError::StateError("Failed to update configs".to_string())
+        })
+    })?;
+
+    register_info_log(
+        caller,
+        "set_creator_payout",
+        &format!("Creator payout set to {:?}", payout),
+    );
+    Ok(config)
+}
+
+/// Pauses or resumes `op`. Resuming also clears the circuit breaker's count of failed
+/// transfers. Callable by operators and lbry_fun.
+#[update]
+fn set_paused(op: PausableOp, paused: bool) -> Result<PauseState, ExecutionError> {
+    let caller = caller();
+    if caller != get_config().lbry_fun_canister_id() && !has_role(caller, Role::Operator) {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "set_paused",
+            ExecutionError::Unauthorized(DEFAULT_UNAUTHORIZED_ERROR.to_string()),
+        ));
+    }
+
+    if paused {
+        pause_operations(&[op], caller, PauseReason::Manual)
+            .map_err(|e| ExecutionError::new_with_log(caller, "set_paused", e))?;
+    } else {
+        let mut state = get_pause_state();
+        state.paused.remove(&op);
+        state.consecutive_transfer_failures = 0;
+        set_pause_state(state)
+            .map_err(|e| ExecutionError::new_with_log(caller, "set_paused", e))?;
+    }
+
+    register_info_log(
+        caller,
+        "set_paused",
+        &format!("{:?} {}", op, if paused { "paused" } else { "resumed" }),
+    );
+    Ok(get_pause_state())
+}
+
+#[update]
+fn update_circuit_breaker(circuit_breaker: CircuitBreakerConfig) -> Result<Configs, ExecutionError> {
+    let caller = caller();
+    if !has_role(caller, Role::Controller) {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "update_circuit_breaker",
+            ExecutionError::Unauthorized(DEFAULT_UNAUTHORIZED_ERROR.to_string()),
+        ));
+    }
+    if circuit_breaker.max_price_move_bps == 0 || circuit_breaker.max_transfer_failures == 0 {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "update_circuit_breaker",
+            ExecutionError::StateError(
+                "max_price_move_bps and max_transfer_failures must be greater than 0".to_string(),
+            ),
+        ));
+    }
+
+    let config = CONFIGS.with(|c| {
+        let mut config = c.borrow().get().clone();
+        config.circuit_breaker = Some(circuit_breaker);
+        c.borrow_mut().set(config.clone()).map(|_| config).map_err(|_| {
+            ExecutionError::StateError("Failed to update configs".to_string())
+        })
+    })?;
+
+    register_info_log(
+        caller,
+        "update_circuit_breaker",
+        &format!("Circuit breaker updated: {:?}", config.circuit_breaker),
+    );
+    Ok(config)
+}
+
+#[update]
+fn update_lock_multipliers(multipliers: LockMultipliers) -> Result<Configs, ExecutionError> {
+    let caller = caller();
+    if !has_role(caller, Role::Controller) {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "update_lock_multipliers",
+            ExecutionError::Unauthorized(DEFAULT_UNAUTHORIZED_ERROR.to_string()),
+        ));
+    }
+    // Longer locks may not weigh less, and no lock may weigh less than none.
+    if multipliers.thirty_days < LOCK_MULTIPLIER_BASE
+        || multipliers.ninety_days < multipliers.thirty_days
+        || multipliers.one_year < multipliers.ninety_days
+        || multipliers.one_year > MAX_LOCK_MULTIPLIER
+    {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "update_lock_multipliers",
+            ExecutionError::StateError(format!(
+                "Multipliers must increase with the lock period and stay within {}..={} basis points",
+                LOCK_MULTIPLIER_BASE, MAX_LOCK_MULTIPLIER
+            )),
+        ));
+    }
+
+    let config = CONFIGS.with(|c| {
+        let mut config = c.borrow().get().clone();
+        config.lock_multipliers = Some(multipliers);
+        c.borrow_mut().set(config.clone()).map(|_| config).map_err(|_| {
+            ExecutionError::StateError("Failed to update configs".to_string())
+        })
+    })?;
+
+    register_info_log(
+        caller,
+        "update_lock_multipliers",
+        &format!("Lock multipliers updated: {:?}", config.lock_multipliers),
+    );
+    Ok(config)
+}
+
+#[update]
+fn update_oracle_config(oracle: OracleConfig) -> Result<Configs, ExecutionError> {
+    let caller = caller();
+    if !has_role(caller, Role::Controller) {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "update_oracle_config",
+            ExecutionError::Unauthorized(DEFAULT_UNAUTHORIZED_ERROR.to_string()),
+        ));
+    }
+    // A zero change cap would freeze the ratio, and a staleness window below the
+    // fetch interval would pause swaps between fetches.
+    if oracle.window_size == 0
+        || oracle.window_size > MAX_ORACLE_WINDOW_SIZE
+        || oracle.max_change_bps == 0
+        || oracle.max_change_bps > BASIS_POINTS
+        || oracle.max_staleness_secs < PRICE_FETCH_INTERVAL.as_secs()
+    {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "update_oracle_config",
+            ExecutionError::StateError(format!(
+                "window_size must be 1..={}, max_change_bps 1..={} and max_staleness_secs at least {}",
+                MAX_ORACLE_WINDOW_SIZE,
+                BASIS_POINTS,
+                PRICE_FETCH_INTERVAL.as_secs()
+            )),
+        ));
+    }
+
+    let config = CONFIGS.with(|c| {
+        let mut config = c.borrow().get().clone();
+        config.oracle = Some(oracle);
+        c.borrow_mut().set(config.clone()).map(|_| config).map_err(|_| {
+            ExecutionError::StateError("Failed to update configs".to_string())
+        })
+    })?;
+
+    register_info_log(
+        caller,
+        "update_oracle_config",
+        &format!("Oracle config updated: {:?}", config.oracle),
+    );
+    Ok(config)
+}
+
 #[post_upgrade]
 fn post_upgrade() {
+    migrate_stake_positions();
+    migrate_reward_checkpoints();
+    migrate_creator_role();
+    migrate_installed_at();
     setup_timers();
     register_info_log(
         caller(),
@@ -197,12 +543,31 @@ fn setup_timers() {
 }
 
 async fn distribute_reward_wrapper() {
-    match distribute_reward().await {
-        Ok(_) => (),
+    let distributing = match distribute_reward().await {
+        Ok(_) => true,
+        Err(e) => {
+            register_info_log(
+                caller(),
+                "distribute_reward_wrapper",
+                &format!("Error distributing rewards: {}", e),
+            );
+            false
+        }
+    };
+    // Keep settling until the round is done, one chunk per message.
+    if distributing && get_distribution_round().in_progress {
+        ic_cdk_timers::set_timer(DISTRIBUTION_CHUNK_INTERVAL, || {
+            ic_cdk::spawn(distribute_reward_wrapper())
+        });
+        return;
+    }
+    // Also picks up rewards left over when an earlier compounding swap failed.
+    match compound_rewards().await {
+        Ok(msg) => register_info_log(caller(), "distribute_reward_wrapper", &msg),
         Err(e) => register_info_log(
             caller(),
             "distribute_reward_wrapper",
-            &format!("Error distributing rewards: {}", e),
+            &format!("Error compounding rewards: {}", e),
         ),
     }
 }
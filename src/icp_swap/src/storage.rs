@@ -8,11 +8,24 @@ use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
 use ic_stable_structures::{ DefaultMemoryImpl, Storable};
 use std::borrow::Cow;
 use std::cell::RefCell;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use ic_cdk_timers::TimerId;
+use access_control::{Role, RoleStore};
+use dex_adapter::{Dex, DexKind};
+use tokenomics_core::{FeeSchedule, LaunchProtection};
+pub use tokenomics_core::RewardIndex;
 
-use crate::utils::DEFAULT_SECONDARY_RATIO;
-use crate::ExecutionError;
+use crate::constants::{
+    EXPIRED_LOCKS_PER_CALL, ICP_LEDGER_CANISTER_ID, LBRY_FUN_CANISTER_ID, XRC_CANISTER_ID,
+};
+
+use crate::utils::{
+    DEFAULT_BREAKER_MAX_PRICE_MOVE_BPS, DEFAULT_BREAKER_MAX_TRANSFER_FAILURES,
+    DEFAULT_MAX_PRICE_STALENESS_SECS, DEFAULT_MAX_RATIO_CHANGE_BPS, DEFAULT_NINETY_DAYS_MULTIPLIER,
+    DEFAULT_ONE_YEAR_MULTIPLIER, DEFAULT_ORACLE_WINDOW_SIZE, DEFAULT_SECONDARY_RATIO,
+    DEFAULT_THIRTY_DAYS_MULTIPLIER, LOCK_MULTIPLIER_BASE,
+};
+use crate::{get_config, ExecutionError};
 
 type Memory = VirtualMemory<DefaultMemoryImpl>;
 // Memory identifiers for each variable
@@ -29,6 +42,18 @@ pub const LOGS_COUNTER_ID: MemoryId = MemoryId::new(9);
 pub const CONFIGS_MEM_ID: MemoryId = MemoryId::new(10);
 pub const LP_TREASURY_MEM_ID: MemoryId = MemoryId::new(11);
 pub const TREASURY_STATE_MEM_ID: MemoryId = MemoryId::new(12);
+pub const STAKE_POSITIONS_MEM_ID: MemoryId = MemoryId::new(13);
+pub const NEXT_POSITION_ID_MEM_ID: MemoryId = MemoryId::new(14);
+pub const REWARD_INDEX_MEM_ID: MemoryId = MemoryId::new(15);
+pub const LOCK_EXPIRIES_MEM_ID: MemoryId = MemoryId::new(16);
+pub const DISTRIBUTION_ROUND_MEM_ID: MemoryId = MemoryId::new(17);
+pub const PRICE_READINGS_MEM_ID: MemoryId = MemoryId::new(18);
+pub const LAUNCH_USAGE_MEM_ID: MemoryId = MemoryId::new(19);
+pub const CREATOR_REVENUE_MEM_ID: MemoryId = MemoryId::new(20);
+pub const PAUSE_STATE_MEM_ID: MemoryId = MemoryId::new(21);
+pub const ROLES_MEM_ID: MemoryId = MemoryId::new(22);
+pub const ROLE_AUDIT_MEM_ID: MemoryId = MemoryId::new(23);
+pub const PENDING_COMPOUNDS_MEM_ID: MemoryId = MemoryId::new(24);
 
 thread_local! {
     static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(
@@ -78,11 +103,88 @@ thread_local! {
                 primary_token_id:Principal::anonymous(),
                 secondary_token_id: Principal::anonymous(),
                 tokenomics_cansiter_id:Principal::anonymous(),
-                icp_ledger_id: Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap(),
+                icp_ledger_id: Principal::from_text(ICP_LEDGER_CANISTER_ID).unwrap(),
+                dex: None,
+                dex_canister_id: None,
+                lbry_fun_canister_id: None,
+                xrc_canister_id: None,
+                lock_multipliers: None,
+                oracle: None,
+                launch_protection: None,
+                liquidity_provided_at: None,
+                installed_at: None,
+                fee_schedule: None,
+                creator: None,
+                creator_payout: None,
+                circuit_breaker: None,
             }
         ).unwrap()
     );
 
+    // Individual deposits behind each `STAKES` entry, whose amount is their sum.
+    pub static STAKE_POSITIONS: RefCell<StableBTreeMap<(Principal, u64), StakePosition, Memory>> = RefCell::new(
+        StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(STAKE_POSITIONS_MEM_ID)))
+    );
+    pub static NEXT_POSITION_ID: RefCell<StableCell<u64, Memory>> = RefCell::new(
+        StableCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(NEXT_POSITION_ID_MEM_ID)), 0).unwrap()
+    );
+
+    pub static REWARD_INDEX: RefCell<StableCell<RewardIndex, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(REWARD_INDEX_MEM_ID)),
+            RewardIndex::default()
+        ).unwrap()
+    );
+    // (unlock_time, principal) of every lock, so expired boosts can be settled in order.
+    pub static LOCK_EXPIRIES: RefCell<StableBTreeMap<(u64, Principal), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(LOCK_EXPIRIES_MEM_ID)))
+    );
+
+    pub static DISTRIBUTION_ROUND: RefCell<StableCell<DistributionRound, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(DISTRIBUTION_ROUND_MEM_ID)),
+            DistributionRound::default()
+        ).unwrap()
+    );
+
+    // Ring buffer of the latest XRC readings, keyed by a sequence number.
+    pub static PRICE_READINGS: RefCell<StableBTreeMap<u64, PriceReading, Memory>> = RefCell::new(
+        StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(PRICE_READINGS_MEM_ID)))
+    );
+
+    // What each principal swapped and burned while launch protection was active.
+    pub static LAUNCH_USAGE: RefCell<StableBTreeMap<Principal, LaunchUsage, Memory>> = RefCell::new(
+        StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(LAUNCH_USAGE_MEM_ID)))
+    );
+
+    // The creator share of reward runs, held until the creator claims it.
+    pub static CREATOR_REVENUE: RefCell<StableCell<CreatorRevenue, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CREATOR_REVENUE_MEM_ID)),
+            CreatorRevenue::default()
+        ).unwrap()
+    );
+
+    // Which operations are paused, by hand or by the circuit breaker.
+    pub static PAUSE_STATE: RefCell<StableCell<PauseState, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(PAUSE_STATE_MEM_ID)),
+            PauseState::default()
+        ).unwrap()
+    );
+
+    // Compounded rewards whose DEX swap failed in a way that may have taken the ICP anyway.
+    pub static PENDING_COMPOUNDS: RefCell<StableBTreeMap<Principal, PendingCompound, Memory>> = RefCell::new(
+        StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(PENDING_COMPOUNDS_MEM_ID)))
+    );
+
+    pub static ROLES: RefCell<RoleStore<Memory>> = RefCell::new(
+        RoleStore::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(ROLES_MEM_ID)),
+            MEMORY_MANAGER.with(|m| m.borrow().get(ROLE_AUDIT_MEM_ID)),
+        )
+    );
+
     pub static LP_TREASURY: RefCell<StableCell<u64, Memory>> = RefCell::new(
         StableCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(LP_TREASURY_MEM_ID)), 0).unwrap()
     );
@@ -146,11 +248,368 @@ pub fn withdraw_from_lp_treasury(amount: u64) -> Result<(), ExecutionError> {
     })
 }
 
+pub fn creator_revenue() -> CreatorRevenue {
+    CREATOR_REVENUE.with(|cell| cell.borrow().get().clone())
+}
+
+pub fn set_creator_revenue(revenue: CreatorRevenue) -> Result<(), ExecutionError> {
+    CREATOR_REVENUE.with(|cell| {
+        cell.borrow_mut().set(revenue).map_err(|_| ExecutionError::StateError("Failed to set creator revenue".to_string()))?;
+        Ok(())
+    })
+}
+
+pub fn add_creator_revenue(amount: u64) -> Result<(), ExecutionError> {
+    let mut revenue = creator_revenue();
+    let overflow = || ExecutionError::AdditionOverflow {
+        operation: "add_creator_revenue".to_string(),
+        details: "Overflow when adding to creator revenue".to_string()
+    };
+    revenue.accrued = revenue.accrued.checked_add(amount).ok_or_else(overflow)?;
+    revenue.unclaimed = revenue.unclaimed.checked_add(amount).ok_or_else(overflow)?;
+    set_creator_revenue(revenue)
+}
+
+/// Holds `rewards` for review, adding to what a staker already has held.
+pub fn hold_pending_compounds(rewards: &[(Principal, u64)], now: u64) {
+    PENDING_COMPOUNDS.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        for (principal, reward_icp) in rewards {
+            let held = pending.get(principal).map_or(0, |p| p.reward_icp);
+            pending.insert(
+                *principal,
+                PendingCompound {
+                    principal: *principal,
+                    reward_icp: held.saturating_add(*reward_icp),
+                    held_at: now,
+                },
+            );
+        }
+    });
+}
+
+pub fn take_pending_compound(principal: Principal) -> Option<PendingCompound> {
+    PENDING_COMPOUNDS.with(|pending| pending.borrow_mut().remove(&principal))
+}
+
+pub fn get_pause_state() -> PauseState {
+    PAUSE_STATE.with(|cell| cell.borrow().get().clone())
+}
+
+pub fn set_pause_state(state: PauseState) -> Result<(), ExecutionError> {
+    PAUSE_STATE.with(|cell| {
+        cell.borrow_mut().set(state).map_err(|_| ExecutionError::StateError("Failed to set pause state".to_string()))?;
+        Ok(())
+    })
+}
+
+/// Grants the launching creator the Creator role, once. Covers canisters installed
+/// before roles existed; a Creator role revoked later is not granted again.
+pub fn migrate_creator_role() {
+    let Some(creator) = get_config().creator else {
+        return;
+    };
+    ROLES.with(|roles| {
+        let mut roles = roles.borrow_mut();
+        if roles.audit_trail(0, 1).is_empty() {
+            let _ = roles.grant(ic_cdk::api::id(), creator, Role::Creator);
+        }
+    });
+}
+
+/// Stamps canisters installed before `installed_at` existed with the upgrade time, so
+/// their launch-protection window is bounded from there.
+pub fn migrate_installed_at() {
+    CONFIGS.with(|c| {
+        let mut config = c.borrow().get().clone();
+        if config.installed_at.is_none() {
+            config.installed_at = Some(ic_cdk::api::time());
+            let _ = c.borrow_mut().set(config);
+        }
+    });
+}
+
+pub fn get_stake_positions_of(principal: Principal) -> Vec<StakePosition> {
+    STAKE_POSITIONS.with(|positions| {
+        positions
+            .borrow()
+            .range((principal, 0)..=(principal, u64::MAX))
+            .map(|(_, position)| position)
+            .collect()
+    })
+}
+
+pub fn add_stake_position(
+    principal: Principal,
+    amount: u64,
+    time: u64,
+    lock_period: Option<LockPeriod>,
+) -> StakePosition {
+    let id = NEXT_POSITION_ID.with(|cell| {
+        let id = *cell.borrow().get();
+        let _ = cell.borrow_mut().set(id + 1);
+        id
+    });
+    let position = StakePosition {
+        id,
+        amount,
+        time,
+        lock_period,
+        unlock_time: lock_period.map(|lock_period| time + lock_period.duration_nanos()),
+    };
+    STAKE_POSITIONS.with(|positions| {
+        positions.borrow_mut().insert((principal, id), position.clone());
+    });
+    if let Some(unlock_time) = position.unlock_time {
+        LOCK_EXPIRIES.with(|expiries| {
+            expiries.borrow_mut().insert((unlock_time, principal), ());
+        });
+    }
+    position
+}
+
+/// Staked amount that is not locked at `now`.
+pub fn get_unlocked_stake_amount(principal: Principal, now: u64) -> u64 {
+    get_stake_positions_of(principal)
+        .iter()
+        .filter(|position| !position.is_locked(now))
+        .map(|position| position.amount)
+        .sum()
+}
+
+/// Extra reward weight the principal's locked positions add at `now`.
+pub fn get_lock_boost(principal: Principal, config: &Configs, now: u64) -> u128 {
+    get_stake_positions_of(principal)
+        .iter()
+        .map(|position| position.lock_boost(config, now))
+        .sum()
+}
+
+/// Takes `amount` out of the principal's unlocked positions, newest first, so the
+/// oldest positions keep their accrual time.
+pub fn remove_from_stake_positions(
+    principal: Principal,
+    amount: u64,
+    now: u64,
+) -> Result<(), ExecutionError> {
+    let mut remaining = amount;
+    for mut position in get_stake_positions_of(principal).into_iter().rev() {
+        if remaining == 0 {
+            break;
+        }
+        if position.is_locked(now) {
+            continue;
+        }
+        let taken = remaining.min(position.amount);
+        position.amount -= taken;
+        remaining -= taken;
+        STAKE_POSITIONS.with(|positions| {
+            let mut positions = positions.borrow_mut();
+            if position.amount == 0 {
+                positions.remove(&(principal, position.id));
+            } else {
+                positions.insert((principal, position.id), position);
+            }
+        });
+    }
+    if remaining > 0 {
+        return Err(ExecutionError::StateError(format!(
+            "Stake positions of {} are short by {}",
+            principal, remaining
+        )));
+    }
+    Ok(())
+}
+
+/// Gives every stake from before positions existed a single position holding its
+/// whole amount. Stakes that already have positions are left alone.
+pub fn migrate_stake_positions() {
+    let stakes: Vec<(Principal, Stake)> = STAKES.with(|stakes| stakes.borrow().iter().collect());
+    for (principal, stake) in stakes {
+        if stake.amount > 0 && get_stake_positions_of(principal).is_empty() {
+            add_stake_position(principal, stake.amount, stake.time, None);
+        }
+    }
+}
+
+pub fn get_reward_index() -> RewardIndex {
+    REWARD_INDEX.with(|index| index.borrow().get().clone())
+}
+
+fn set_reward_index(index: RewardIndex) {
+    REWARD_INDEX.with(|cell| {
+        let _ = cell.borrow_mut().set(index);
+    });
+}
+
+/// Raises the reward index by `reward_per_weight` for every staker at once.
+pub fn add_to_reward_index(reward_per_weight: u128) {
+    let mut index = get_reward_index();
+    index.add(reward_per_weight);
+    set_reward_index(index);
+}
+
+/// Rewards the stake has earned since its checkpoint and not yet added to `reward_icp`.
+pub fn pending_reward(stake: &Stake, index: &RewardIndex) -> u64 {
+    index.pending(stake.weight.unwrap_or(0), stake.reward_checkpoint)
+}
+
+/// The stake as it would read after settling, without writing anything.
+pub fn with_pending_reward(mut stake: Stake) -> Stake {
+    stake.reward_icp = stake
+        .reward_icp
+        .saturating_add(pending_reward(&stake, &get_reward_index()));
+    stake
+}
+
+/// Moves the principal's pending rewards into `reward_icp`, checkpoints the stake at
+/// the current index and refreshes its weight from its positions. Call it before and
+/// after anything that changes the stake's amount or locks.
+pub fn settle_stake(principal: Principal, now: u64) -> Option<Stake> {
+    let mut stake = STAKES.with(|stakes| stakes.borrow().get(&principal))?;
+    let mut index = get_reward_index();
+
+    let weight = stake.amount as u128 + get_lock_boost(principal, &get_config(), now);
+    let earned = index.settle(stake.weight, stake.reward_checkpoint, weight);
+    stake.reward_icp = stake.reward_icp.saturating_add(earned);
+    stake.reward_checkpoint = Some(index.reward_per_weight);
+    stake.weight = Some(weight);
+
+    set_reward_index(index);
+    STAKES.with(|stakes| {
+        stakes.borrow_mut().insert(principal, stake.clone());
+    });
+    Some(stake)
+}
+
+/// Settles up to `EXPIRED_LOCKS_PER_CALL` principals whose lock ran out by `now`,
+/// dropping their boost. Returns whether expired locks are left for another call.
+pub fn settle_expired_locks(now: u64) -> bool {
+    let expired: Vec<(u64, Principal)> = LOCK_EXPIRIES.with(|expiries| {
+        expiries
+            .borrow()
+            .iter()
+            .take_while(|((unlock_time, _), _)| *unlock_time <= now)
+            .take(EXPIRED_LOCKS_PER_CALL)
+            .map(|(key, _)| key)
+            .collect()
+    });
+    for key in expired {
+        LOCK_EXPIRIES.with(|expiries| {
+            expiries.borrow_mut().remove(&key);
+        });
+        settle_stake(key.1, now);
+    }
+    has_expired_locks(now)
+}
+
+/// Whether a lock ran out by `now` without its stake being settled since.
+pub fn has_expired_locks(now: u64) -> bool {
+    LOCK_EXPIRIES.with(|expiries| {
+        expiries
+            .borrow()
+            .iter()
+            .next()
+            .is_some_and(|((unlock_time, _), _)| unlock_time <= now)
+    })
+}
+
+/// Checkpoints stakes that predate the reward index. Their `reward_icp` is already
+/// settled, so they only need a weight and the current index.
+pub fn migrate_reward_checkpoints() {
+    let now = ic_cdk::api::time();
+    let unsettled: Vec<Principal> = STAKES.with(|stakes| {
+        stakes
+            .borrow()
+            .iter()
+            .filter(|(_, stake)| stake.reward_checkpoint.is_none())
+            .map(|(principal, _)| principal)
+            .collect()
+    });
+    for principal in unsettled {
+        settle_stake(principal, now);
+    }
+}
+
 #[derive(CandidType, Deserialize, Clone)]
 pub struct Stake {
     pub amount: u64,
     pub time: u64,
+    // Settled rewards; rewards since `reward_checkpoint` are added when the stake is touched.
     pub reward_icp: u64,
+    // Rewards are swapped into more primary stake instead of waiting for a claim.
+    pub auto_compound: Option<bool>,
+    // `RewardIndex::reward_per_weight` when the stake was last settled.
+    pub reward_checkpoint: Option<u128>,
+    // Amount plus lock boost as of the last settle.
+    pub weight: Option<u128>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct StakePosition {
+    pub id: u64,
+    pub amount: u64,
+    pub time: u64,
+    pub lock_period: Option<LockPeriod>,
+    pub unlock_time: Option<u64>,
+}
+
+impl StakePosition {
+    pub fn is_locked(&self, now: u64) -> bool {
+        self.unlock_time.is_some_and(|unlock_time| now < unlock_time)
+    }
+
+    /// Reward weight on top of `amount` while the position is locked.
+    pub fn lock_boost(&self, config: &Configs, now: u64) -> u128 {
+        if !self.is_locked(now) {
+            return 0;
+        }
+        let multiplier = config.lock_multiplier(self.lock_period);
+        (self.amount as u128) * (multiplier.saturating_sub(LOCK_MULTIPLIER_BASE) as u128)
+            / (LOCK_MULTIPLIER_BASE as u128)
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockPeriod {
+    ThirtyDays,
+    NinetyDays,
+    OneYear,
+}
+
+impl LockPeriod {
+    pub const ALL: [LockPeriod; 3] = [
+        LockPeriod::ThirtyDays,
+        LockPeriod::NinetyDays,
+        LockPeriod::OneYear,
+    ];
+
+    pub fn duration_nanos(&self) -> u64 {
+        let days = match self {
+            LockPeriod::ThirtyDays => 30,
+            LockPeriod::NinetyDays => 90,
+            LockPeriod::OneYear => 365,
+        };
+        days * 24 * 60 * 60 * 1_000_000_000
+    }
+}
+
+/// Reward weight multipliers per lock period, in basis points (10_000 = 1x).
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct LockMultipliers {
+    pub thirty_days: u64,
+    pub ninety_days: u64,
+    pub one_year: u64,
+}
+
+impl Default for LockMultipliers {
+    fn default() -> Self {
+        LockMultipliers {
+            thirty_days: DEFAULT_THIRTY_DAYS_MULTIPLIER,
+            ninety_days: DEFAULT_NINETY_DAYS_MULTIPLIER,
+            one_year: DEFAULT_ONE_YEAR_MULTIPLIER,
+        }
+    }
 }
 
 #[derive(CandidType, Deserialize, Clone)]
@@ -167,6 +626,35 @@ impl Default for SecondaryRatio {
     }
 }
 
+/// How the secondary ratio follows XRC readings.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct OracleConfig {
+    // Readings kept in the ring buffer and averaged over.
+    pub window_size: u64,
+    // Largest move of the ratio per reading, in basis points.
+    pub max_change_bps: u64,
+    // Swaps are paused once the ratio is older than this.
+    pub max_staleness_secs: u64,
+}
+
+impl Default for OracleConfig {
+    fn default() -> Self {
+        OracleConfig {
+            window_size: DEFAULT_ORACLE_WINDOW_SIZE,
+            max_change_bps: DEFAULT_MAX_RATIO_CHANGE_BPS,
+            max_staleness_secs: DEFAULT_MAX_PRICE_STALENESS_SECS,
+        }
+    }
+}
+
+/// One XRC reading and the ratio it resulted in. Times are in seconds.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct PriceReading {
+    pub time: u64,
+    pub price_cents: u64,
+    pub ratio: u64,
+}
+
 #[derive(CandidType, Deserialize, Clone, Default)]
 pub struct Trxs {
     pub archive_trx: HashMap<Principal, ArchiveBalance>,
@@ -200,6 +688,69 @@ pub struct Configs {
     pub secondary_token_id: Principal,
     pub tokenomics_cansiter_id: Principal,
     pub icp_ledger_id: Principal,
+    // DEX the primary token trades on; KongSwap when unset.
+    pub dex: Option<DexKind>,
+    // External canisters; the mainnet ones when unset.
+    pub dex_canister_id: Option<Principal>,
+    pub lbry_fun_canister_id: Option<Principal>,
+    pub xrc_canister_id: Option<Principal>,
+    // Reward weight of locked stakes; the defaults when unset.
+    pub lock_multipliers: Option<LockMultipliers>,
+    // Secondary ratio oracle settings; the defaults when unset.
+    pub oracle: Option<OracleConfig>,
+    // Per-principal caps while the token launches; none when unset.
+    pub launch_protection: Option<LaunchProtection>,
+    // When lbry_fun provided liquidity on the DEX, in nanoseconds. Set once.
+    pub liquidity_provided_at: Option<u64>,
+    // When the canister was installed, or first upgraded with this field, in nanoseconds.
+    pub installed_at: Option<u64>,
+    // How reward runs split the ICP they release; the defaults when unset.
+    pub fee_schedule: Option<FeeSchedule>,
+    // Who launched the token, credited the creator share.
+    pub creator: Option<Principal>,
+    // Where the creator's revenue is paid; the creator when unset.
+    pub creator_payout: Option<Principal>,
+    // When operations pause on their own; the defaults when unset.
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+}
+
+impl Configs {
+    pub fn oracle(&self) -> OracleConfig {
+        self.oracle.clone().unwrap_or_default()
+    }
+
+    pub fn fee_schedule(&self) -> FeeSchedule {
+        self.fee_schedule.clone().unwrap_or_default()
+    }
+
+    pub fn circuit_breaker(&self) -> CircuitBreakerConfig {
+        self.circuit_breaker.clone().unwrap_or_default()
+    }
+
+    pub fn lock_multiplier(&self, lock_period: Option<LockPeriod>) -> u64 {
+        let multipliers = self.lock_multipliers.clone().unwrap_or_default();
+        match lock_period {
+            None => LOCK_MULTIPLIER_BASE,
+            Some(LockPeriod::ThirtyDays) => multipliers.thirty_days,
+            Some(LockPeriod::NinetyDays) => multipliers.ninety_days,
+            Some(LockPeriod::OneYear) => multipliers.one_year,
+        }
+    }
+
+    pub fn dex(&self) -> Dex {
+        let kind = self.dex.unwrap_or_default();
+        Dex::new(kind, self.dex_canister_id.unwrap_or_else(|| kind.mainnet_canister_id()))
+    }
+
+    pub fn lbry_fun_canister_id(&self) -> Principal {
+        self.lbry_fun_canister_id
+            .unwrap_or_else(|| Principal::from_text(LBRY_FUN_CANISTER_ID).unwrap())
+    }
+
+    pub fn xrc_canister_id(&self) -> Principal {
+        self.xrc_canister_id
+            .unwrap_or_else(|| Principal::from_text(XRC_CANISTER_ID).unwrap())
+    }
 }
 #[derive(CandidType, Deserialize, Clone, Debug)]
 pub enum LogType {
@@ -219,6 +770,18 @@ impl Storable for Stake {
     const BOUND: Bound = Bound::Unbounded;
 }
 
+impl Storable for StakePosition {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
 impl Storable for ArchiveBalance {
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
         Cow::Owned(Encode!(self).unwrap())
@@ -277,6 +840,192 @@ impl Storable for Configs {
     const BOUND: Bound = Bound::Unbounded;
 }
 
+/// The latest reward distribution. The reward is fixed when the round starts; the
+/// stakes are then settled a chunk at a time, resuming after `cursor`, and the reward
+/// is shared once they and any expired locks are.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct DistributionRound {
+    pub round: u64,
+    pub in_progress: bool,
+    pub started_at: u64,
+    pub completed_at: Option<u64>,
+    pub total_icp_allocated: u128,
+    pub staker_share: u128,
+    pub icp_reward_per_primary: u128,
+    pub cursor: Option<Principal>,
+    pub stakes_processed: u64,
+    pub total_stakes: u64,
+}
+
+impl Storable for DistributionRound {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+pub fn get_distribution_round() -> DistributionRound {
+    DISTRIBUTION_ROUND.with(|round| round.borrow().get().clone())
+}
+
+pub fn set_distribution_round(round: DistributionRound) {
+    DISTRIBUTION_ROUND.with(|cell| {
+        let _ = cell.borrow_mut().set(round);
+    });
+}
+
+impl Storable for PriceReading {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// ICP swapped and secondary burned by one principal under launch protection, in e8s.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct LaunchUsage {
+    pub icp_swapped: u64,
+    pub secondary_burned: u64,
+}
+
+impl Storable for LaunchUsage {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// The creator share of reward runs, in e8s. `accrued` is everything credited so far,
+/// `claimed` what was paid out, and `unclaimed` what is still held for the creator.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct CreatorRevenue {
+    pub accrued: u64,
+    pub unclaimed: u64,
+    pub claimed: u64,
+}
+
+impl Storable for CreatorRevenue {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// ICP rewards taken out for compounding whose swap failed after the ICP may have left
+/// for the DEX. Held, and out of `TOTAL_UNCLAIMED_ICP_REWARD`, until a controller resolves it.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct PendingCompound {
+    pub principal: Principal,
+    pub reward_icp: u64,
+    pub held_at: u64,
+}
+
+impl Storable for PendingCompound {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+pub use access_control::PausableOp;
+
+/// Why an operation was paused.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum PauseReason {
+    Manual,
+    // The XRC price moved more than the circuit breaker allows in one reading.
+    PriceMove { previous_cents: u64, current_cents: u64, move_bps: u64 },
+    // This many ICP transfers out of the canister failed in a row.
+    TransferFailures { count: u32 },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct PauseInfo {
+    pub paused_at: u64,
+    pub paused_by: Principal,
+    pub reason: PauseReason,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct PauseState {
+    pub paused: BTreeMap<PausableOp, PauseInfo>,
+    // Outgoing ICP transfers that failed since the last one that went through.
+    pub consecutive_transfer_failures: u32,
+}
+
+impl Storable for PauseState {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// When the circuit breaker pauses operations on its own.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct CircuitBreakerConfig {
+    // Largest move of the XRC price between two readings, in basis points, before
+    // swaps and burns are paused.
+    pub max_price_move_bps: u64,
+    // Failed ICP transfers in a row before everything moving ICP out is paused.
+    pub max_transfer_failures: u32,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        CircuitBreakerConfig {
+            max_price_move_bps: DEFAULT_BREAKER_MAX_PRICE_MOVE_BPS,
+            max_transfer_failures: DEFAULT_BREAKER_MAX_TRANSFER_FAILURES,
+        }
+    }
+}
+
+/// Readings in the ring buffer, oldest first.
+pub fn get_price_readings() -> Vec<PriceReading> {
+    PRICE_READINGS.with(|readings| readings.borrow().iter().map(|(_, reading)| reading).collect())
+}
+
+/// Appends a reading and drops the oldest ones beyond `window_size`.
+pub fn push_price_reading(reading: PriceReading, window_size: u64) {
+    PRICE_READINGS.with(|readings| {
+        let mut readings = readings.borrow_mut();
+        let next_seq = readings.last_key_value().map_or(0, |(seq, _)| seq + 1);
+        readings.insert(next_seq, reading);
+        while readings.len() > window_size.max(1) {
+            readings.pop_first();
+        }
+    });
+}
+
 #[derive(CandidType, Deserialize, Clone, Debug, Default)]
 pub struct TreasuryState {
     pub next_earliest_provision_timestamp: u64,
@@ -5,11 +5,12 @@ use crate::{
     DEFAULT_INSUFFICIENT_BALANCE_REWARD_DISTRIBUTION_ERROR,
     DEFAULT_INSUFFICIENT_CANISTER_BALANCE_ERROR, DEFAULT_INVALID_AMOUNT_ERROR,
     DEFAULT_MINIMUM_REQUIRED_ERROR, DEFAULT_MINT_FAILED, DEFAULT_MULTIPLICATION_OVERFLOW_ERROR,
-    DEFAULT_TRANSFER_FAILED_ERROR, DEFAULT_UNDERFLOW_ERROR,
+    DEFAULT_TRANSFER_FAILED_ERROR, DEFAULT_UNAUTHORIZED_ERROR, DEFAULT_UNDERFLOW_ERROR,
 };
 use crate::{get_stake, storage::*};
-use crate::{get_user_archive_balance, utils::*};
+use crate::{get_user_archive_balance, utils::*, BurnOutput, BurnSimulation};
 use crate::{constants::*, dex_integration::*};
+use access_control::Role;
 use candid::{CandidType, Nat, Principal};
 use ic_cdk::{self, caller, update};
 use ic_ledger_types::{
@@ -26,8 +27,8 @@ use icrc_ledger_types::icrc1::transfer::{BlockIndex, TransferArg, TransferError}
 use icrc_ledger_types::icrc2::transfer_from::{TransferFromArgs, TransferFromError};
 use num_bigint::BigUint;
 use serde::Deserialize;
+use std::ops::Bound;
 
-const LBRY_FUN_CANISTER_ID: &str = "j362g-ziaaa-aaaap-qkt7q-cai";
 
 #[warn(non_snake_case)]
 #[derive(CandidType, Deserialize, Debug)]
@@ -72,6 +73,8 @@ pub struct GetExchangeRateRequest {
 pub async fn swap(
     amount_icp: u64,
     from_subaccount: Option<[u8; 32]>,
+    min_amount_out: Option<u64>,
+    deadline: Option<u64>,
 ) -> Result<String, ExecutionError> {
     let caller = ic_cdk::caller();
     let _guard =
@@ -81,6 +84,7 @@ pub async fn swap(
         "swap",
         &format!("Swap initiated: {}  ICP (e8s)", amount_icp),
     );
+    check_not_paused(PausableOp::Swap).map_err(|e| ExecutionError::new_with_log(caller, "swap", e))?;
     if amount_icp < 10_000_000 {
         return Err(ExecutionError::new_with_log(
             caller,
@@ -93,6 +97,18 @@ pub async fn swap(
             },
         ));
     }
+    check_deadline(deadline).map_err(|e| ExecutionError::new_with_log(caller, "swap", e))?;
+    check_launch_cap(caller, LaunchCap::IcpSwapped, amount_icp)
+        .map_err(|e| ExecutionError::new_with_log(caller, "swap", e))?;
+    // Don't take the deposit while the price is stale or already below the minimum.
+    let quoted_ratio = get_fresh_secondary_ratio(ic_cdk::api::time() / 1_000_000_000)
+        .map_err(|e| ExecutionError::new_with_log(caller, "swap", e))?;
+    check_min_amount_out(
+        "secondary",
+        amount_icp.saturating_mul(quoted_ratio),
+        min_amount_out,
+    )
+    .map_err(|e| ExecutionError::new_with_log(caller, "swap", e))?;
 
     deposit_icp_in_canister(amount_icp, from_subaccount)
         .await
@@ -133,9 +149,15 @@ pub async fn swap(
             },
         )
     })?;
+    // The ratio may have moved while the deposit was in flight.
+    if let Err(e) = check_min_amount_out("secondary", secondary_amount, min_amount_out) {
+        refund_icp_deposit(caller, amount_icp).await?;
+        return Err(ExecutionError::new_with_log(caller, "swap", e));
+    }
 
     match mint_secondary_token(secondary_amount).await {
         Ok(_) => {
+            record_launch_usage(caller, LaunchCap::IcpSwapped, amount_icp);
             register_info_log(
                 caller,
                 "swap",
@@ -185,6 +207,8 @@ pub async fn swap(
 pub async fn burn_secondary(
     amount_secondary: u64,
     from_subaccount: Option<[u8; 32]>,
+    min_amount_out: Option<BurnOutput>,
+    deadline: Option<u64>,
 ) -> Result<String, ExecutionError> {
     let caller = ic_cdk::caller();
     let _guard =
@@ -194,6 +218,8 @@ pub async fn burn_secondary(
         "burn_secondary",
         &format!("burn_secondary initiated: {} secondary ", amount_secondary),
     );
+    check_not_paused(PausableOp::BurnSecondary)
+        .map_err(|e| ExecutionError::new_with_log(caller, "burn_secondary", e))?;
 
     if amount_secondary < 1 {
         return Err(ExecutionError::new_with_log(
@@ -207,6 +233,15 @@ pub async fn burn_secondary(
             },
         ));
     }
+    check_deadline(deadline)
+        .map_err(|e| ExecutionError::new_with_log(caller, "burn_secondary", e))?;
+    // Caps are in e8s; an overflowing amount is rejected further down.
+    check_launch_cap(
+        caller,
+        LaunchCap::SecondaryBurned,
+        amount_secondary.saturating_mul(E8S),
+    )
+    .map_err(|e| ExecutionError::new_with_log(caller, "burn_secondary", e))?;
 
     //Dynamic price
     let mut icp_rate_in_cents: u64 = get_current_secondary_ratio();
@@ -260,6 +295,19 @@ pub async fn burn_secondary(
             },
         ));
     }
+    let min_primary_out = match min_amount_out {
+        Some(min_amount_out) => {
+            check_min_amount_out("ICP", amount_icp_e8s, Some(min_amount_out.icp))
+                .map_err(|e| ExecutionError::new_with_log(caller, "burn_secondary", e))?;
+            let primary = quote_primary_mint(amount_secondary)
+                .await
+                .map_err(|e| ExecutionError::new_with_log(caller, "burn_secondary", e))?;
+            check_min_amount_out("primary", primary, Some(min_amount_out.primary))
+                .map_err(|e| ExecutionError::new_with_log(caller, "burn_secondary", e))?;
+            Some(min_amount_out.primary)
+        }
+        None => None,
+    };
 
     let mut total_icp_available: u64 = 0;
     match fetch_canister_icp_balance().await {
@@ -305,6 +353,23 @@ pub async fn burn_secondary(
                 },
             )
         })?;
+    // The creator's revenue waits in the canister until claimed.
+    let creator_unclaimed: u64 = creator_revenue().unclaimed;
+    remaining_icp = remaining_icp
+        .checked_sub(creator_unclaimed)
+        .ok_or_else(|| {
+            ExecutionError::new_with_log(
+                caller,
+                "burn_secondary",
+                ExecutionError::Underflow {
+                    operation: DEFAULT_UNDERFLOW_ERROR.to_string(),
+                    details: format!(
+                        "remaining_icp: {} with creator_unclaimed: {}",
+                        remaining_icp, creator_unclaimed
+                    ),
+                },
+            )
+        })?;
 
     // For burns, we only need to ensure we have enough ICP to pay out
     // No need to reserve 50% since burning increases our ICP reserves
@@ -354,10 +419,60 @@ pub async fn burn_secondary(
         caller,
         "burn_secondary",
         &format!(
-            "Successfully burned {} secondary tokens ({} e8s). Preparing to send {} ICP (e8s).",
+            "Successfully burned {} secondary tokens ({} e8s). Minting primary before sending {} ICP (e8s).",
             amount_secondary, amount_secondary_e8s, amount_icp_e8s
         ),
     );
+
+    // No secondary burn limit - the 21M primary cap is still enforced in the mint_primary function (reason described in commented out utils.rs function)
+    // Original code checked against secondary thresholds:
+    // let limit_result = within_max_limit(amount_secondary).await.map_err(|e|
+    //     ExecutionError::new_with_log(
+    //         caller,
+    //         "burn_secondary",
+    //         ExecutionError::StateError(format!("Failed to check max limit: {}", e))
+    //     )
+    // )?;
+
+    // if limit_result > 0 {
+    // Primary is minted before any ICP leaves the canister, so a burn tokenomics rejects
+    // for slippage (other burns moved the threshold since the quote) can be undone.
+    let mint_error = match mint_primary(amount_secondary, caller, from_subaccount, min_primary_out)
+        .await
+    {
+        Ok(_) => {
+            register_info_log(
+                caller,
+                "burn_secondary",
+                &format!("Minted primary tokens to {}", caller),
+            );
+            None
+        }
+        Err(MintPrimaryError::SlippageExceeded { min_amount_out, amount_out }) => {
+            refund_burned_secondary(caller, amount_secondary_e8s, amount_icp_e8s).await?;
+            return Err(ExecutionError::new_with_log(
+                caller,
+                "burn_secondary",
+                ExecutionError::SlippageExceeded {
+                    token: "primary".to_string(),
+                    min_amount_out,
+                    amount_out,
+                },
+            ));
+        }
+        Err(MintPrimaryError::Failed(e)) => Some(e),
+    };
+    // }
+    // // else {
+    // //     // primary fully minted
+    // //     register_info_log(
+    //         caller,
+    //         "burn_secondary",
+    //         &format!("Burn completed successfully. No more primary tokens can be minted.")
+    //     );
+    // }
+    record_launch_usage(caller, LaunchCap::SecondaryBurned, amount_secondary_e8s);
+
     // Is this the problem since from_subaccount is alice/bob/etc.?
     match send_icp(caller, amount_icp_e8s, None).await {
         Ok(_) => {
@@ -371,15 +486,20 @@ pub async fn burn_secondary(
             );
         }
         Err(e) => {
+            // The primary half is archived as well when its mint failed.
+            let multiplier: u64 = if mint_error.is_some() { 2 } else { 1 };
             let amount_icp_after_fee = amount_icp_e8s
-                .checked_mul(2)
+                .checked_mul(multiplier)
                 .ok_or_else(|| {
                     ExecutionError::new_with_log(
                         caller,
                         "burn_secondary",
                         ExecutionError::MultiplicationOverflow {
                             operation: DEFAULT_MULTIPLICATION_OVERFLOW_ERROR.to_string(),
-                            details: format!("amount_icp_e8s: {} with {}", amount_icp_e8s, 2),
+                            details: format!(
+                                "amount_icp_e8s: {} with {}",
+                                amount_icp_e8s, multiplier
+                            ),
                         },
                     )
                 })?
@@ -414,68 +534,37 @@ pub async fn burn_secondary(
         }
     }
 
-    // No secondary burn limit - the 21M primary cap is still enforced in the mint_primary function (reason described in commented out utils.rs function)
-    // Original code checked against secondary thresholds:
-    // let limit_result = within_max_limit(amount_secondary).await.map_err(|e|
-    //     ExecutionError::new_with_log(
-    //         caller,
-    //         "burn_secondary",
-    //         ExecutionError::StateError(format!("Failed to check max limit: {}", e))
-    //     )
-    // )?;
-
-    // if limit_result > 0 {
-    match mint_primary(amount_secondary, caller, from_subaccount).await {
-        Ok(_) => {
-            register_info_log(
-                caller,
-                "burn_secondary",
-                &format!(
-                    "Burn completed successfully.Minted primary tokens to {}",
-                    caller
-                ),
-            );
-        }
-        Err(e) => {
-            let amount_icp_after_fee =
-                amount_icp_e8s
-                    .checked_sub(ICP_TRANSFER_FEE)
-                    .ok_or_else(|| {
-                        ExecutionError::new_with_log(
-                            caller,
-                            "burn_secondary",
-                            ExecutionError::Underflow {
-                                operation: DEFAULT_UNDERFLOW_ERROR.to_string(),
-                                details: format!(
-                                    "amount_icp_e8s: {} with ICP_TRANSFER_FEE: {}",
-                                    amount_icp_e8s, ICP_TRANSFER_FEE
-                                ),
-                            },
-                        )
-                    })?;
+    if let Some(e) = mint_error {
+        // The ICP for the primary half is archived for redeem.
+        let amount_icp_after_fee =
+            amount_icp_e8s
+                .checked_sub(ICP_TRANSFER_FEE)
+                .ok_or_else(|| {
+                    ExecutionError::new_with_log(
+                        caller,
+                        "burn_secondary",
+                        ExecutionError::Underflow {
+                            operation: DEFAULT_UNDERFLOW_ERROR.to_string(),
+                            details: format!(
+                                "amount_icp_e8s: {} with ICP_TRANSFER_FEE: {}",
+                                amount_icp_e8s, ICP_TRANSFER_FEE
+                            ),
+                        },
+                    )
+                })?;
 
-            archive_user_transaction(amount_icp_after_fee)?;
-            return Err(ExecutionError::new_with_log(
-                caller,
-                "burn_secondary",
-                ExecutionError::MintFailed {
-                    token: "primary".to_string(),
-                    amount: amount_secondary,
-                    details: e,
-                    reason: DEFAULT_MINT_FAILED.to_string(),
-                },
-            ));
-        }
+        archive_user_transaction(amount_icp_after_fee)?;
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "burn_secondary",
+            ExecutionError::MintFailed {
+                token: "primary".to_string(),
+                amount: amount_secondary,
+                details: e,
+                reason: DEFAULT_MINT_FAILED.to_string(),
+            },
+        ));
     }
-    // }
-    // // else {
-    // //     // primary fully minted
-    // //     register_info_log(
-    //         caller,
-    //         "burn_secondary",
-    //         &format!("Burn completed successfully. No more primary tokens can be minted.")
-    //     );
-    // }
 
     Ok("Burn Successfully!".to_string())
 }
@@ -584,14 +673,30 @@ async fn send_icp(
     )
     .await;
 
-    match result {
+    let result = match result {
         Ok((Ok(block_index),)) => Ok(block_index),
         Ok((Err(err),)) => Err(format!("Ledger transfer error: {:?}", err)),
         Err((code, msg)) => Err(format!(
             "Failed to call ledger: (code: {:?}, message: \"{}\")",
             code, msg
         )),
-    }
+    };
+    record_transfer_outcome(result.is_ok());
+    result
+}
+
+/// Why tokenomics did not mint primary for a burn.
+enum MintPrimaryError {
+    /// The threshold moved since the quote and the mint fell below the caller's minimum.
+    SlippageExceeded { min_amount_out: u64, amount_out: u64 },
+    Failed(String),
+}
+
+// The one tokenomics `ExecutionError` variant a burn reacts to; any other variant decodes
+// to `None` through the candid opt rule.
+#[derive(CandidType, Deserialize)]
+enum TokenomicsMintError {
+    SlippageExceeded { min_amount_out: u64, amount_out: u64 },
 }
 
 #[allow(non_snake_case)]
@@ -599,7 +704,8 @@ async fn mint_primary(
     secondary_amount: u64,
     caller: Principal,
     to_subaccount: Option<[u8; 32]>,
-) -> Result<String, String> {
+    min_primary_out: Option<u64>,
+) -> Result<String, MintPrimaryError> {
     // Get the tokenomics canister ID from config
     let tokenomics_canister_id = get_config().tokenomics_cansiter_id;
 
@@ -607,36 +713,169 @@ async fn mint_primary(
     let result = ic_cdk::api::call::call_raw(
         tokenomics_canister_id,
         "mint_primary",
-        candid::encode_args((secondary_amount, caller, to_subaccount))
-            .map_err(|e| format!("Failed to encode arguments: {}", e))?,
+        candid::encode_args((secondary_amount, caller, to_subaccount, min_primary_out)).map_err(
+            |e| MintPrimaryError::Failed(format!("Failed to encode arguments: {}", e)),
+        )?,
         0,
     )
     .await;
 
     match result {
         Ok(bytes) => {
-            // where CandidString can represent the serialized ExecutionError
-            match candid::decode_one::<Result<String, String>>(&bytes) {
+            match candid::decode_one::<Result<String, Option<TokenomicsMintError>>>(&bytes) {
                 Ok(Ok(success_msg)) => Ok(success_msg),
-                Ok(Err(err_msg)) => Err(format!("Ledger error: {}", err_msg)),
-                Err(e) => Err(format!("Failed to decode successful response: {}", e)),
+                Ok(Err(Some(TokenomicsMintError::SlippageExceeded {
+                    min_amount_out,
+                    amount_out,
+                }))) => Err(MintPrimaryError::SlippageExceeded {
+                    min_amount_out,
+                    amount_out,
+                }),
+                Ok(Err(None)) => Err(MintPrimaryError::Failed(
+                    "Tokenomics rejected the mint".to_string(),
+                )),
+                Err(e) => Err(MintPrimaryError::Failed(format!(
+                    "Failed to decode successful response: {}",
+                    e
+                ))),
             }
         }
         Err((code, msg)) => {
             ic_cdk::println!("Error: {:?}", msg);
-            Err(format!(
+            Err(MintPrimaryError::Failed(format!(
                 "Failed to call ledger: (code: {:?}, message: \"{}\")",
                 code, msg
-            ))
+            )))
         }
     }
 }
+
+// Gives a burn back after tokenomics rejected its primary mint for slippage. If the
+// secondary cannot be minted back, the burn's full ICP value is archived for redeem.
+async fn refund_burned_secondary(
+    caller: Principal,
+    amount_secondary_e8s: u64,
+    amount_icp_e8s: u64,
+) -> Result<(), ExecutionError> {
+    match mint_secondary_token(amount_secondary_e8s).await {
+        Ok(_) => {
+            register_info_log(
+                caller,
+                "refund_burned_secondary",
+                &format!("Refunded {} secondary (e8s) to {}", amount_secondary_e8s, caller),
+            );
+        }
+        Err(e) => {
+            let amount_icp_after_fee = amount_icp_e8s
+                .checked_mul(2)
+                .and_then(|amount| amount.checked_sub(ICP_TRANSFER_FEE))
+                .ok_or_else(|| {
+                    ExecutionError::new_with_log(
+                        caller,
+                        "refund_burned_secondary",
+                        ExecutionError::Underflow {
+                            operation: DEFAULT_UNDERFLOW_ERROR.to_string(),
+                            details: format!(
+                                "amount_icp_e8s: {} with ICP_TRANSFER_FEE: {}",
+                                amount_icp_e8s, ICP_TRANSFER_FEE
+                            ),
+                        },
+                    )
+                })?;
+            register_info_log(
+                caller,
+                "refund_burned_secondary",
+                &format!("Refund failed, archiving {} ICP (e8s): {}", amount_icp_after_fee, e),
+            );
+            archive_user_transaction(amount_icp_after_fee)?;
+        }
+    }
+    Ok(())
+}
+/// Primary tokenomics would mint for burning `secondary_amount` right now.
+pub(crate) async fn quote_primary_mint(secondary_amount: u64) -> Result<u64, ExecutionError> {
+    let tokenomics_canister_id = get_config().tokenomics_cansiter_id;
+    let result: Result<(Result<u64, candid::Reserved>,), _> =
+        ic_cdk::call(tokenomics_canister_id, "quote_mint_primary", (secondary_amount,)).await;
+
+    match result {
+        Ok((Ok(primary),)) => Ok(primary),
+        Ok((Err(_),)) => Err(ExecutionError::StateError(format!(
+            "Tokenomics cannot mint primary for {} secondary",
+            secondary_amount
+        ))),
+        Err((code, msg)) => Err(ExecutionError::CanisterCallFailed {
+            canister: tokenomics_canister_id.to_string(),
+            method: "quote_mint_primary".to_string(),
+            details: format!("code: {:?}, message: {}", code, msg),
+        }),
+    }
+}
+
+/// Tokenomics' breakdown of the primary minted for burning `secondary_amount` right now.
+pub(crate) async fn simulate_primary_mint(
+    secondary_amount: u64,
+) -> Result<BurnSimulation, ExecutionError> {
+    let tokenomics_canister_id = get_config().tokenomics_cansiter_id;
+    let result: Result<(Result<BurnSimulation, candid::Reserved>,), _> =
+        ic_cdk::call(tokenomics_canister_id, "simulate_burn", (secondary_amount,)).await;
+
+    match result {
+        Ok((Ok(simulation),)) => Ok(simulation),
+        Ok((Err(_),)) => Err(ExecutionError::StateError(format!(
+            "Tokenomics cannot simulate a burn of {} secondary",
+            secondary_amount
+        ))),
+        Err((code, msg)) => Err(ExecutionError::CanisterCallFailed {
+            canister: tokenomics_canister_id.to_string(),
+            method: "simulate_burn".to_string(),
+            details: format!("code: {:?}, message: {}", code, msg),
+        }),
+    }
+}
+
+// Sends a swap deposit back, or archives it for redeem if the transfer fails.
+async fn refund_icp_deposit(caller: Principal, amount_icp: u64) -> Result<(), ExecutionError> {
+    let amount_icp_after_fee = amount_icp.checked_sub(ICP_TRANSFER_FEE).ok_or_else(|| {
+        ExecutionError::new_with_log(
+            caller,
+            "refund_icp_deposit",
+            ExecutionError::Underflow {
+                operation: DEFAULT_UNDERFLOW_ERROR.to_string(),
+                details: format!(
+                    "amount_icp: {} with ICP_TRANSFER_FEE: {}",
+                    amount_icp, ICP_TRANSFER_FEE
+                ),
+            },
+        )
+    })?;
+    match send_icp(caller, amount_icp_after_fee, None).await {
+        Ok(_) => {
+            register_info_log(
+                caller,
+                "refund_icp_deposit",
+                &format!("Refunded {} ICP (e8s) to {}", amount_icp_after_fee, caller),
+            );
+        }
+        Err(e) => {
+            register_info_log(
+                caller,
+                "refund_icp_deposit",
+                &format!("Refund failed, archiving {} ICP (e8s): {}", amount_icp_after_fee, e),
+            );
+            archive_user_transaction(amount_icp_after_fee)?;
+        }
+    }
+    Ok(())
+}
+
 //stake
 #[allow(non_snake_case)]
 #[update(guard = "not_anon")]
 async fn stake_primary(
     amount: u64,
     from_subaccount: Option<[u8; 32]>,
+    lock_period: Option<LockPeriod>,
 ) -> Result<String, ExecutionError> {
     let caller = ic_cdk::caller();
     let _guard =
@@ -646,6 +885,8 @@ async fn stake_primary(
         "stake_primary",
         &format!("Staking initiated: {} primary", amount),
     );
+    check_not_paused(PausableOp::StakePrimary)
+        .map_err(|e| ExecutionError::new_with_log(caller, "stake_primary", e))?;
     let mut primary_fee = PRIMARY_FEE.with(|fee| *fee.borrow());
     if amount < 100_000_000 {
         return Err(ExecutionError::new_with_log(
@@ -702,6 +943,7 @@ async fn stake_primary(
         ),
     );
     let current_time = ic_cdk::api::time();
+    settle_stake(caller, current_time);
     STAKES.with(|stakes| -> Result<(), ExecutionError> {
         let mut stakes_map = stakes.borrow_mut();
 
@@ -737,12 +979,27 @@ async fn stake_primary(
                 amount: post_fee_amount,
                 time: current_time,
                 reward_icp: 0,
+                auto_compound: None,
+                reward_checkpoint: None,
+                weight: None,
             },
         };
 
         stakes_map.insert(caller, updated_stake);
         Ok(())
     })?;
+    let position = add_stake_position(caller, post_fee_amount, current_time, lock_period);
+    settle_stake(caller, current_time);
+    if let Some(unlock_time) = position.unlock_time {
+        register_info_log(
+            caller,
+            "stake_primary",
+            &format!(
+                "Position {} locked for {:?} until {}",
+                position.id, lock_period, unlock_time
+            ),
+        );
+    }
 
     Ok("Staked Successfully!".to_string())
 }
@@ -775,7 +1032,16 @@ async fn un_stake_all_primary(from_subaccount: Option<[u8; 32]>) -> Result<Strin
         primary_fee = fee;
     }
 
-    let staked_amount = current_stake.amount;
+    // Locked positions stay staked until their unlock time.
+    let now = ic_cdk::api::time();
+    let staked_amount = get_unlocked_stake_amount(caller, now);
+    if staked_amount == 0 && current_stake.amount > 0 {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "un_stake_all_primary",
+            ExecutionError::StateError("All staked primary is still locked".to_string()),
+        ));
+    }
 
     // Verify caller balance
     if staked_amount <= primary_fee {
@@ -849,18 +1115,19 @@ async fn un_stake_all_primary(from_subaccount: Option<[u8; 32]>) -> Result<Strin
                 },
             )
         })?;
-    // Update the stake
+    // Update the stake, keeping its rewards
+    settle_stake(caller, now);
     STAKES.with(|stakes| {
         let mut stakes_map = stakes.borrow_mut();
-        stakes_map.insert(
-            caller,
-            Stake {
-                amount: new_amount,
-                time: ic_cdk::api::time(),
-                reward_icp: current_stake.reward_icp, // Keep the same reward_icp value
-            },
-        );
+        if let Some(mut stake) = stakes_map.get(&caller) {
+            stake.amount = new_amount;
+            stake.time = ic_cdk::api::time();
+            stakes_map.insert(caller, stake);
+        }
     });
+    remove_from_stake_positions(caller, staked_amount, now)
+        .map_err(|e| ExecutionError::new_with_log(caller, "un_stake_all_primary", e))?;
+    settle_stake(caller, now);
     register_info_log(
         caller,
         "un_stake_all_primary",
@@ -869,6 +1136,127 @@ async fn un_stake_all_primary(from_subaccount: Option<[u8; 32]>) -> Result<Strin
     Ok("Successfully unstaked!".to_string())
 }
 
+#[allow(non_snake_case)]
+#[update(guard = "not_anon")]
+async fn un_stake_primary(
+    amount: u64,
+    from_subaccount: Option<[u8; 32]>,
+) -> Result<String, ExecutionError> {
+    let caller = ic_cdk::caller();
+    let _guard =
+        CallerGuard::new(caller).map_err(|e| ExecutionError::Unauthorized(e.to_string()))?;
+    register_info_log(
+        caller,
+        "un_stake_primary",
+        &format!("Partial unstaking initiated: {} primary", amount),
+    );
+    let mut primary_fee = PRIMARY_FEE.with(|fee| *fee.borrow());
+
+    let current_stake = get_stake(caller).ok_or_else(|| {
+        ExecutionError::new_with_log(
+            caller,
+            "un_stake_primary",
+            ExecutionError::StateError("No stake found for caller".to_string()),
+        )
+    })?;
+
+    if primary_fee == 0 {
+        let fee: u64 = get_primary_fee().await?;
+        update_primary_fee(fee)?;
+        primary_fee = fee;
+    }
+
+    let now = ic_cdk::api::time();
+    let unlocked_amount = get_unlocked_stake_amount(caller, now);
+    if amount > unlocked_amount {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "un_stake_primary",
+            ExecutionError::InsufficientBalance {
+                required: amount,
+                available: unlocked_amount,
+                details: DEFAULT_INSUFFICIENT_BALANCE_ERROR.to_string(),
+                token: "primary".to_string(),
+            },
+        ));
+    }
+    if amount <= primary_fee {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "un_stake_primary",
+            ExecutionError::MinimumRequired {
+                required: primary_fee + 1,
+                provided: amount,
+                token: "primary".to_string(),
+                details: DEFAULT_MINIMUM_REQUIRED_ERROR.to_string(),
+            },
+        ));
+    }
+    // An unlocked remainder that cannot cover the fee could never be withdrawn.
+    let remaining = current_stake.amount - amount;
+    let unlocked_remaining = unlocked_amount - amount;
+    if unlocked_remaining > 0 && unlocked_remaining <= primary_fee {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "un_stake_primary",
+            ExecutionError::InvalidAmount {
+                amount,
+                reason: format!(
+                    "Remaining unlocked stake of {} would not cover the fee",
+                    unlocked_remaining
+                ),
+                details: "Use un_stake_all_primary to withdraw everything".to_string(),
+            },
+        ));
+    }
+
+    let post_fee_amount = amount - primary_fee;
+    withdraw_token(post_fee_amount, from_subaccount)
+        .await
+        .map_err(|e| {
+            ExecutionError::new_with_log(
+                caller,
+                "un_stake_primary",
+                ExecutionError::TransferFailed {
+                    source: "Canister".to_string(),
+                    dest: caller.to_string(),
+                    token: "primary".to_string(),
+                    amount: post_fee_amount,
+                    reason: DEFAULT_TRANSFER_FAILED_ERROR.to_string(),
+                    details: e.to_string(),
+                },
+            )
+        })?;
+    register_info_log(
+        caller,
+        "un_stake_primary",
+        &format!(
+            "Successfully withdrawn {} primary to {}.",
+            post_fee_amount, caller
+        ),
+    );
+
+    // Re-read the stake: rewards may have been distributed while the transfer ran.
+    settle_stake(caller, now);
+    STAKES.with(|stakes| {
+        let mut stakes_map = stakes.borrow_mut();
+        if let Some(mut stake) = stakes_map.get(&caller) {
+            stake.amount = stake.amount.saturating_sub(amount);
+            stakes_map.insert(caller, stake);
+        }
+    });
+    remove_from_stake_positions(caller, amount, now)
+        .map_err(|e| ExecutionError::new_with_log(caller, "un_stake_primary", e))?;
+    settle_stake(caller, now);
+
+    register_info_log(
+        caller,
+        "un_stake_primary",
+        &format!("Successfully unstaked {} primary, {} still staked.", amount, remaining),
+    );
+    Ok("Successfully unstaked!".to_string())
+}
+
 // This is now an internal function, called by a timer.
 async fn provide_liquidity_from_treasury() {
     let treasury_balance = LP_TREASURY.with(|cell| *cell.borrow().get());
@@ -881,9 +1269,7 @@ async fn provide_liquidity_from_treasury() {
         // Use a fixed 50% of the treasury for deployment.
         let deploy_percent = 50;
         
-        let primary_token_symbol = get_primary_token_symbol()
-            .await
-            .map_err(|e| ExecutionError::StateError(format!("Failed to get primary token symbol: {}", e)))?;
+        let config = get_config();
 
         let icp_to_deploy = (treasury_balance * deploy_percent) / 100;
         let icp_for_buyback = icp_to_deploy / 2;
@@ -891,9 +1277,9 @@ async fn provide_liquidity_from_treasury() {
 
         // 2. Execute buyback on DEX
         let primary_tokens_bought_nat = execute_swap_on_dex(
-            "ICP".to_string(),
+            config.icp_ledger_id,
             Nat::from(icp_for_buyback),
-            primary_token_symbol.clone(),
+            config.primary_token_id,
         )
         .await
         .map_err(|e| ExecutionError::StateError(format!("Failed to execute swap on DEX: {}", e)))?;
@@ -904,8 +1290,7 @@ async fn provide_liquidity_from_treasury() {
 
         // 3. Add liquidity to DEX with the assets we have.
         // The DEX will handle the ratio, leaving any "dust" unspent.
-        let lp_result = add_liquidity_to_kong(
-            primary_token_symbol,
+        let lp_result = add_liquidity_on_dex(
             primary_tokens_bought_nat.clone(),
             Nat::from(icp_for_pairing),
         )
@@ -924,12 +1309,12 @@ async fn provide_liquidity_from_treasury() {
 
         Ok(format!(
             "Successfully deployed {} e8s ICP ({}% of treasury). Bought {} primary tokens, added {} to LP.",
-            final_icp_spent, deploy_percent, primary_tokens_bought, lp_result.add_lp_token_amount
+            final_icp_spent, deploy_percent, primary_tokens_bought, lp_result.receipt
         ))
     }.await;
     
     // Log the outcome, regardless of success or failure.
-    let principal = get_principal(LBRY_FUN_CANISTER_ID);
+    let principal = get_config().lbry_fun_canister_id();
     match result {
         Ok(msg) => register_info_log(principal, "provide_liquidity_from_treasury", &msg),
         Err(e) => register_error_log(principal, "provide_liquidity_from_treasury", e),
@@ -948,13 +1333,24 @@ pub fn schedule_liquidity_provision() {
 }
 
 pub async fn distribute_reward() -> Result<String, ExecutionError> {
+    check_not_paused(PausableOp::DistributeReward)
+        .map_err(|e| ExecutionError::new_with_log(caller(), "distribute_reward", e))?;
+    // Finish the round in progress before starting another one.
+    if !get_distribution_round().in_progress {
+        start_distribution_round().await?;
+    }
+    process_distribution_chunk()
+}
+
+/// Pays out the fee shares and snapshots what the stakers get, so the round shares a
+/// fixed amount however many messages it takes to finish.
+async fn start_distribution_round() -> Result<(), ExecutionError> {
     register_info_log(
         caller(),
         "distribute_reward",
         "distribute_reward initiated.",
     );
-    let intervals = get_distribution_interval();
-    let staking_percentage = STAKING_REWARD_PERCENTAGE;
+    let staking_percentage = get_config().fee_schedule().release_rate_bps;
     let mut total_icp_available: u64 = 0;
 
     match fetch_canister_icp_balance().await {
@@ -970,8 +1366,11 @@ pub async fn distribute_reward() -> Result<String, ExecutionError> {
     let total_unclaimed_icp_reward: u64 = get_total_unclaimed_icp_reward();
     let total_archived_bal: u64 = get_total_archived_balance();
 
+    let creator_unclaimed: u64 = creator_revenue().unclaimed;
+
     let unclaimed_icps: u64 = total_unclaimed_icp_reward
         .checked_add(total_archived_bal)
+        .and_then(|sum| sum.checked_add(creator_unclaimed))
         .ok_or_else(|| {
             ExecutionError::new_with_log(
                 caller(),
@@ -979,8 +1378,8 @@ pub async fn distribute_reward() -> Result<String, ExecutionError> {
                 ExecutionError::AdditionOverflow {
                     operation: DEFAULT_ADDITION_OVERFLOW_ERROR.to_string(),
                     details: format!(
-                        "total_unclaimed_icp_reward: {} with total_archived_bal: {}",
-                        total_unclaimed_icp_reward, total_archived_bal
+                        "total_unclaimed_icp_reward: {} with total_archived_bal: {} and creator_unclaimed: {}",
+                        total_unclaimed_icp_reward, total_archived_bal, creator_unclaimed
                     ),
                 },
             )
@@ -1040,34 +1439,21 @@ pub async fn distribute_reward() -> Result<String, ExecutionError> {
         )
     })?;
 
-    // Calculate the 1% fee for the Alexandria project.
-    let alexandria_fee_share = total_icp_allocated.checked_div(100).ok_or_else(|| ExecutionError::DivisionFailed {
-        operation: "distribute_reward".to_string(),
-        details: "Failed to calculate alexandria_fee_share".to_string()
-    })?;
-
-    // Calculate the 49.5% share for the LP Treasury.
-    // Multiplying by 495 and dividing by 1000 is a safe way to handle 49.5%.
-    let lp_treasury_share = total_icp_allocated.checked_mul(495).ok_or_else(|| ExecutionError::MultiplicationOverflow {
-        operation: "distribute_reward".to_string(),
-        details: "Failed to calculate lp_treasury_share".to_string()
-    })?
-    .checked_div(1000).ok_or_else(|| ExecutionError::DivisionFailed {
-        operation: "distribute_reward".to_string(),
-        details: "Failed to calculate lp_treasury_share".to_string()
-    })?;
+    let fee_schedule = get_config().fee_schedule();
+    let share_of = |bps: u64| total_icp_allocated * bps as u128 / BASIS_POINTS as u128;
+    let alexandria_fee_share = share_of(fee_schedule.protocol_fee_bps);
+    let lp_treasury_share = share_of(fee_schedule.lp_share_bps);
+    let creator_share = share_of(fee_schedule.creator_share_bps);
 
     // The remainder is for the stakers. This avoids potential rounding errors.
-    let staker_share = total_icp_allocated.checked_sub(alexandria_fee_share).ok_or_else(|| ExecutionError::Underflow {
-        operation: "distribute_reward".to_string(),
-        details: "Failed to calculate staker_share".to_string()
-    })?
-    .checked_sub(lp_treasury_share).ok_or_else(|| ExecutionError::Underflow {
-        operation: "distribute_reward".to_string(),
-        details: "Failed to calculate staker_share".to_string()
-    })?;
+    let staker_share = total_icp_allocated
+        .checked_sub(alexandria_fee_share + lp_treasury_share + creator_share)
+        .ok_or_else(|| ExecutionError::Underflow {
+            operation: "distribute_reward".to_string(),
+            details: "Failed to calculate staker_share".to_string()
+        })?;
 
-    let lbry_fun_principal = Principal::from_text(LBRY_FUN_CANISTER_ID).expect("Invalid lbry_fun canister principal");
+    let lbry_fun_principal = get_config().lbry_fun_canister_id();
 
     if alexandria_fee_share > 0 {
         match send_icp(lbry_fun_principal, alexandria_fee_share as u64, None).await {
@@ -1087,7 +1473,12 @@ pub async fn distribute_reward() -> Result<String, ExecutionError> {
             }
         }
     }
-    
+
+    // Held for the creator until they claim it.
+    if creator_share > 0 {
+        add_creator_revenue(creator_share as u64)?;
+    }
+
     add_to_lp_treasury(lp_treasury_share as u64)?;
 
     if staker_share < 1_000_000 {
@@ -1101,9 +1492,78 @@ pub async fn distribute_reward() -> Result<String, ExecutionError> {
         ));
     }
 
-    let total_staked_primary = get_total_primary_staked().await? as u128;
+    let now = ic_cdk::api::time();
+    let previous_round = get_distribution_round();
+    set_distribution_round(DistributionRound {
+        round: previous_round.round + 1,
+        in_progress: true,
+        started_at: now,
+        completed_at: None,
+        total_icp_allocated,
+        staker_share,
+        icp_reward_per_primary: 0,
+        cursor: None,
+        stakes_processed: 0,
+        total_stakes: STAKES.with(|stakes| stakes.borrow().len()),
+    });
+    register_info_log(
+        caller(),
+        "distribute_reward",
+        &format!(
+            "Distribution round {} started: {} e8s to stakers.",
+            previous_round.round + 1,
+            staker_share
+        ),
+    );
+    Ok(())
+}
+
+/// Settles the next `DISTRIBUTION_CHUNK_SIZE` stakes of the round in progress, resuming
+/// after `cursor`, so their weights are current when the reward is shared. Once every
+/// stake and every expired lock is settled, the snapshotted `staker_share` goes into
+/// the reward index and the round completes.
+pub fn process_distribution_chunk() -> Result<String, ExecutionError> {
+    let mut round = get_distribution_round();
+    if !round.in_progress {
+        return Ok(format!("Distribution round {} already completed", round.round));
+    }
 
-    if total_staked_primary == 0 {
+    let now = ic_cdk::api::time();
+    let chunk: Vec<Principal> = STAKES.with(|stakes| {
+        let stakes = stakes.borrow();
+        let range = match round.cursor {
+            Some(cursor) => stakes.range((Bound::Excluded(cursor), Bound::Unbounded)),
+            None => stakes.range(..),
+        };
+        range
+            .take(DISTRIBUTION_CHUNK_SIZE)
+            .map(|(principal, _)| principal)
+            .collect()
+    });
+    for principal in &chunk {
+        settle_stake(*principal, now);
+    }
+    round.stakes_processed += chunk.len() as u64;
+    round.cursor = chunk.last().copied().or(round.cursor);
+    let walked = chunk.len() < DISTRIBUTION_CHUNK_SIZE;
+    // Locks that ran out since their stake was settled stop counting before anything
+    // is shared. That is capped per message too.
+    if !walked || settle_expired_locks(now) {
+        let msg = format!(
+            "Distribution round {}: {}/{} stakes settled",
+            round.round, round.stakes_processed, round.total_stakes
+        );
+        set_distribution_round(round);
+        return Ok(msg);
+    }
+
+    round.in_progress = false;
+    round.completed_at = Some(now);
+    let total_stake_weight = get_reward_index().total_weight;
+
+    if total_stake_weight == 0 {
+        // Not reserved, so the staker share is allocated again next round.
+        set_distribution_round(round);
         return Err(ExecutionError::new_with_log(
             caller(),
             "distribute_reward",
@@ -1112,7 +1572,8 @@ pub async fn distribute_reward() -> Result<String, ExecutionError> {
             },
         ));
     }
-    let mut icp_reward_per_primary = staker_share
+    let staker_share = round.staker_share;
+    let icp_reward_per_primary = staker_share
         .checked_mul(SCALING_FACTOR)
         .ok_or_else(||
             ExecutionError::new_with_log(
@@ -1128,7 +1589,7 @@ pub async fn distribute_reward() -> Result<String, ExecutionError> {
                 }
             )
         )?
-        .checked_div(total_staked_primary)
+        .checked_div(total_stake_weight)
         .ok_or_else(||
             ExecutionError::new_with_log(
                 caller(),
@@ -1136,99 +1597,43 @@ pub async fn distribute_reward() -> Result<String, ExecutionError> {
                 ExecutionError::DivisionFailed {
                     operation: DEFAULT_DIVISION_ERROR.to_string(),
                     details: format!(
-                        "total_icp_allocated * SCALING_FACTOR: {} divided by total_staked_primary: {}",
+                        "total_icp_allocated * SCALING_FACTOR: {} divided by total_stake_weight: {}",
                         staker_share * SCALING_FACTOR,
-                        total_staked_primary
+                        total_stake_weight
                     ),
                 }
             )
         )?;
 
-    let mut total_icp_reward: u128 = 0;
-    STAKES.with(
-        |stakes| -> Result<(), ExecutionError> {
-            let mut stakes_map = stakes.borrow_mut();
-
-            let keys: Vec<Principal> = stakes_map
-                .iter()
-                .map(|(principal, _)| principal.clone())
-                .collect();
-
-            for principal in keys {
-                // Retrieve.
-                if let Some(mut stake) = stakes_map.get(&principal) {
-                    let reward = (stake.amount as u128)
-                        .checked_mul(icp_reward_per_primary)
-                        .ok_or_else(||
-                            ExecutionError::new_with_log(
-                                caller(),
-                                "distribute_reward",
-                                ExecutionError::MultiplicationOverflow {
-                                    operation: DEFAULT_MULTIPLICATION_OVERFLOW_ERROR.to_string(),
-                                    details: format!(
-                                        "stake.amount: {} with icp_reward_per_primary: {}",
-                                        stake.amount,
-                                        icp_reward_per_primary
-                                    ),
-                                }
-                            )
-                        )?
-                        .checked_div(SCALING_FACTOR)
-                        .ok_or_else(||
-                            ExecutionError::new_with_log(
-                                caller(),
-                                "distribute_reward",
-                                ExecutionError::DivisionFailed {
-                                    operation: DEFAULT_DIVISION_ERROR.to_string(),
-                                    details: format!(
-                                        "stake.amount*icp_reward_per_primary: {} with SCALING_FACTOR: {}",
-                                        (stake.amount as u128) * icp_reward_per_primary,
-                                        SCALING_FACTOR
-                                    ),
-                                }
-                            )
-                        )?;
-
-                    total_icp_reward = total_icp_reward.checked_add(reward).ok_or_else(||
-                        ExecutionError::new_with_log(
-                            caller(),
-                            "distribute_reward",
-                            ExecutionError::AdditionOverflow {
-                                operation: DEFAULT_ADDITION_OVERFLOW_ERROR.to_string(),
-                                details: format!(
-                                    "total_icp_reward: {} with total_icp_reward: {}",
-                                    total_icp_reward,
-                                    total_icp_reward
-                                ),
-                            }
-                        )
-                    )?;
-
-                    stake.reward_icp = stake.reward_icp.checked_add(reward as u64).ok_or_else(||
-                        ExecutionError::new_with_log(
-                            caller(),
-                            "distribute_reward",
-                            ExecutionError::AdditionOverflow {
-                                operation: DEFAULT_ADDITION_OVERFLOW_ERROR.to_string(),
-                                details: format!(
-                                    "stake.reward_icp: {} with reward: {}",
-                                    stake.reward_icp,
-                                    reward
-                                ),
-                            }
-                        )
-                    )?;
-
-                    // Reinsert the updated stake back into the map.
-                    stakes_map.insert(principal, stake);
+    // Stakers pick their share up when their stake is next settled. Each share rounds
+    // down, so the sum reserved here covers all of them.
+    let total_icp_reward = icp_reward_per_primary
+        .checked_mul(total_stake_weight)
+        .ok_or_else(||
+            ExecutionError::new_with_log(
+                caller(),
+                "distribute_reward",
+                ExecutionError::MultiplicationOverflow {
+                    operation: DEFAULT_MULTIPLICATION_OVERFLOW_ERROR.to_string(),
+                    details: format!(
+                        "icp_reward_per_primary: {} with total_stake_weight: {}",
+                        icp_reward_per_primary,
+                        total_stake_weight
+                    ),
                 }
-            }
-
-            Ok(())
-        }
-    )?;
+            )
+        )?
+        / SCALING_FACTOR;
+    // Completed before the index moves, so a failure below cannot share the round twice.
+    round.icp_reward_per_primary = icp_reward_per_primary;
+    let msg = format!(
+        "Distribution round {} completed: {} stakes settled, {} e8s to stakers.",
+        round.round, round.stakes_processed, staker_share
+    );
+    set_distribution_round(round);
+    add_to_reward_index(icp_reward_per_primary);
 
-    let index = intervals % MAX_DAYS;
+    let index = get_distribution_interval() % MAX_DAYS;
 
     APY.with(|apy| {
         let mut apy_map = apy.borrow_mut();
@@ -1240,13 +1645,9 @@ pub async fn distribute_reward() -> Result<String, ExecutionError> {
     add_to_unclaimed_amount(total_icp_reward as u64)?;
 
     add_to_distribution_intervals(1)?;
-    register_info_log(
-        caller(),
-        "distribute_reward",
-        "Successfully distributed reward. Completed.",
-    );
+    register_info_log(caller(), "distribute_reward", &msg);
 
-    Ok("Success".to_string())
+    Ok(msg)
 }
 
 #[update(guard = "not_anon")]
@@ -1256,7 +1657,7 @@ async fn claim_icp_reward(from_subaccount: Option<[u8; 32]>) -> Result<String, E
         CallerGuard::new(caller).map_err(|e| ExecutionError::Unauthorized(e.to_string()))?;
     register_info_log(caller, "claim_icp_reward", "claim_icp_reward initiated.");
 
-    let caller_stake_reward: Option<Stake> = get_stake(caller);
+    let caller_stake_reward: Option<Stake> = settle_stake(caller, ic_cdk::api::time());
     match caller_stake_reward {
         Some(stake) => {
             if stake.reward_icp <= 1000_000 {
@@ -1344,9 +1745,13 @@ async fn claim_icp_reward(from_subaccount: Option<[u8; 32]>) -> Result<String, E
                     amount: 0,
                     time: ic_cdk::api::time(),
                     reward_icp: 0,
+                    auto_compound: None,
+                    reward_checkpoint: None,
+                    weight: None,
                 });
 
-                current_stake.reward_icp = 0;
+                // Rewards settled while the transfer ran stay with the stake.
+                current_stake.reward_icp = current_stake.reward_icp.saturating_sub(stake.reward_icp);
 
                 // Reinsert the updated stake back into the map.
                 stakes_map.insert(caller, current_stake);
@@ -1369,6 +1774,311 @@ async fn claim_icp_reward(from_subaccount: Option<[u8; 32]>) -> Result<String, E
     }
 }
 
+/// Pays the creator's unclaimed revenue to their payout principal, or to the creator if
+/// none is set. Callable by holders of the Creator role or the payout principal; either
+/// way it only ever pays the payout. Returns the amount sent, after the transfer fee.
+#[update(guard = "not_anon")]
+async fn claim_creator_revenue() -> Result<u64, ExecutionError> {
+    let caller = ic_cdk::caller();
+    let _guard =
+        CallerGuard::new(caller).map_err(|e| ExecutionError::Unauthorized(e.to_string()))?;
+    let config = get_config();
+    let Some(creator) = config.creator else {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "claim_creator_revenue",
+            ExecutionError::StateError("This token has no creator".to_string()),
+        ));
+    };
+    let payout = config.creator_payout.unwrap_or(creator);
+    if caller != payout && !has_role(caller, Role::Creator) {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "claim_creator_revenue",
+            ExecutionError::Unauthorized(DEFAULT_UNAUTHORIZED_ERROR.to_string()),
+        ));
+    }
+
+    let revenue = creator_revenue();
+    if revenue.unclaimed <= ICP_TRANSFER_FEE {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "claim_creator_revenue",
+            ExecutionError::MinimumRequired {
+                required: ICP_TRANSFER_FEE + 1,
+                provided: revenue.unclaimed,
+                token: "ICP".to_string(),
+                details: DEFAULT_MINIMUM_REQUIRED_ERROR.to_string(),
+            },
+        ));
+    }
+    let amount = revenue.unclaimed;
+    let amount_after_fee = amount - ICP_TRANSFER_FEE;
+
+    // Marked as claimed first so a reward run during the transfer doesn't count it as free.
+    set_creator_revenue(CreatorRevenue {
+        accrued: revenue.accrued,
+        unclaimed: 0,
+        claimed: revenue.claimed.saturating_add(amount),
+    })?;
+
+    if let Err(e) = send_icp(payout, amount_after_fee, None).await {
+        let mut current = creator_revenue();
+        current.unclaimed = current.unclaimed.saturating_add(amount);
+        current.claimed = current.claimed.saturating_sub(amount);
+        set_creator_revenue(current)?;
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "claim_creator_revenue",
+            ExecutionError::TransferFailed {
+                source: "canister".to_string(),
+                dest: payout.to_string(),
+                token: "ICP".to_string(),
+                amount: amount_after_fee,
+                details: e,
+                reason: DEFAULT_TRANSFER_FAILED_ERROR.to_string(),
+            },
+        ));
+    }
+
+    register_info_log(
+        caller,
+        "claim_creator_revenue",
+        &format!(
+            "Successfully sent {} ICP (e8s) of creator revenue to {}",
+            amount_after_fee, payout
+        ),
+    );
+    Ok(amount_after_fee)
+}
+
+#[update(guard = "not_anon")]
+fn set_auto_compound(enabled: bool) -> Result<String, ExecutionError> {
+    let caller = ic_cdk::caller();
+    let _guard =
+        CallerGuard::new(caller).map_err(|e| ExecutionError::Unauthorized(e.to_string()))?;
+
+    STAKES.with(|stakes| {
+        let mut stakes_map = stakes.borrow_mut();
+        let mut stake = stakes_map.get(&caller).ok_or_else(|| {
+            ExecutionError::new_with_log(
+                caller,
+                "set_auto_compound",
+                ExecutionError::StateError("No stake found for caller".to_string()),
+            )
+        })?;
+        stake.auto_compound = Some(enabled);
+        stakes_map.insert(caller, stake);
+        Ok::<(), ExecutionError>(())
+    })?;
+
+    register_info_log(
+        caller,
+        "set_auto_compound",
+        &format!("Auto-compounding {}", if enabled { "enabled" } else { "disabled" }),
+    );
+    Ok(format!("Auto-compounding set to {}", enabled))
+}
+
+/// Swaps the accrued rewards of every auto-compounding staker into primary in one
+/// DEX trade and stakes each staker's pro rata share as a new unlocked position.
+pub async fn compound_rewards() -> Result<String, ExecutionError> {
+    let canister = ic_cdk::api::id();
+
+    // Holding each staker's guard keeps their own claims and unstakes out of the way.
+    let mut guards = Vec::new();
+    let mut compounding: Vec<(Principal, u64)> = Vec::new();
+    let now = ic_cdk::api::time();
+    let candidates: Vec<Principal> = STAKES.with(|stakes| {
+        stakes
+            .borrow()
+            .iter()
+            .filter(|(_, stake)| stake.auto_compound == Some(true))
+            .filter(|(_, stake)| with_pending_reward(stake.clone()).reward_icp >= MIN_COMPOUND_ICP_E8S)
+            .map(|(principal, _)| principal)
+            .collect()
+    });
+    for principal in candidates {
+        if let Ok(guard) = CallerGuard::new(principal) {
+            if let Some(stake) = settle_stake(principal, now) {
+                guards.push(guard);
+                compounding.push((principal, stake.reward_icp));
+            }
+        }
+    }
+
+    let total_icp: u64 = compounding.iter().map(|(_, reward_icp)| reward_icp).sum();
+    // The approval and the DEX's transfer_from each cost one ledger fee.
+    let swap_amount = total_icp.saturating_sub(2 * ICP_TRANSFER_FEE);
+    if swap_amount == 0 {
+        return Ok("Nothing to compound".to_string());
+    }
+
+    // Take the rewards out before the swap so nothing else can pay them out meanwhile.
+    set_reward_icp(&compounding, false);
+    sub_to_unclaimed_amount(total_icp)?;
+
+    let config = get_config();
+    let quoted = match get_dex_quote(
+        config.icp_ledger_id,
+        Nat::from(swap_amount),
+        config.primary_token_id,
+    )
+    .await
+    {
+        Ok(quoted) => quoted,
+        Err(e) => {
+            // Nothing left the canister yet.
+            set_reward_icp(&compounding, true);
+            add_to_unclaimed_amount(total_icp)?;
+            return Err(ExecutionError::new_with_log(
+                canister,
+                "compound_rewards",
+                ExecutionError::StateError(format!("Failed to get quote from DEX: {}", e)),
+            ));
+        }
+    };
+    let swapped = swap_at_quote(
+        config.icp_ledger_id,
+        Nat::from(swap_amount),
+        config.primary_token_id,
+        quoted,
+    )
+    .await;
+    let bought = match swapped {
+        Ok(bought) => bought,
+        Err(e) => {
+            // A failed reply does not mean the DEX kept its hands off the ICP, so the
+            // rewards are held per staker until a controller resolves them.
+            hold_pending_compounds(&compounding, ic_cdk::api::time());
+            register_info_log(
+                canister,
+                "compound_rewards",
+                &format!(
+                    "Swap failed but {} ICP (e8s) may have left for the DEX; rewards of {} stakers are held as pending compounds",
+                    swap_amount,
+                    compounding.len()
+                ),
+            );
+            return Err(ExecutionError::new_with_log(
+                canister,
+                "compound_rewards",
+                ExecutionError::StateError(format!("Failed to execute swap on DEX: {}", e)),
+            ));
+        }
+    };
+
+    let now = ic_cdk::api::time();
+    for (principal, reward_icp) in &compounding {
+        // Rounding dust stays in the canister and is shared at the next distribution.
+        let share = bought.clone() * Nat::from(*reward_icp) / Nat::from(total_icp);
+        match u64::try_from(share.0) {
+            Ok(share) => {
+                compound_into_stake(*principal, share, now);
+                register_info_log(
+                    *principal,
+                    "compound_rewards",
+                    &format!("Compounded {} ICP (e8s) into {} primary (e8s)", reward_icp, share),
+                );
+            }
+            // The ICP is spent; hold the reward rather than credit ICP the canister no longer has.
+            Err(_) => hold_pending_compounds(&[(*principal, *reward_icp)], now),
+        }
+    }
+
+    Ok(format!(
+        "Compounded {} ICP (e8s) for {} stakers into {} primary (e8s)",
+        total_icp,
+        compounding.len(),
+        bought
+    ))
+}
+
+/// Adds `share` primary to the stake as a new unlocked position.
+fn compound_into_stake(principal: Principal, share: u64, now: u64) {
+    if share == 0 {
+        return;
+    }
+    settle_stake(principal, now);
+    STAKES.with(|stakes| {
+        let mut stakes_map = stakes.borrow_mut();
+        if let Some(mut stake) = stakes_map.get(&principal) {
+            stake.amount = stake.amount.saturating_add(share);
+            stakes_map.insert(principal, stake);
+        }
+    });
+    add_stake_position(principal, share, now, None);
+    settle_stake(principal, now);
+}
+
+/// Resolves a compound held after a failed swap. With `primary`, the DEX is taken to have
+/// spent the ICP and that much primary, already back in this canister, is staked for the
+/// staker; without it, the ICP reward is given back. Callable by controllers.
+#[update]
+pub fn resolve_pending_compound(
+    principal: Principal,
+    primary: Option<u64>,
+) -> Result<PendingCompound, ExecutionError> {
+    let caller = caller();
+    if !has_role(caller, Role::Controller) {
+        return Err(ExecutionError::new_with_log(
+            caller,
+            "resolve_pending_compound",
+            ExecutionError::Unauthorized(DEFAULT_UNAUTHORIZED_ERROR.to_string()),
+        ));
+    }
+    let _guard = CallerGuard::new(principal).map_err(|e| {
+        ExecutionError::new_with_log(caller, "resolve_pending_compound", ExecutionError::StateError(e))
+    })?;
+    let pending = take_pending_compound(principal).ok_or_else(|| {
+        ExecutionError::new_with_log(
+            caller,
+            "resolve_pending_compound",
+            ExecutionError::StateError(format!("No pending compound for {}", principal)),
+        )
+    })?;
+
+    match primary {
+        Some(share) => compound_into_stake(principal, share, ic_cdk::api::time()),
+        None => {
+            set_reward_icp(&[(principal, pending.reward_icp)], true);
+            add_to_unclaimed_amount(pending.reward_icp)
+                .map_err(|e| ExecutionError::new_with_log(caller, "resolve_pending_compound", e))?;
+        }
+    }
+    register_info_log(
+        caller,
+        "resolve_pending_compound",
+        &format!(
+            "Resolved {} ICP (e8s) held for {}: {}",
+            pending.reward_icp,
+            principal,
+            match primary {
+                Some(share) => format!("staked {} primary (e8s)", share),
+                None => "ICP reward restored".to_string(),
+            }
+        ),
+    );
+    Ok(pending)
+}
+
+/// Zeroes the listed stakers' rewards, or gives the listed amounts back on `restore`.
+fn set_reward_icp(rewards: &[(Principal, u64)], restore: bool) {
+    STAKES.with(|stakes| {
+        let mut stakes_map = stakes.borrow_mut();
+        for (principal, reward_icp) in rewards {
+            if let Some(mut stake) = stakes_map.get(principal) {
+                stake.reward_icp = if restore {
+                    stake.reward_icp.saturating_add(*reward_icp)
+                } else {
+                    stake.reward_icp.saturating_sub(*reward_icp)
+                };
+                stakes_map.insert(*principal, stake);
+            }
+        }
+    });
+}
+
 pub async fn get_icp_rate_in_cents() -> Result<u64, ExecutionError> {
     register_info_log(
         caller(),
@@ -1388,7 +2098,7 @@ pub async fn get_icp_rate_in_cents() -> Result<u64, ExecutionError> {
         timestamp: None,
     };
 
-    let xrc_canister_id = Principal::from_text(XRC_CANISTER_ID).unwrap();
+    let xrc_canister_id = get_config().xrc_canister_id();
 
     let call_result: Result<Vec<u8>, (ic_cdk::api::call::RejectionCode, String)> =
         ic_cdk::api::call::call_raw(
@@ -1454,12 +2164,11 @@ pub async fn get_icp_rate_in_cents() -> Result<u64, ExecutionError> {
                                         },
                                     )
                                 })?;
-                        // Update the closure to handle potential errors
-                        update_current_secondary_ratio(price_in_cents, time)?;
+                        let ratio = update_current_secondary_ratio(price_in_cents, time)?;
                         register_info_log(
                                 caller(),
                                 "get_icp_rate_in_cents",
-                                &format!("get_icp_rate_in_cents process completed successfully.Got {} ICP price in cents", price_in_cents)
+                                &format!("get_icp_rate_in_cents process completed successfully.Got {} ICP price in cents, secondary ratio now {}", price_in_cents, ratio)
                             );
 
                         Ok(price_in_cents)
@@ -1,5 +1,5 @@
 use crate::{
-    get_config, get_distribution_interval, get_distribution_interval_mem, get_secondary_ratio_mem, get_stake, get_total_archived_balance, get_total_archived_balance_mem, get_total_unclaimed_icp_reward, get_total_unclaimed_icp_reward_mem, ArchiveBalance, ExecutionError, SecondaryRatio, Log, LogType, ARCHIVED_TRANSACTION_LOG, CONFIGS, DEFAULT_ADDITION_OVERFLOW_ERROR, DEFAULT_MULTIPLICATION_OVERFLOW_ERROR, DEFAULT_UNDERFLOW_ERROR, LOGS, LOG_COUNTER, PRIMARY_FEE
+    get_config, get_distribution_interval, get_price_readings, push_price_reading, PriceReading, get_distribution_interval_mem, get_secondary_ratio_mem, get_stake, get_total_archived_balance, get_total_archived_balance_mem, get_total_unclaimed_icp_reward, get_total_unclaimed_icp_reward_mem, ArchiveBalance, ExecutionError, LaunchUsage, get_pause_state, set_pause_state, PausableOp, PauseInfo, PauseReason, SecondaryRatio, Log, LogType, LAUNCH_USAGE, ARCHIVED_TRANSACTION_LOG, CONFIGS, DEFAULT_ADDITION_OVERFLOW_ERROR, DEFAULT_MULTIPLICATION_OVERFLOW_ERROR, DEFAULT_UNDERFLOW_ERROR, LOGS, LOG_COUNTER, PRIMARY_FEE
 };
 use candid::{ CandidType, Nat, Principal };
 use ic_cdk::api::call::RejectionCode;
@@ -9,15 +9,26 @@ use ic_ledger_types::Subaccount;
 use ic_ledger_types::{ AccountBalanceArgs, DEFAULT_SUBACCOUNT };
 use serde::Deserialize;
 
-pub const STAKING_REWARD_PERCENTAGE: u64 = 100; // 1%
-pub const XRC_CANISTER_ID: &str = "uf6dk-hyaaa-aaaaq-qaaaq-cai";
-pub const ICP_CANISTER_ID: &str = "ryjl3-tyaaa-aaaaa-aaaba-cai";
-
 pub const ICP_TRANSFER_FEE: u64 = 10_000;
 pub const MAX_DAYS: u32 = 30;
-pub const SCALING_FACTOR: u128 = 1_000_000_000_000; // Adjust based on your precision needs
+pub const SCALING_FACTOR: u128 = tokenomics_core::REWARD_SCALING_FACTOR;
+// Lock multipliers are in basis points of the unlocked weight.
+pub const LOCK_MULTIPLIER_BASE: u64 = 10_000;
+pub const DEFAULT_THIRTY_DAYS_MULTIPLIER: u64 = 12_500; // 1.25x
+pub const DEFAULT_NINETY_DAYS_MULTIPLIER: u64 = 15_000; // 1.5x
+pub const DEFAULT_ONE_YEAR_MULTIPLIER: u64 = 20_000; // 2x
+pub const MAX_LOCK_MULTIPLIER: u64 = 50_000; // 5x
 pub const BURN_CYCLE_FEE: u64 = 10_000_000_000;
 pub const DEFAULT_SECONDARY_RATIO: u64 = 400;
+// Secondary ratio oracle defaults: a week of daily readings, 10% moves, one missed fetch.
+pub const DEFAULT_ORACLE_WINDOW_SIZE: u64 = 7;
+pub const DEFAULT_MAX_RATIO_CHANGE_BPS: u64 = 1_000;
+pub const DEFAULT_MAX_PRICE_STALENESS_SECS: u64 = 2 * 24 * 60 * 60;
+pub const MAX_ORACLE_WINDOW_SIZE: u64 = 365;
+// Circuit breaker defaults: a 20% price jump between readings, or five failed transfers.
+pub const DEFAULT_BREAKER_MAX_PRICE_MOVE_BPS: u64 = 2_000;
+pub const DEFAULT_BREAKER_MAX_TRANSFER_FAILURES: u32 = 5;
+pub const BASIS_POINTS: u64 = 10_000;
 pub const E8S: u64 = 100_000_000;
 pub const LOGS_LIMIT: u64 = 100_000;
 
@@ -198,22 +209,268 @@ pub(crate) fn sub_to_unclaimed_amount(amount: u64) -> Result<(), ExecutionError>
     Ok(())
 }
 
+/// Records an XRC reading and moves the secondary ratio towards the time-weighted
+/// average of the buffered readings, by at most `max_change_bps` per reading.
 pub(crate) fn update_current_secondary_ratio(
-    new_ratio: u64,
+    price_in_cents: u64,
     current_time: u64
-) -> Result<(), ExecutionError> {
-    // Get the StableBTreeMap for secondary ratio
+) -> Result<u64, ExecutionError> {
+    let oracle = get_config().oracle();
     let mut secondary_ratio_map = get_secondary_ratio_mem();
 
-    // Create a new SecondaryRatio instance with the provided values
-    let secondary_ratio = SecondaryRatio {
-        ratio: new_ratio,
-        time: current_time,
+    let mut readings = get_price_readings();
+    readings.push(PriceReading { time: current_time, price_cents: price_in_cents, ratio: 0 });
+    let skip = readings.len().saturating_sub(oracle.window_size.max(1) as usize);
+    let average = time_weighted_average(&readings[skip..]);
+
+    // A jump this large is more likely a bad reading or a market event than a price
+    // to trade at.
+    if let Some(previous) = readings.iter().rev().nth(1) {
+        let move_bps = ((previous.price_cents.abs_diff(price_in_cents) as u128)
+            * (BASIS_POINTS as u128)
+            / (previous.price_cents.max(1) as u128)) as u64;
+        if move_bps > get_config().circuit_breaker().max_price_move_bps {
+            trip_circuit_breaker(
+                &[PausableOp::Swap, PausableOp::BurnSecondary],
+                PauseReason::PriceMove {
+                    previous_cents: previous.price_cents,
+                    current_cents: price_in_cents,
+                    move_bps,
+                },
+            );
+        }
+    }
+
+    let ratio = match secondary_ratio_map.get(&()) {
+        Some(previous) => {
+            let max_change = ((previous.ratio as u128) * (oracle.max_change_bps as u128)
+                / (BASIS_POINTS as u128)) as u64;
+            average.clamp(
+                previous.ratio.saturating_sub(max_change),
+                previous.ratio.saturating_add(max_change),
+            )
+        }
+        None => average,
     };
 
-    // Insert or update the SecondaryRatio value at the key `()`
-    secondary_ratio_map.insert((), secondary_ratio);
-    Ok(())
+    push_price_reading(
+        PriceReading { time: current_time, price_cents: price_in_cents, ratio },
+        oracle.window_size,
+    );
+    secondary_ratio_map.insert((), SecondaryRatio { ratio, time: current_time });
+    Ok(ratio)
+}
+
+// Trapezoidal average, so each reading counts for the time around it.
+fn time_weighted_average(readings: &[PriceReading]) -> u64 {
+    let (first, last) = match (readings.first(), readings.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return DEFAULT_SECONDARY_RATIO,
+    };
+    let span = last.time.saturating_sub(first.time) as u128;
+    if span == 0 {
+        let sum: u128 = readings.iter().map(|r| r.price_cents as u128).sum();
+        return (sum / readings.len() as u128) as u64;
+    }
+    let weighted: u128 = readings
+        .windows(2)
+        .map(|pair| {
+            let elapsed = pair[1].time.saturating_sub(pair[0].time) as u128;
+            (pair[0].price_cents as u128 + pair[1].price_cents as u128) * elapsed
+        })
+        .sum();
+    (weighted / (2 * span)) as u64
+}
+
+/// Rejects calls landing after the caller's `deadline` (nanoseconds since the epoch).
+pub(crate) fn check_deadline(deadline: Option<u64>) -> Result<(), ExecutionError> {
+    let now = ic_cdk::api::time();
+    match deadline {
+        Some(deadline) if now > deadline => Err(ExecutionError::DeadlineExceeded { deadline, now }),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects outputs below the caller's `min_amount_out`.
+pub(crate) fn check_min_amount_out(
+    token: &str,
+    amount_out: u64,
+    min_amount_out: Option<u64>
+) -> Result<(), ExecutionError> {
+    match min_amount_out {
+        Some(min_amount_out) if amount_out < min_amount_out => {
+            Err(ExecutionError::SlippageExceeded {
+                token: token.to_string(),
+                min_amount_out,
+                amount_out,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Which launch cap an operation counts against.
+#[derive(Clone, Copy)]
+pub(crate) enum LaunchCap {
+    IcpSwapped,
+    SecondaryBurned,
+}
+
+/// Rejects `amount` if it would take `principal` over their launch cap. Does nothing
+/// once the protection window has closed, or if the launch has none.
+pub(crate) fn check_launch_cap(
+    principal: Principal,
+    cap: LaunchCap,
+    amount: u64
+) -> Result<(), ExecutionError> {
+    let config = get_config();
+    let Some(protection) = config.launch_protection else {
+        return Ok(());
+    };
+    let installed_at = config.installed_at.unwrap_or_default();
+    if !protection.is_active(installed_at, config.liquidity_provided_at, ic_cdk::api::time()) {
+        return Ok(());
+    }
+    let usage = launch_usage_of(principal);
+    let (token, limit, used) = match cap {
+        LaunchCap::IcpSwapped => ("ICP", protection.max_icp_swapped, usage.icp_swapped),
+        LaunchCap::SecondaryBurned => {
+            ("secondary", protection.max_secondary_burned, usage.secondary_burned)
+        }
+    };
+    match limit {
+        Some(limit) if used.saturating_add(amount) > limit => {
+            Err(ExecutionError::LaunchCapExceeded {
+                token: token.to_string(),
+                cap: limit,
+                used,
+                requested: amount,
+                window_ends_at: Some(protection.ends_at(installed_at, config.liquidity_provided_at)),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Counts `amount` against `principal`'s launch cap while the window is open.
+pub(crate) fn record_launch_usage(principal: Principal, cap: LaunchCap, amount: u64) {
+    let config = get_config();
+    let active = config
+        .launch_protection
+        .is_some_and(|protection| {
+            protection.is_active(
+                config.installed_at.unwrap_or_default(),
+                config.liquidity_provided_at,
+                ic_cdk::api::time(),
+            )
+        });
+    if !active {
+        return;
+    }
+    let mut usage = launch_usage_of(principal);
+    match cap {
+        LaunchCap::IcpSwapped => usage.icp_swapped = usage.icp_swapped.saturating_add(amount),
+        LaunchCap::SecondaryBurned => {
+            usage.secondary_burned = usage.secondary_burned.saturating_add(amount)
+        }
+    }
+    LAUNCH_USAGE.with(|usages| usages.borrow_mut().insert(principal, usage));
+}
+
+pub(crate) fn launch_usage_of(principal: Principal) -> LaunchUsage {
+    LAUNCH_USAGE.with(|usages| usages.borrow().get(&principal)).unwrap_or_default()
+}
+
+/// Rejects `op` while it is paused.
+pub(crate) fn check_not_paused(op: PausableOp) -> Result<(), ExecutionError> {
+    match get_pause_state().paused.get(&op) {
+        Some(info) => Err(ExecutionError::Paused {
+            operation: format!("{:?}", op),
+            reason: format!("{:?}", info.reason),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Pauses every operation in `ops` that isn't paused yet, keeping the reason of those
+/// that are.
+pub(crate) fn pause_operations(
+    ops: &[PausableOp],
+    paused_by: Principal,
+    reason: PauseReason,
+) -> Result<(), ExecutionError> {
+    let mut state = get_pause_state();
+    for op in ops {
+        state.paused.entry(*op).or_insert_with(|| PauseInfo {
+            paused_at: ic_cdk::api::time(),
+            paused_by,
+            reason: reason.clone(),
+        });
+    }
+    set_pause_state(state)
+}
+
+/// Pauses `ops` on an anomaly and logs why, or that the pause could not be saved.
+pub(crate) fn trip_circuit_breaker(ops: &[PausableOp], reason: PauseReason) {
+    register_error_log(
+        ic_cdk::api::id(),
+        "circuit_breaker",
+        ExecutionError::Paused {
+            operation: format!("{:?}", ops),
+            reason: format!("{:?}", reason),
+        },
+    );
+    if let Err(e) = pause_operations(ops, ic_cdk::api::id(), reason) {
+        register_error_log(ic_cdk::api::id(), "circuit_breaker", e);
+    }
+}
+
+/// Counts failed outgoing ICP transfers in a row, and pauses everything that sends ICP
+/// once there are too many.
+pub(crate) fn record_transfer_outcome(succeeded: bool) {
+    let mut state = get_pause_state();
+    if succeeded {
+        if state.consecutive_transfer_failures == 0 {
+            return;
+        }
+        state.consecutive_transfer_failures = 0;
+        if let Err(e) = set_pause_state(state) {
+            register_error_log(ic_cdk::api::id(), "record_transfer_outcome", e);
+        }
+        return;
+    }
+    state.consecutive_transfer_failures = state.consecutive_transfer_failures.saturating_add(1);
+    let count = state.consecutive_transfer_failures;
+    if let Err(e) = set_pause_state(state) {
+        register_error_log(ic_cdk::api::id(), "record_transfer_outcome", e);
+    }
+    if count >= get_config().circuit_breaker().max_transfer_failures {
+        trip_circuit_breaker(
+            &[PausableOp::Swap, PausableOp::BurnSecondary, PausableOp::DistributeReward],
+            PauseReason::TransferFailures { count },
+        );
+    }
+}
+
+/// The secondary ratio, unless it is older than the oracle allows.
+pub(crate) fn get_fresh_secondary_ratio(current_time: u64) -> Result<u64, ExecutionError> {
+    let max_staleness_secs = get_config().oracle().max_staleness_secs;
+    match get_secondary_ratio_mem().get(&()) {
+        Some(secondary_ratio)
+            if current_time.saturating_sub(secondary_ratio.time) <= max_staleness_secs =>
+        {
+            Ok(secondary_ratio.ratio)
+        }
+        Some(secondary_ratio) => Err(ExecutionError::RateLookupFailed {
+            details: format!(
+                "ICP price is stale: last updated at {}, more than {} seconds ago",
+                secondary_ratio.time, max_staleness_secs
+            ),
+        }),
+        None => Err(ExecutionError::RateLookupFailed {
+            details: "No ICP price has been fetched yet".to_string(),
+        }),
+    }
 }
 pub(crate) fn update_primary_fee(fee: u64) -> Result<(), ExecutionError> {
     PRIMARY_FEE.with(|fee_cell| {
@@ -259,45 +516,6 @@ pub(crate) fn archive_user_transaction(amount: u64) -> Result<String, ExecutionE
     Ok("Transaction added successfully!".to_string())
 }
 
-pub(crate) async fn get_total_primary_staked() -> Result<u64, ExecutionError> {
-    let primary_token_id=get_config().primary_token_id;
-    let canister_id = ic_cdk::api::id();
-    let args = BalanceOfArgs {
-        owner: canister_id,
-        subaccount: None, // Set subaccount to None, or Some(...) if needed
-    };
-
-    let result: Result<(Nat,), (RejectionCode, String)> = ic_cdk::call(
-        primary_token_id,
-        "icrc1_balance_of",
-        (args,)
-    ).await;
-
-    match result {
-        Ok((balance,)) =>
-            balance.0
-                .try_into()
-                .map_err(|_|
-                    ExecutionError::new_with_log(
-                        caller(),
-                        "get_total_primary_staked",
-                        ExecutionError::StateError("Balance exceeds u64 max value".to_string())
-                    )
-                ),
-        Err((code, msg)) =>
-            Err(
-                ExecutionError::new_with_log(
-                    caller(),
-                    "get_total_primary_staked",
-                    ExecutionError::CanisterCallFailed {
-                        canister: "primary".to_string(),
-                        method: "icrc1_balance_of".to_string(),
-                        details: format!("Rejection code: {:?}, Message: {}", code, msg),
-                    }
-                )
-            ),
-    }
-}
 pub(crate) async fn fetch_canister_icp_balance() -> Result<u64, ExecutionError> {
     let canister_id = ic_cdk::api::id();
     let account_identifier = AccountIdentifier::new(&canister_id, &DEFAULT_SUBACCOUNT);
@@ -395,12 +613,6 @@ pub fn register_error_log(caller: Principal, function: &str, error: ExecutionErr
     LOGS.with(|logs| logs.borrow_mut().insert(log_id, log_entry));
 }
 
-#[derive(CandidType)]
-struct BalanceOfArgs {
-    owner: Principal,
-    subaccount: Option<Vec<u8>>,
-}
-
 #[derive(CandidType, Deserialize, Debug)]
 pub enum ExchangeRateError {
     AnonymousPrincipalNotAllowed,
@@ -430,70 +642,6 @@ pub struct Account {
     pub subaccount: Option<[u8; 32]>,
 }
 
-#[derive(CandidType, Deserialize, Debug)]
-pub struct ApproveArgs {
-    pub from_subaccount: Option<[u8; 32]>,
-    pub spender: Account,
-    pub amount: Nat,
-    pub expected_allowance: Option<Nat>,
-    pub expires_at: Option<u64>,
-    pub fee: Option<Nat>,
-    pub memo: Option<Vec<u8>>,
-    pub created_at_time: Option<u64>,
-}
-
-#[derive(CandidType, Deserialize, Debug)]
-pub enum ApproveError {
-    BadFee { expected_fee: Nat },
-    BadMemo,
-    Duplicate,
-    TemporarilyUnavailable,
-    InsufficientFunds { balance: Nat },
-    AllowanceChanged { current_allowance: Nat },
-    Expired { ledger_time: u64 },
-    TooOld,
-    CreatedInFuture { ledger_time: u64 },
-    GenericError{ error_code: Nat, message: String},
-    Throttled,
-    Unauthorized{ tokens: Nat },
-}
-
-pub(crate) fn get_primary_canister_id() -> Principal {
-    get_config().primary_token_id
-}
-
-pub(crate) async fn icrc2_approve(
-    token_canister_id: Principal,
-    spender: Principal,
-    amount: Nat,
-) -> Result<Nat, String> {
-    let args = ApproveArgs {
-        from_subaccount: None,
-        spender: Account {
-            owner: spender,
-            subaccount: None,
-        },
-        amount,
-        expected_allowance: None,
-        expires_at: None,
-        fee: None,
-        memo: None,
-        created_at_time: None,
-    };
-
-    let result: Result<(Result<Nat, ApproveError>,), (RejectionCode, String)> =
-        ic_cdk::call(token_canister_id, "icrc2_approve", (args,)).await;
-
-    match result {
-        Ok((Ok(value),)) => Ok(value),
-        Ok((Err(err),)) => Err(format!("ICRC-2 approve failed: {:?}", err)),
-        Err((code, msg)) => Err(format!(
-            "ICRC-2 approve call failed: rejection code {:?}, message: {}",
-            code, msg
-        )),
-    }
-}
-
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct AllowanceArgs {
     pub account: Account,
@@ -546,13 +694,3 @@ pub(crate) async fn icrc2_allowance(
         )),
     }
 }
-
-pub(crate) async fn get_primary_token_symbol() -> Result<String, String> {
-    let primary_token_id = get_config().primary_token_id;
-    let result: Result<(String,), (RejectionCode, String)> =
-        ic_cdk::call(primary_token_id, "icrc1_symbol", ()).await;
-
-    result
-        .map(|(symbol,)| symbol)
-        .map_err(|(code, msg)| format!("Failed to get symbol: {} {:?}", msg, code))
-}
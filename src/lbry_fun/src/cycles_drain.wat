@@ -0,0 +1,63 @@
+;; Minimal canister installed on the canisters of a failed launch before they are
+;; deleted. `drain` sends the canister's cycles, minus a small reserve for the call
+;; itself, to the caller's `accept_reclaimed_cycles` endpoint and replies once the
+;; transfer has been answered.
+;;
+;; Rebuild with any WAT assembler, e.g. `wat2wasm cycles_drain.wat -o cycles_drain.wasm`.
+(module
+  (import "ic0" "msg_caller_size" (func $msg_caller_size (result i32)))
+  (import "ic0" "msg_caller_copy" (func $msg_caller_copy (param i32 i32 i32)))
+  (import "ic0" "canister_cycle_balance128" (func $canister_cycle_balance128 (param i32)))
+  (import "ic0" "call_new"
+    (func $call_new (param i32 i32 i32 i32 i32 i32 i32 i32)))
+  (import "ic0" "call_data_append" (func $call_data_append (param i32 i32)))
+  (import "ic0" "call_cycles_add128" (func $call_cycles_add128 (param i64 i64)))
+  (import "ic0" "call_perform" (func $call_perform (result i32)))
+  (import "ic0" "msg_reply_data_append" (func $msg_reply_data_append (param i32 i32)))
+  (import "ic0" "msg_reply" (func $msg_reply))
+
+  (memory 1)
+  (table 1 funcref)
+  (elem (i32.const 0) $on_response)
+
+  ;; 0..23: method name, 32..38: empty candid arguments,
+  ;; 64..: caller principal, 128..144: cycle balance.
+  (data (i32.const 0) "accept_reclaimed_cycles")
+  (data (i32.const 32) "DIDL\00\00")
+
+  ;; Cycles kept back to pay for the outgoing call.
+  (global $reserve i64 (i64.const 5000000000))
+
+  (func $reply_empty
+    (call $msg_reply_data_append (i32.const 32) (i32.const 6))
+    (call $msg_reply))
+
+  (func $on_response (param $env i32)
+    (call $reply_empty))
+
+  (func $drain
+    (local $caller_size i32)
+    (local $balance i64)
+    (local.set $caller_size (call $msg_caller_size))
+    (call $msg_caller_copy (i32.const 64) (i32.const 0) (local.get $caller_size))
+    (call $canister_cycle_balance128 (i32.const 128))
+    (local.set $balance (i64.load (i32.const 128)))
+
+    (if (i64.le_u (local.get $balance) (global.get $reserve))
+      (then
+        (call $reply_empty)
+        (return)))
+
+    (call $call_new
+      (i32.const 64) (local.get $caller_size)
+      (i32.const 0) (i32.const 23)
+      (i32.const 0) (i32.const 0)
+      (i32.const 0) (i32.const 0))
+    (call $call_data_append (i32.const 32) (i32.const 6))
+    (call $call_cycles_add128
+      (i64.const 0)
+      (i64.sub (local.get $balance) (global.get $reserve)))
+    (if (call $call_perform)
+      (then (call $reply_empty))))
+
+  (export "canister_update drain" (func $drain)))
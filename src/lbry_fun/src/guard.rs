@@ -0,0 +1,3 @@
+use crate::ROLES;
+
+access_control::role_guards!(ROLES);
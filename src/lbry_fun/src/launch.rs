@@ -0,0 +1,388 @@
+use candid::Principal;
+use ic_cdk::{
+    api::management_canister::main::{canister_status, CanisterIdRecord},
+    query, update,
+};
+use std::{cell::RefCell, collections::BTreeSet};
+
+use dex_adapter::DexAdapter;
+
+use crate::{
+    create_a_canister, get_config, get_refund, has_role, install_icp_swap_wasm_on_existing_canister,
+    install_icrc1_ledger, install_logs_wasm_on_existing_canister,
+    install_tokenomics_wasm_on_existing_canister, logo_data_url, next_token_id, LaunchParams, LaunchRecord, LaunchStatus,
+    LaunchStep, Role, TokenRecord, BUNDLED_WASM_VERSION, CANISTER_CREATION_CYCLES, INTITAL_PRIMARY_MINT,
+    LAUNCHES, TOKENS,
+};
+
+/// A launch is marked as failed once it has stalled this many times.
+pub const MAX_LAUNCH_ATTEMPTS: u32 = 5;
+/// In-progress launches untouched for this long are picked up by the retry timer.
+pub const LAUNCH_RETRY_AFTER_NANOS: u64 = 5 * 60 * 1_000_000_000;
+
+thread_local! {
+    static LAUNCHES_IN_FLIGHT: RefCell<BTreeSet<u64>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+/// Makes sure a launch is only driven by one call at a time.
+pub(crate) struct LaunchGuard {
+    launch_id: u64,
+}
+
+impl LaunchGuard {
+    pub(crate) fn new(launch_id: u64) -> Result<Self, String> {
+        LAUNCHES_IN_FLIGHT.with(|in_flight| {
+            if !in_flight.borrow_mut().insert(launch_id) {
+                return Err(format!("Launch {} is already being processed", launch_id));
+            }
+            Ok(Self { launch_id })
+        })
+    }
+}
+
+impl Drop for LaunchGuard {
+    fn drop(&mut self) {
+        LAUNCHES_IN_FLIGHT.with(|in_flight| {
+            in_flight.borrow_mut().remove(&self.launch_id);
+        })
+    }
+}
+
+pub fn get_launch(launch_id: u64) -> Option<LaunchRecord> {
+    LAUNCHES.with(|launches| launches.borrow().get(&launch_id))
+}
+
+pub(crate) fn save_launch(record: &LaunchRecord) {
+    LAUNCHES.with(|launches| {
+        launches.borrow_mut().insert(record.id, record.clone());
+    });
+}
+
+/// Persists a new launch once the fee has been collected. Nothing has been created yet.
+pub fn register_launch(caller: Principal, params: LaunchParams) -> u64 {
+    let now = ic_cdk::api::time();
+    LAUNCHES.with(|launches| {
+        let mut launches = launches.borrow_mut();
+        let launch_id = launches.last_key_value().map(|(id, _)| id + 1).unwrap_or(1);
+        launches.insert(
+            launch_id,
+            LaunchRecord {
+                id: launch_id,
+                caller,
+                params,
+                step: LaunchStep::CreateSwapCanister,
+                status: LaunchStatus::InProgress,
+                swap_canister_id: None,
+                tokenomics_canister_id: None,
+                frontend_canister_id: None,
+                logs_canister_id: None,
+                primary_token_id: None,
+                secondary_token_id: None,
+                token_record_id: None,
+                attempts: 0,
+                last_error: None,
+                created_time: now,
+                updated_time: now,
+            },
+        );
+        launch_id
+    })
+}
+
+/// Runs the remaining steps of a launch. Progress is saved after every step, so a
+/// launch that stops halfway can be picked up again from where it left off.
+pub async fn run_launch(launch_id: u64) -> Result<LaunchRecord, String> {
+    let _guard = LaunchGuard::new(launch_id)?;
+    let mut record = get_launch(launch_id).ok_or(format!("Launch {} not found", launch_id))?;
+    if record.status != LaunchStatus::InProgress {
+        return Err(format!("Launch {} is {:?}", launch_id, record.status));
+    }
+
+    loop {
+        if let Err(e) = execute_step(&mut record).await {
+            record.attempts += 1;
+            record.last_error = Some(e.clone());
+            if record.attempts >= MAX_LAUNCH_ATTEMPTS {
+                record.status = LaunchStatus::Failed;
+            }
+            record.updated_time = ic_cdk::api::time();
+            save_launch(&record);
+            return Err(format!(
+                "Launch {} stopped at {:?}: {}",
+                launch_id, record.step, e
+            ));
+        }
+
+        record.last_error = None;
+        record.updated_time = ic_cdk::api::time();
+        match record.step.next() {
+            Some(step) => {
+                record.step = step;
+                save_launch(&record);
+            }
+            None => {
+                record.status = LaunchStatus::Completed;
+                save_launch(&record);
+                return Ok(record);
+            }
+        }
+    }
+}
+
+async fn execute_step(record: &mut LaunchRecord) -> Result<(), String> {
+    let params = record.params.clone();
+    match record.step {
+        LaunchStep::CreateSwapCanister => {
+            if record.swap_canister_id.is_none() {
+                record.swap_canister_id = Some(create_a_canister(CANISTER_CREATION_CYCLES).await?);
+            }
+        }
+        LaunchStep::CreateTokenomicsCanister => {
+            if record.tokenomics_canister_id.is_none() {
+                record.tokenomics_canister_id =
+                    Some(create_a_canister(CANISTER_CREATION_CYCLES).await?);
+            }
+        }
+        LaunchStep::CreateFrontendCanister => {
+            if record.frontend_canister_id.is_none() {
+                record.frontend_canister_id =
+                    Some(create_a_canister(CANISTER_CREATION_CYCLES).await?);
+            }
+        }
+        LaunchStep::CreateLogsCanister => {
+            if record.logs_canister_id.is_none() {
+                record.logs_canister_id = Some(create_a_canister(CANISTER_CREATION_CYCLES).await?);
+            }
+        }
+        LaunchStep::CreatePrimaryLedger => {
+            if record.primary_token_id.is_none() {
+                record.primary_token_id = Some(create_a_canister(CANISTER_CREATION_CYCLES).await?);
+            }
+        }
+        LaunchStep::InstallPrimaryLedger => {
+            let canister_id = required(record.primary_token_id, "primary ledger")?;
+            let tokenomics_canister_id = required(record.tokenomics_canister_id, "tokenomics")?;
+            if !is_code_installed(canister_id).await? {
+                // The primary ledger only starts with `INTITAL_PRIMARY_MINT`, which is
+                // used to seed the DEX pool; everything else is minted by tokenomics.
+                install_icrc1_ledger(
+                    canister_id,
+                    params.primary_token_symbol,
+                    params.primary_token_name,
+                    params.primary_token_description,
+                    tokenomics_canister_id,
+                    tokenomics_canister_id,
+                    INTITAL_PRIMARY_MINT,
+                    logo_data_url(&params.primary_logo)?,
+                )
+                .await?;
+            }
+        }
+        LaunchStep::CreateSecondaryLedger => {
+            if record.secondary_token_id.is_none() {
+                record.secondary_token_id =
+                    Some(create_a_canister(CANISTER_CREATION_CYCLES).await?);
+            }
+        }
+        LaunchStep::InstallSecondaryLedger => {
+            let canister_id = required(record.secondary_token_id, "secondary ledger")?;
+            let swap_canister_id = required(record.swap_canister_id, "swap")?;
+            if !is_code_installed(canister_id).await? {
+                install_icrc1_ledger(
+                    canister_id,
+                    params.secondary_token_symbol,
+                    params.secondary_token_name,
+                    params.secondary_token_description,
+                    swap_canister_id,
+                    swap_canister_id,
+                    0,
+                    logo_data_url(&params.secondary_logo)?,
+                )
+                .await?;
+            }
+        }
+        LaunchStep::InstallTokenomics => {
+            let canister_id = required(record.tokenomics_canister_id, "tokenomics")?;
+            if !is_code_installed(canister_id).await? {
+                install_tokenomics_wasm_on_existing_canister(
+                    canister_id,
+                    record.primary_token_id,
+                    record.secondary_token_id,
+                    record.swap_canister_id,
+                    record.frontend_canister_id,
+                    params.primary_max_supply,
+                    params.initial_primary_mint,
+                    params.initial_secondary_burn,
+                    params.primary_max_phase_mint,
+                    params.halving_step,
+                    params.initial_reward_per_burn_unit,
+                    params.emission_curve(),
+                    params.vesting(),
+                    params.tge_allocations(record.caller),
+                )
+                .await?;
+            }
+        }
+        LaunchStep::MintTgeAllocations => {
+            // Tokenomics skips allocations it already minted, so this can be retried.
+            let canister_id = required(record.tokenomics_canister_id, "tokenomics")?;
+            let (result,): (Result<u64, candid::Reserved>,) =
+                ic_cdk::call(canister_id, "mint_tge_allocations", ())
+                    .await
+                    .map_err(|(code, msg)| {
+                        format!("Failed to mint TGE allocations: {:?} - {}", code, msg)
+                    })?;
+            result.map_err(|_| {
+                "Tokenomics failed to mint the TGE allocations; see its logs".to_string()
+            })?;
+        }
+        LaunchStep::InstallIcpSwap => {
+            let canister_id = required(record.swap_canister_id, "swap")?;
+            if !is_code_installed(canister_id).await? {
+                install_icp_swap_wasm_on_existing_canister(
+                    canister_id,
+                    record.primary_token_id,
+                    record.secondary_token_id,
+                    record.tokenomics_canister_id,
+                    Some(params.dex()),
+                    params.launch_protection(),
+                    params.fee_schedule(),
+                    record.caller,
+                )
+                .await?;
+            }
+        }
+        LaunchStep::InstallLogs => {
+            let canister_id = required(record.logs_canister_id, "logs")?;
+            if !is_code_installed(canister_id).await? {
+                install_logs_wasm_on_existing_canister(
+                    canister_id,
+                    required(record.primary_token_id, "primary ledger")?,
+                    required(record.secondary_token_id, "secondary ledger")?,
+                    required(record.swap_canister_id, "swap")?,
+                    required(record.tokenomics_canister_id, "tokenomics")?,
+                )
+                .await?;
+            }
+        }
+        LaunchStep::AddTokenToDex => {
+            let primary_token_id = required(record.primary_token_id, "primary ledger")?;
+            get_config()
+                .dex(params.dex())
+                .add_token(primary_token_id)
+                .await
+                .map_err(|e| format!("Failed to add token to DEX: {}", e))?;
+        }
+        LaunchStep::RecordToken => {
+            if record.token_record_id.is_none() {
+                record.token_record_id = Some(insert_token_record(record)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn required(canister_id: Option<Principal>, name: &str) -> Result<Principal, String> {
+    canister_id.ok_or(format!("The {} canister has not been created yet", name))
+}
+
+/// Lets install steps be retried: a canister that already has a module is left alone.
+async fn is_code_installed(canister_id: Principal) -> Result<bool, String> {
+    let (status,) = canister_status(CanisterIdRecord { canister_id })
+        .await
+        .map_err(|(_code, msg)| format!("Failed to get canister status: {}", msg))?;
+    Ok(status.module_hash.is_some())
+}
+
+fn insert_token_record(record: &LaunchRecord) -> Result<u64, String> {
+    let params = &record.params;
+    let token_record = TokenRecord {
+        id: 0,
+        primary_token_id: required(record.primary_token_id, "primary ledger")?,
+        primary_token_name: params.primary_token_name.clone(),
+        primary_token_symbol: params.primary_token_symbol.clone(),
+        primary_token_max_supply: params.primary_max_supply,
+        secondary_token_id: required(record.secondary_token_id, "secondary ledger")?,
+        secondary_token_name: params.secondary_token_name.clone(),
+        secondary_token_symbol: params.secondary_token_symbol.clone(),
+        tokenomics_canister_id: required(record.tokenomics_canister_id, "tokenomics")?,
+        icp_swap_canister_id: required(record.swap_canister_id, "swap")?,
+        logs_canister_id: required(record.logs_canister_id, "logs")?,
+        initial_primary_mint: params.initial_primary_mint,
+        initial_secondary_burn: params.initial_secondary_burn,
+        primary_max_phase_mint: params.primary_max_phase_mint,
+        halving_step: params.halving_step,
+        caller: record.caller,
+        created_time: ic_cdk::api::time(),
+        liquidity_provided_at: 0,
+        is_live: false,
+        dex: Some(params.dex()),
+        tge_allocations: Some(params.tge_allocations(record.caller)),
+        launch_protection: params.launch_protection(),
+        launch_protection_pending: None,
+        canister_version: Some(BUNDLED_WASM_VERSION),
+    };
+
+    let token_id = next_token_id();
+    TOKENS.with(|tokens| {
+        tokens
+            .borrow_mut()
+            .insert(token_id, TokenRecord { id: token_id, ..token_record });
+    });
+    Ok(token_id)
+}
+
+/// Picks up launches that stalled, e.g. because a call failed or the canister was upgraded mid-launch.
+pub async fn retry_stalled_launches() {
+    let now = ic_cdk::api::time();
+    let stalled: Vec<u64> = LAUNCHES.with(|launches| {
+        launches
+            .borrow()
+            .iter()
+            .filter(|(_, launch)| {
+                launch.status == LaunchStatus::InProgress
+                    && launch.updated_time + LAUNCH_RETRY_AFTER_NANOS <= now
+            })
+            .map(|(id, _)| id)
+            .collect()
+    });
+
+    for launch_id in stalled {
+        match run_launch(launch_id).await {
+            Ok(_) => ic_cdk::println!("Launch {} completed on retry", launch_id),
+            Err(e) => ic_cdk::println!("Retry failed: {}", e),
+        }
+    }
+}
+
+#[update]
+async fn resume_launch(launch_id: u64) -> Result<String, String> {
+    let caller = ic_cdk::api::caller();
+    let mut record = get_launch(launch_id).ok_or(format!("Launch {} not found", launch_id))?;
+    if caller != record.caller && !has_role(caller, Role::Operator) {
+        return Err("Only the launch creator or an operator can resume a launch".to_string());
+    }
+
+    match record.status {
+        LaunchStatus::Completed => return Err(format!("Launch {} is already completed", launch_id)),
+        LaunchStatus::Refunded => return Err(format!("Launch {} has been refunded", launch_id)),
+        LaunchStatus::Failed => {
+            // Give a failed launch a fresh set of attempts, unless its rollback has started.
+            if get_refund(launch_id).is_some() {
+                return Err(format!("Launch {} is being refunded", launch_id));
+            }
+            record.status = LaunchStatus::InProgress;
+            record.attempts = 0;
+            save_launch(&record);
+        }
+        LaunchStatus::InProgress => (),
+    }
+
+    run_launch(launch_id).await?;
+    Ok("Tokens created and stored!".to_string())
+}
+
+#[query]
+fn get_launch_status(launch_id: u64) -> Option<LaunchRecord> {
+    get_launch(launch_id)
+}
@@ -1,13 +1,25 @@
 mod storage;
 pub use storage::*;
-mod simulation;
-pub use simulation::*;
+mod guard;
+pub use guard::*;
+mod launch;
+pub use launch::*;
+mod metadata;
+pub use metadata::*;
+mod refund;
+pub use refund::*;
+mod roles;
+pub use roles::*;
 mod queries;
 pub use queries::*;
 mod update;
 pub use update::*;
+mod upgrade;
+pub use upgrade::*;
 mod utlis;
 pub use utlis::*;
+mod validation;
+pub use validation::*;
 use icrc_ledger_types::icrc1::transfer::BlockIndex;
 use icrc_ledger_types::icrc2::transfer_from::TransferFromError;
 use candid::{CandidType, Deserialize, Nat, Principal};
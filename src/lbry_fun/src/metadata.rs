@@ -0,0 +1,326 @@
+use candid::{CandidType, Encode, Principal};
+use ic_cdk::{
+    api::management_canister::main::{install_code, CanisterInstallMode, InstallCodeArgument},
+    update,
+};
+use icrc_ledger_types::icrc::generic_metadata_value::MetadataValue as LedgerMetadataValue;
+use serde::Deserialize;
+use std::{cell::RefCell, collections::BTreeSet};
+
+use crate::{
+    LaunchParams, LaunchStatus, LedgerArg, MetadataValue, TokenRecord, UpgradeArgs, LAUNCHES,
+    TOKENS,
+};
+
+pub const MIN_SYMBOL_LEN: usize = 3;
+pub const MAX_SYMBOL_LEN: usize = 8;
+pub const MAX_NAME_LEN: usize = 32;
+pub const MAX_DESCRIPTION_LEN: usize = 1_024;
+/// Largest logo accepted, after base64 decoding.
+pub const MAX_LOGO_BYTES: usize = 256 * 1024;
+pub const ALLOWED_LOGO_MIME_TYPES: [&str; 4] =
+    ["image/svg+xml", "image/png", "image/jpeg", "image/webp"];
+// Logos sent as bare base64 have always been installed as SVG.
+const DEFAULT_LOGO_MIME_TYPE: &str = "image/svg+xml";
+
+const DESCRIPTION_METADATA_KEY: &str = "description";
+const LOGO_METADATA_KEY: &str = "logo";
+
+thread_local! {
+    static METADATA_UPDATES_IN_FLIGHT: RefCell<BTreeSet<u64>> = RefCell::new(BTreeSet::new());
+}
+
+/// Which of a launched token pair's ledgers to update.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenLedger {
+    Primary,
+    Secondary,
+}
+
+/// New ledger metadata. Unset fields keep their current value.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct TokenMetadataUpdate {
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub description: Option<String>,
+    pub logo: Option<String>,
+}
+
+pub fn validate_token_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() || name.trim() != name {
+        return Err(format!("Token name '{}' must not be empty or padded with spaces", name));
+    }
+    if name.chars().count() > MAX_NAME_LEN {
+        return Err(format!("Token name must be at most {} characters", MAX_NAME_LEN));
+    }
+    if name.chars().any(char::is_control) {
+        return Err("Token name must not contain control characters".to_string());
+    }
+    Ok(())
+}
+
+pub fn validate_token_symbol(symbol: &str) -> Result<(), String> {
+    if !(MIN_SYMBOL_LEN..=MAX_SYMBOL_LEN).contains(&symbol.len()) {
+        return Err(format!(
+            "Token symbol '{}' must be {} to {} characters",
+            symbol, MIN_SYMBOL_LEN, MAX_SYMBOL_LEN
+        ));
+    }
+    if !symbol.starts_with(|c: char| c.is_ascii_uppercase())
+        || !symbol.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+    {
+        return Err(format!(
+            "Token symbol '{}' must be uppercase letters and digits, starting with a letter",
+            symbol
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_token_description(description: &str) -> Result<(), String> {
+    if description.chars().count() > MAX_DESCRIPTION_LEN {
+        return Err(format!(
+            "Token description must be at most {} characters",
+            MAX_DESCRIPTION_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Checks a logo given either as a `data:<mime>;base64,` URL or as bare base64, and
+/// returns it as the data URL the ledger stores.
+pub fn logo_data_url(logo: &str) -> Result<String, String> {
+    let (mime_type, payload) = match logo.strip_prefix("data:") {
+        Some(data_url) => data_url
+            .split_once(";base64,")
+            .ok_or("Token logo must be a base64 data URL".to_string())?,
+        None => (DEFAULT_LOGO_MIME_TYPE, logo),
+    };
+    if !ALLOWED_LOGO_MIME_TYPES.contains(&mime_type) {
+        return Err(format!(
+            "Token logo type '{}' is not one of {}",
+            mime_type,
+            ALLOWED_LOGO_MIME_TYPES.join(", ")
+        ));
+    }
+    if payload.is_empty()
+        || payload.len() % 4 != 0
+        || !payload
+            .trim_end_matches('=')
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/')
+    {
+        return Err("Token logo is not valid base64".to_string());
+    }
+    if payload.len() / 4 * 3 > MAX_LOGO_BYTES {
+        return Err(format!("Token logo must be at most {} KiB", MAX_LOGO_BYTES / 1024));
+    }
+    Ok(format!("data:{};base64,{}", mime_type, payload))
+}
+
+/// Fails if a launched token, or a launch that may still complete, already uses `symbol`.
+/// `except_token_id` is skipped so a token can keep its own symbol.
+pub fn ensure_symbol_available(symbol: &str, except_token_id: Option<u64>) -> Result<(), String> {
+    let taken_by_token = TOKENS.with(|tokens| {
+        tokens.borrow().iter().any(|(id, token)| {
+            Some(id) != except_token_id
+                && (token.primary_token_symbol.eq_ignore_ascii_case(symbol)
+                    || token.secondary_token_symbol.eq_ignore_ascii_case(symbol))
+        })
+    });
+    let taken_by_launch = LAUNCHES.with(|launches| {
+        launches.borrow().iter().any(|(_, launch)| {
+            matches!(launch.status, LaunchStatus::InProgress | LaunchStatus::Failed)
+                && (launch.params.primary_token_symbol.eq_ignore_ascii_case(symbol)
+                    || launch.params.secondary_token_symbol.eq_ignore_ascii_case(symbol))
+        })
+    });
+    if taken_by_token || taken_by_launch {
+        return Err(format!("Token symbol '{}' is already in use", symbol));
+    }
+    Ok(())
+}
+
+/// The metadata checks `create_token` runs before taking payment.
+pub fn validate_launch_metadata(params: &LaunchParams) -> Result<(), String> {
+    let tokens = [
+        (
+            &params.primary_token_name,
+            &params.primary_token_symbol,
+            &params.primary_token_description,
+            &params.primary_logo,
+        ),
+        (
+            &params.secondary_token_name,
+            &params.secondary_token_symbol,
+            &params.secondary_token_description,
+            &params.secondary_logo,
+        ),
+    ];
+    for (name, symbol, description, logo) in tokens {
+        validate_token_name(name)?;
+        validate_token_symbol(symbol)?;
+        validate_token_description(description)?;
+        logo_data_url(logo)?;
+        ensure_symbol_available(symbol, None)?;
+    }
+    if params
+        .primary_token_symbol
+        .eq_ignore_ascii_case(&params.secondary_token_symbol)
+    {
+        return Err("Primary and secondary tokens must have different symbols".to_string());
+    }
+    Ok(())
+}
+
+/// Upgrades one of a launched token's ledgers with new metadata. Creator only.
+#[update]
+async fn update_token_metadata(
+    token_id: u64,
+    ledger: TokenLedger,
+    update: TokenMetadataUpdate,
+) -> Result<TokenRecord, String> {
+    let caller = ic_cdk::api::caller();
+    let token = TOKENS
+        .with(|tokens| tokens.borrow().get(&token_id))
+        .ok_or(format!("Token {} not found", token_id))?;
+    if caller != token.caller {
+        return Err("Only the token creator can update its metadata".to_string());
+    }
+
+    if let Some(name) = &update.name {
+        validate_token_name(name)?;
+    }
+    if let Some(symbol) = &update.symbol {
+        validate_token_symbol(symbol)?;
+        ensure_symbol_available(symbol, Some(token_id))?;
+        let other_symbol = match ledger {
+            TokenLedger::Primary => &token.secondary_token_symbol,
+            TokenLedger::Secondary => &token.primary_token_symbol,
+        };
+        if symbol.eq_ignore_ascii_case(other_symbol) {
+            return Err("Primary and secondary tokens must have different symbols".to_string());
+        }
+    }
+    if let Some(description) = &update.description {
+        validate_token_description(description)?;
+    }
+    let logo = update.logo.as_deref().map(logo_data_url).transpose()?;
+
+    let _guard = MetadataUpdateGuard::new(token_id)?;
+    let ledger_id = match ledger {
+        TokenLedger::Primary => token.primary_token_id,
+        TokenLedger::Secondary => token.secondary_token_id,
+    };
+
+    // The upgrade replaces the whole custom metadata list, so unchanged entries are
+    // carried over from the ledger.
+    let (current,): (Vec<(String, LedgerMetadataValue)>,) =
+        ic_cdk::call(ledger_id, "icrc1_metadata", ())
+            .await
+            .map_err(|(code, msg)| {
+                format!("Failed to read ledger metadata: {:?} - {}", code, msg)
+            })?;
+    let current_text = |key: &str| {
+        current.iter().find_map(|(k, value)| match value {
+            LedgerMetadataValue::Text(text) if k == key => Some(text.clone()),
+            _ => None,
+        })
+    };
+    let description = update
+        .description
+        .clone()
+        .or_else(|| current_text(DESCRIPTION_METADATA_KEY))
+        .unwrap_or_default();
+    let logo = logo
+        .or_else(|| current_text(LOGO_METADATA_KEY))
+        .unwrap_or_default();
+
+    upgrade_icrc1_ledger(
+        ledger_id,
+        UpgradeArgs {
+            metadata: Some(vec![
+                (DESCRIPTION_METADATA_KEY.to_string(), MetadataValue::Text(description)),
+                (LOGO_METADATA_KEY.to_string(), MetadataValue::Text(logo)),
+            ]),
+            token_symbol: update.symbol.clone(),
+            token_name: update.name.clone(),
+            transfer_fee: None,
+            max_memo_length: None,
+            feature_flags: None,
+            maximum_number_of_accounts: None,
+            accounts_overflow_trim_quantity: None,
+        },
+    )
+    .await?;
+
+    TOKENS.with(|tokens| {
+        let mut tokens = tokens.borrow_mut();
+        let mut token = tokens
+            .get(&token_id)
+            .ok_or(format!("Token {} not found", token_id))?;
+        match ledger {
+            TokenLedger::Primary => {
+                if let Some(name) = update.name {
+                    token.primary_token_name = name;
+                }
+                if let Some(symbol) = update.symbol {
+                    token.primary_token_symbol = symbol;
+                }
+            }
+            TokenLedger::Secondary => {
+                if let Some(name) = update.name {
+                    token.secondary_token_name = name;
+                }
+                if let Some(symbol) = update.symbol {
+                    token.secondary_token_symbol = symbol;
+                }
+            }
+        }
+        tokens.insert(token_id, token.clone());
+        Ok(token)
+    })
+}
+
+/// Reinstalls the ledger wasm this canister launched with, keeping its state.
+pub(crate) async fn upgrade_icrc1_ledger(
+    canister_id: Principal,
+    args: UpgradeArgs,
+) -> Result<(), String> {
+    let wasm_bytes = include_bytes!("ic-icrc1-ledger.wasm");
+    let encoded_args = Encode!(&LedgerArg::Upgrade(Some(args)))
+        .map_err(|e| format!("Failed to encode upgrade args: {:?}", e))?;
+
+    install_code(InstallCodeArgument {
+        mode: CanisterInstallMode::Upgrade(None),
+        canister_id,
+        wasm_module: wasm_bytes.to_vec(),
+        arg: encoded_args,
+    })
+    .await
+    .map_err(|e| format!("Failed to upgrade ICRC-1 token: {:?}", e))
+}
+
+/// Keeps two metadata updates for the same token from interleaving.
+struct MetadataUpdateGuard {
+    token_id: u64,
+}
+
+impl MetadataUpdateGuard {
+    fn new(token_id: u64) -> Result<Self, String> {
+        METADATA_UPDATES_IN_FLIGHT.with(|in_flight| {
+            if !in_flight.borrow_mut().insert(token_id) {
+                return Err(format!("Token {} metadata is already being updated", token_id));
+            }
+            Ok(Self { token_id })
+        })
+    }
+}
+
+impl Drop for MetadataUpdateGuard {
+    fn drop(&mut self) {
+        METADATA_UPDATES_IN_FLIGHT.with(|in_flight| {
+            in_flight.borrow_mut().remove(&self.token_id);
+        })
+    }
+}
@@ -1,6 +1,12 @@
+use candid::{CandidType, Principal};
 use ic_cdk::query;
-use crate::{TokenRecord, TOKENS, get_self_icp_balance};
-use crate::simulation::{preview_tokenomics, GraphData, PreviewArgs};
+use serde::Deserialize;
+use crate::{
+    get_config, validate_launch_tokenomics, Configs, LaunchTokenomics, LaunchValidation,
+    TokenRecord, TOKENS, get_self_icp_balance,
+};
+use tokenomics_core::preview_tokenomics;
+pub use tokenomics_core::{GraphData, PreviewArgs};
 
 #[query]
 pub fn get_all_token_record() -> Vec<(u64, TokenRecord)> {
@@ -41,6 +47,11 @@ pub fn get_live() -> Vec<(u64, TokenRecord)> {
     })
 }
 
+#[query]
+pub fn get_external_canisters() -> Configs {
+    get_config()
+}
+
 #[query]
 async fn get_treasury_balance() -> Result<u64, String> {
     let canister_principal = ic_cdk::api::id();
@@ -52,3 +63,58 @@ fn preview_tokenomics_graphs(args: PreviewArgs) -> GraphData {
     preview_tokenomics(args)
 }
 
+/// The checks `create_token` runs before taking payment, with warnings it lets through.
+#[query]
+fn validate_launch_params(params: LaunchTokenomics) -> LaunchValidation {
+    validate_launch_tokenomics(&params)
+}
+
+
+/// The creator share one token's icp_swap credited, in e8s.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct CreatorRevenue {
+    pub accrued: u64,
+    pub unclaimed: u64,
+    pub claimed: u64,
+}
+
+/// A creator's revenue across the tokens they launched, in e8s.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct CreatorEarnings {
+    pub accrued: u64,
+    pub unclaimed: u64,
+    pub claimed: u64,
+    pub tokens: Vec<(u64, CreatorRevenue)>,
+    // Tokens whose icp_swap could not be read; left out of the totals.
+    pub unavailable: Vec<u64>,
+}
+
+/// Totals the creator revenue of every token `creator` launched. Each token's share is
+/// claimed on its own icp_swap with `claim_creator_revenue`.
+#[query(composite = true)]
+async fn get_creator_earnings(creator: Principal) -> CreatorEarnings {
+    let tokens: Vec<TokenRecord> = TOKENS.with(|tokens| {
+        tokens
+            .borrow()
+            .iter()
+            .filter(|(_, token)| token.caller == creator)
+            .map(|(_, token)| token)
+            .collect()
+    });
+
+    let mut earnings = CreatorEarnings::default();
+    for token in tokens {
+        let result: ic_cdk::api::call::CallResult<(CreatorRevenue,)> =
+            ic_cdk::call(token.icp_swap_canister_id, "get_creator_revenue", ()).await;
+        match result {
+            Ok((revenue,)) => {
+                earnings.accrued = earnings.accrued.saturating_add(revenue.accrued);
+                earnings.unclaimed = earnings.unclaimed.saturating_add(revenue.unclaimed);
+                earnings.claimed = earnings.claimed.saturating_add(revenue.claimed);
+                earnings.tokens.push((token.id, revenue));
+            }
+            Err(_) => earnings.unavailable.push(token.id),
+        }
+    }
+    earnings
+}
@@ -0,0 +1,274 @@
+use candid::{Nat, Principal};
+use ic_cdk::{
+    api::management_canister::main::{
+        delete_canister, install_code, stop_canister, update_settings, CanisterIdRecord,
+        CanisterInstallMode, CanisterSettings, InstallCodeArgument, UpdateSettingsArgument,
+    },
+    query, update,
+};
+use icrc_ledger_types::icrc1::{
+    account::Account,
+    transfer::{TransferArg, TransferError},
+};
+use num_traits::ToPrimitive;
+
+use crate::{
+    get_config, get_launch, save_launch, IcpXdrConversionRateResponse, LaunchGuard, LaunchRecord,
+    LaunchStatus, RefundRecord, RefundStatus, CANISTER_CREATION_CYCLES, ICP_TRANSFER_FEE,
+    LAUNCHES, LAUNCH_FEE, RECLAIMED_CYCLES, REFUNDS,
+};
+
+/// Receives the leftover cycles of a canister that is being deleted. Cycles from any
+/// canister a refund is not draining right now are left unaccepted.
+#[update]
+fn accept_reclaimed_cycles() {
+    let caller = ic_cdk::api::caller();
+    RECLAIMED_CYCLES.with(|reclaimed| {
+        let mut reclaimed = reclaimed.borrow_mut();
+        let Some(received) = reclaimed.get(&caller) else {
+            return;
+        };
+        let available = ic_cdk::api::call::msg_cycles_available128();
+        let accepted = ic_cdk::api::call::msg_cycles_accept128(available);
+        reclaimed.insert(caller, received.saturating_add(accepted));
+    });
+}
+
+/// Rolls back every failed launch that has not been refunded yet.
+pub async fn refund_failed_launches() {
+    let failed: Vec<u64> = LAUNCHES.with(|launches| {
+        launches
+            .borrow()
+            .iter()
+            .filter(|(_, launch)| launch.status == LaunchStatus::Failed)
+            .map(|(id, _)| id)
+            .collect()
+    });
+
+    for launch_id in failed {
+        match refund_launch(launch_id).await {
+            Ok(refund) => ic_cdk::println!(
+                "Launch {} refunded {} e8s to {}",
+                launch_id,
+                refund.refunded_amount,
+                refund.caller
+            ),
+            Err(e) => ic_cdk::println!("Refund of launch {} failed: {}", launch_id, e),
+        }
+    }
+}
+
+/// Deletes the canisters a failed launch created, pulls their cycles back and returns
+/// the launch fee minus what was actually spent. Progress is saved after every
+/// canister, so an interrupted refund picks up where it stopped.
+pub async fn refund_launch(launch_id: u64) -> Result<RefundRecord, String> {
+    let _guard = LaunchGuard::new(launch_id)?;
+    let mut launch = get_launch(launch_id).ok_or(format!("Launch {} not found", launch_id))?;
+    if launch.status != LaunchStatus::Failed {
+        return Err(format!("Launch {} is {:?}", launch_id, launch.status));
+    }
+
+    let mut refund = get_refund(launch_id).unwrap_or(RefundRecord {
+        launch_id,
+        caller: launch.caller,
+        status: RefundStatus::Pending,
+        fee_paid: LAUNCH_FEE,
+        deleted_canisters: vec![],
+        cycles_reclaimed: 0,
+        cycles_spent: 0,
+        fees_spent: 0,
+        refunded_amount: 0,
+        block_index: None,
+        created_time: ic_cdk::api::time(),
+        refunded_time: None,
+        transfer_time: None,
+    });
+
+    while let Some(canister_id) = take_next_canister(&mut launch) {
+        reclaim_and_delete(canister_id, &mut refund).await?;
+        refund.deleted_canisters.push(canister_id);
+        save_launch(&launch);
+        save_refund(&refund);
+    }
+
+    // The amount is fixed before the first transfer attempt so retries stay duplicates.
+    if refund.fees_spent == 0 {
+        refund.cycles_spent = (CANISTER_CREATION_CYCLES * refund.deleted_canisters.len() as u128)
+            .saturating_sub(refund.cycles_reclaimed);
+        refund.fees_spent = cycles_to_icp_e8s(refund.cycles_spent).await? + ICP_TRANSFER_FEE;
+        refund.refunded_amount = refund.fee_paid.saturating_sub(refund.fees_spent);
+        save_refund(&refund);
+    }
+
+    if refund.refunded_amount > 0 {
+        refund.block_index = Some(transfer_refund(&mut refund).await?);
+    }
+    refund.status = RefundStatus::Refunded;
+    refund.refunded_time = Some(ic_cdk::api::time());
+    save_refund(&refund);
+
+    launch.status = LaunchStatus::Refunded;
+    launch.updated_time = ic_cdk::api::time();
+    save_launch(&launch);
+
+    Ok(refund)
+}
+
+/// Clears the next canister still owned by the launch so it is only deleted once.
+fn take_next_canister(launch: &mut LaunchRecord) -> Option<Principal> {
+    launch
+        .secondary_token_id
+        .take()
+        .or_else(|| launch.primary_token_id.take())
+        .or_else(|| launch.logs_canister_id.take())
+        .or_else(|| launch.frontend_canister_id.take())
+        .or_else(|| launch.tokenomics_canister_id.take())
+        .or_else(|| launch.swap_canister_id.take())
+}
+
+/// Drains the canister into `refund.cycles_reclaimed`, which is saved before the
+/// canister is stopped and deleted so a retry does not lose what was drained.
+async fn reclaim_and_delete(canister_id: Principal, refund: &mut RefundRecord) -> Result<(), String> {
+    // Without a freezing threshold the drain canister can send out all but its reserve.
+    update_settings(UpdateSettingsArgument {
+        canister_id,
+        settings: CanisterSettings {
+            freezing_threshold: Some(Nat::from(0u64)),
+            ..Default::default()
+        },
+    })
+    .await
+    .map_err(|e| format!("Failed to update settings of {}: {:?}", canister_id, e))?;
+
+    install_code(InstallCodeArgument {
+        mode: CanisterInstallMode::Reinstall,
+        canister_id,
+        wasm_module: include_bytes!("cycles_drain.wasm").to_vec(),
+        arg: vec![],
+    })
+    .await
+    .map_err(|e| format!("Failed to install drain wasm on {}: {:?}", canister_id, e))?;
+
+    RECLAIMED_CYCLES.with(|reclaimed| reclaimed.borrow_mut().insert(canister_id, 0));
+    // A failed drain only loses the cycles; the canister is deleted either way.
+    if let Err(e) = ic_cdk::call::<(), ()>(canister_id, "drain", ()).await {
+        ic_cdk::println!("Failed to drain cycles of {}: {:?}", canister_id, e);
+    }
+    let reclaimed =
+        RECLAIMED_CYCLES.with(|reclaimed| reclaimed.borrow_mut().remove(&canister_id).unwrap_or(0));
+    refund.cycles_reclaimed += reclaimed;
+    save_refund(refund);
+
+    stop_canister(CanisterIdRecord { canister_id })
+        .await
+        .map_err(|e| format!("Failed to stop {}: {:?}", canister_id, e))?;
+    delete_canister(CanisterIdRecord { canister_id })
+        .await
+        .map_err(|e| format!("Failed to delete {}: {:?}", canister_id, e))?;
+
+    Ok(())
+}
+
+/// Values cycles at the cycles minting canister's current ICP/XDR rate
+/// (1 XDR buys one trillion cycles).
+async fn cycles_to_icp_e8s(cycles: u128) -> Result<u64, String> {
+    if cycles == 0 {
+        return Ok(0);
+    }
+    let (rate,): (IcpXdrConversionRateResponse,) = ic_cdk::call(
+        get_config().cycles_minting_canister_id(),
+        "get_icp_xdr_conversion_rate",
+        (),
+    )
+    .await
+    .map_err(|e| format!("Failed to fetch ICP/XDR rate: {:?}", e))?;
+
+    let xdr_permyriad_per_icp = rate.data.xdr_permyriad_per_icp as u128;
+    if xdr_permyriad_per_icp == 0 {
+        return Err("ICP/XDR rate is zero".to_string());
+    }
+    // e8s = cycles * 10_000 * 1e8 / (xdr_permyriad_per_icp * 1e12)
+    (cycles / xdr_permyriad_per_icp)
+        .to_u64()
+        .ok_or("Spent cycles exceed u64 max".to_string())
+}
+
+async fn transfer_refund(refund: &mut RefundRecord) -> Result<u64, String> {
+    let mut renewed = false;
+    loop {
+        let transfer_time = match refund.transfer_time {
+            Some(transfer_time) => transfer_time,
+            None => {
+                let now = ic_cdk::api::time();
+                refund.transfer_time = Some(now);
+                save_refund(refund);
+                now
+            }
+        };
+        let args = TransferArg {
+            from_subaccount: None,
+            to: Account {
+                owner: refund.caller,
+                subaccount: None,
+            },
+            fee: Some(Nat::from(ICP_TRANSFER_FEE)),
+            // A fixed memo and creation time make a retried transfer a ledger duplicate.
+            created_at_time: Some(transfer_time),
+            memo: Some(refund.launch_id.to_be_bytes().to_vec().into()),
+            amount: Nat::from(refund.refunded_amount),
+        };
+
+        let (result,): (Result<Nat, TransferError>,) =
+            ic_cdk::call(get_config().icp_ledger_id(), "icrc1_transfer", (args,))
+                .await
+                .map_err(|e| format!("Failed to call icrc1_transfer: {:?}", e))?;
+
+        let block_index = match result {
+            Ok(block_index) => block_index,
+            Err(TransferError::Duplicate { duplicate_of }) => duplicate_of,
+            // Past the ledger's dedup window; the next attempt gets a fresh time.
+            Err(TransferError::TooOld) if !renewed => {
+                refund.transfer_time = None;
+                renewed = true;
+                continue;
+            }
+            Err(e) => return Err(format!("Refund transfer failed: {:?}", e)),
+        };
+        return block_index
+            .0
+            .to_u64()
+            .ok_or("Block index exceeds u64 max".to_string());
+    }
+}
+
+fn save_refund(refund: &RefundRecord) {
+    REFUNDS.with(|refunds| {
+        refunds.borrow_mut().insert(refund.launch_id, refund.clone());
+    });
+}
+
+pub fn get_refund(launch_id: u64) -> Option<RefundRecord> {
+    REFUNDS.with(|refunds| refunds.borrow().get(&launch_id))
+}
+
+#[query]
+fn get_refund_status(launch_id: u64) -> Option<RefundRecord> {
+    get_refund(launch_id)
+}
+
+#[query]
+fn get_refunds() -> Vec<RefundRecord> {
+    REFUNDS.with(|refunds| refunds.borrow().iter().map(|(_, refund)| refund).collect())
+}
+
+#[query]
+fn get_refunds_by_caller(caller: Principal) -> Vec<RefundRecord> {
+    REFUNDS.with(|refunds| {
+        refunds
+            .borrow()
+            .iter()
+            .filter(|(_, refund)| refund.caller == caller)
+            .map(|(_, refund)| refund)
+            .collect()
+    })
+}
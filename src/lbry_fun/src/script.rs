@@ -1,7 +1,7 @@
 use ic_cdk::{self, init, post_upgrade};
 use std::time::Duration;
 
-use crate::publish_eligible_tokens_on_kongswap;
+use crate::publish_eligible_tokens_on_dex;
 
 pub const INTERVAL: Duration = Duration::from_secs(60); // 1 hour.
 
@@ -13,11 +13,11 @@ fn post_upgrade() {
 fn setup_timers() {
     // Periodic
     let _timer_id: ic_cdk_timers::TimerId =
-        ic_cdk_timers::set_timer_interval(INTERVAL, || ic_cdk::spawn(publish_eligible_tokens_on_kongswap_wrapper()));
+        ic_cdk_timers::set_timer_interval(INTERVAL, || ic_cdk::spawn(publish_eligible_tokens_on_dex_wrapper()));
 }
 
-async fn publish_eligible_tokens_on_kongswap_wrapper() {
-    match publish_eligible_tokens_on_kongswap().await {
+async fn publish_eligible_tokens_on_dex_wrapper() {
+    match publish_eligible_tokens_on_dex().await {
         Ok(_) => ic_cdk::println!("Published eligible tokens completed!"),
         Err(e) => {
             ic_cdk::println!("Error launching on Kongswap: {}", e);
@@ -2,13 +2,22 @@ use candid::{ CandidType, Decode, Encode, Principal};
 use ic_stable_structures::{
     memory_manager::{MemoryId, MemoryManager, VirtualMemory},
     storable::Bound,
-    DefaultMemoryImpl, StableBTreeMap, Storable,
+    DefaultMemoryImpl, StableBTreeMap, StableCell, Storable,
 };
+use access_control::RoleStore;
+use dex_adapter::{Dex, DexKind};
+use tokenomics_core::{
+    unallocated_tge, AllocationBucket, EmissionCurve, FeeSchedule, LaunchProtection,
+    TgeAllocation, VestingArgs,
+};
+
+use crate::{get_principal, BUNDLED_WASM_VERSION, CYCLES_MINTING_CANISTER_ID, ICP_CANISTER_ID, LBRY_SWAP_CANISTER_ID};
 use serde::Deserialize;
 use std::{borrow::Cow, cell::RefCell};
 
 type Memory = VirtualMemory<DefaultMemoryImpl>;
 pub const TOKEN_RECORD_MEM_ID: MemoryId = MemoryId::new(0);
+pub const LAST_TOKEN_ID_MEM_ID: MemoryId = MemoryId::new(10);
 
 thread_local! {
     // Initialize memory manager
@@ -21,6 +30,28 @@ thread_local! {
             MEMORY_MANAGER.with(|m| m.borrow().get(TOKEN_RECORD_MEM_ID)) // Bind to VirtualMemory
         )
     );
+
+    // Highest token id handed out, so an id is never given out twice.
+    pub static LAST_TOKEN_ID: RefCell<StableCell<u64, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(LAST_TOKEN_ID_MEM_ID)),
+            0
+        ).unwrap()
+    );
+}
+
+/// Hands out the next token id. Tokens registered before the counter was kept are
+/// accounted for through their keys.
+pub fn next_token_id() -> u64 {
+    let last_registered = TOKENS.with(|tokens| {
+        tokens.borrow().last_key_value().map(|(id, _)| id).unwrap_or(0)
+    });
+    LAST_TOKEN_ID.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        let token_id = (*cell.get()).max(last_registered) + 1;
+        cell.set(token_id).expect("Failed to store the last token id");
+        token_id
+    })
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
@@ -45,6 +76,35 @@ pub struct TokenRecord {
     pub created_time: u64,
     pub liquidity_provided_at: u64,
     pub is_live: bool,
+    // DEX the token is listed on; KongSwap when unset.
+    pub dex: Option<DexKind>,
+    // Who got the TGE allocation. Unset for tokens launched before allocations existed.
+    pub tge_allocations: Option<Vec<TgeAllocation>>,
+    pub launch_protection: Option<LaunchProtection>,
+    // Whether the swap canister still has to be told when liquidity was provided.
+    pub launch_protection_pending: Option<bool>,
+    // Release of the swap, tokenomics and logs wasms the token runs; see `canister_version`.
+    pub canister_version: Option<u64>,
+}
+
+impl TokenRecord {
+    /// Unset for tokens never upgraded since launch, which run `BUNDLED_WASM_VERSION`.
+    pub fn canister_version(&self) -> u64 {
+        self.canister_version.unwrap_or(BUNDLED_WASM_VERSION)
+    }
+
+    /// Unset for tokens that went live before the call to the swap canister was retried.
+    pub fn launch_protection_pending(&self) -> bool {
+        self.launch_protection_pending.unwrap_or(false)
+    }
+
+    pub fn canister_id(&self, canister: TokenCanister) -> Principal {
+        match canister {
+            TokenCanister::Tokenomics => self.tokenomics_canister_id,
+            TokenCanister::IcpSwap => self.icp_swap_canister_id,
+            TokenCanister::Logs => self.logs_canister_id,
+        }
+    }
 }
 
 impl Storable for TokenRecord {
@@ -56,3 +116,473 @@ impl Storable for TokenRecord {
     }
     const BOUND: Bound = Bound::Unbounded;
 }
+
+pub const LAUNCHES_MEM_ID: MemoryId = MemoryId::new(1);
+
+thread_local! {
+    pub static LAUNCHES: RefCell<StableBTreeMap<u64, LaunchRecord, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(LAUNCHES_MEM_ID))
+        )
+    );
+}
+
+/// Everything the caller passed to `create_token`, kept so a launch can be resumed later.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct LaunchParams {
+    pub primary_token_name: String,
+    pub primary_token_symbol: String,
+    pub primary_token_description: String,
+    pub primary_logo: String,
+    pub secondary_token_name: String,
+    pub secondary_token_symbol: String,
+    pub secondary_token_description: String,
+    pub secondary_logo: String,
+    pub primary_max_supply: u64,
+    pub primary_max_phase_mint: u64,
+    pub initial_primary_mint: u64,
+    pub initial_secondary_burn: u64,
+    pub halving_step: u64,
+    pub initial_reward_per_burn_unit: u64,
+    pub options: Option<LaunchOptions>,
+}
+
+/// Optional settings for `create_token`. Every field falls back to a default when unset.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct LaunchOptions {
+    pub dex: Option<DexKind>,
+    // Halving by `halving_step` when unset.
+    pub emission_curve: Option<EmissionCurve>,
+    // Parts of the TGE allocation tokenomics holds back and releases over time.
+    pub vesting: Option<Vec<VestingArgs>>,
+    // How the TGE allocation is split. Whatever is left goes to the creator. `Airdrop`
+    // allocations are held by tokenomics until their recipient opens the airdrop.
+    pub allocations: Option<Vec<TgeAllocation>>,
+    // Per-principal swap and burn caps while the token launches.
+    pub launch_protection: Option<LaunchProtection>,
+    // How the swap canister splits its rewards; the protocol defaults when unset.
+    pub fee_schedule: Option<FeeSchedule>,
+}
+
+impl LaunchParams {
+    pub fn dex(&self) -> DexKind {
+        self.options
+            .as_ref()
+            .and_then(|options| options.dex)
+            .unwrap_or_default()
+    }
+
+    pub fn emission_curve(&self) -> Option<EmissionCurve> {
+        self.options
+            .as_ref()
+            .and_then(|options| options.emission_curve.clone())
+    }
+
+    pub fn vesting(&self) -> Option<Vec<VestingArgs>> {
+        self.options
+            .as_ref()
+            .and_then(|options| options.vesting.clone())
+    }
+
+    pub fn allocations(&self) -> Option<Vec<TgeAllocation>> {
+        self.options
+            .as_ref()
+            .and_then(|options| options.allocations.clone())
+    }
+
+    pub fn launch_protection(&self) -> Option<LaunchProtection> {
+        self.options
+            .as_ref()
+            .and_then(|options| options.launch_protection.clone())
+    }
+
+    pub fn fee_schedule(&self) -> FeeSchedule {
+        self.options
+            .as_ref()
+            .and_then(|options| options.fee_schedule.clone())
+            .unwrap_or_default()
+    }
+
+    /// The allocations tokenomics is installed with: the requested ones, plus the part of
+    /// `initial_primary_mint` nobody else gets, unvested, for `creator`.
+    pub fn tge_allocations(&self, creator: Principal) -> Vec<TgeAllocation> {
+        let mut allocations = self.allocations().unwrap_or_default();
+        let remainder = unallocated_tge(
+            &allocations,
+            &self.vesting().unwrap_or_default(),
+            self.initial_primary_mint,
+        );
+        if remainder > 0 {
+            allocations.push(TgeAllocation {
+                bucket: AllocationBucket::Creator,
+                recipient: creator,
+                amount: remainder,
+                vesting: None,
+            });
+        }
+        allocations
+    }
+}
+
+/// The next step a launch has to run. Steps are executed in declaration order.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum LaunchStep {
+    CreateSwapCanister,
+    CreateTokenomicsCanister,
+    CreateFrontendCanister,
+    CreateLogsCanister,
+    CreatePrimaryLedger,
+    InstallPrimaryLedger,
+    CreateSecondaryLedger,
+    InstallSecondaryLedger,
+    InstallTokenomics,
+    MintTgeAllocations,
+    InstallIcpSwap,
+    InstallLogs,
+    AddTokenToDex,
+    RecordToken,
+}
+
+impl LaunchStep {
+    pub fn next(&self) -> Option<LaunchStep> {
+        use LaunchStep::*;
+        match self {
+            CreateSwapCanister => Some(CreateTokenomicsCanister),
+            CreateTokenomicsCanister => Some(CreateFrontendCanister),
+            CreateFrontendCanister => Some(CreateLogsCanister),
+            CreateLogsCanister => Some(CreatePrimaryLedger),
+            CreatePrimaryLedger => Some(InstallPrimaryLedger),
+            InstallPrimaryLedger => Some(CreateSecondaryLedger),
+            CreateSecondaryLedger => Some(InstallSecondaryLedger),
+            InstallSecondaryLedger => Some(InstallTokenomics),
+            InstallTokenomics => Some(MintTgeAllocations),
+            MintTgeAllocations => Some(InstallIcpSwap),
+            InstallIcpSwap => Some(InstallLogs),
+            InstallLogs => Some(AddTokenToDex),
+            AddTokenToDex => Some(RecordToken),
+            RecordToken => None,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum LaunchStatus {
+    InProgress,
+    Completed,
+    Failed,
+    Refunded,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct LaunchRecord {
+    pub id: u64,
+    pub caller: Principal,
+    pub params: LaunchParams,
+    pub step: LaunchStep,
+    pub status: LaunchStatus,
+    pub swap_canister_id: Option<Principal>,
+    pub tokenomics_canister_id: Option<Principal>,
+    pub frontend_canister_id: Option<Principal>,
+    pub logs_canister_id: Option<Principal>,
+    pub primary_token_id: Option<Principal>,
+    pub secondary_token_id: Option<Principal>,
+    pub token_record_id: Option<u64>,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub created_time: u64,
+    pub updated_time: u64,
+}
+
+impl Storable for LaunchRecord {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+pub const REFUNDS_MEM_ID: MemoryId = MemoryId::new(2);
+
+thread_local! {
+    pub static REFUNDS: RefCell<StableBTreeMap<u64, RefundRecord, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(REFUNDS_MEM_ID))
+        )
+    );
+}
+
+pub const RECLAIMED_CYCLES_MEM_ID: MemoryId = MemoryId::new(8);
+
+thread_local! {
+    // Cycles received from each canister a refund is draining. A canister is only listed
+    // while it is drained, so nothing else can deposit here.
+    pub static RECLAIMED_CYCLES: RefCell<StableBTreeMap<Principal, u128, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(RECLAIMED_CYCLES_MEM_ID))
+        )
+    );
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum RefundStatus {
+    Pending,
+    Refunded,
+}
+
+/// Rollback of a failed launch, keyed by launch id.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct RefundRecord {
+    pub launch_id: u64,
+    pub caller: Principal,
+    pub status: RefundStatus,
+    pub fee_paid: u64,
+    pub deleted_canisters: Vec<Principal>,
+    pub cycles_reclaimed: u128,
+    pub cycles_spent: u128,
+    pub fees_spent: u64,
+    pub refunded_amount: u64,
+    pub block_index: Option<u64>,
+    pub created_time: u64,
+    pub refunded_time: Option<u64>,
+    // `created_at_time` of the refund transfer, shared by its retries so the ledger
+    // dedups them. Renewed once the ledger rejects it as too old.
+    pub transfer_time: Option<u64>,
+}
+
+impl Storable for RefundRecord {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+pub const CONFIGS_MEM_ID: MemoryId = MemoryId::new(3);
+
+thread_local! {
+    pub static CONFIGS: RefCell<StableCell<Configs, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CONFIGS_MEM_ID)),
+            Configs::default()
+        ).unwrap()
+    );
+}
+
+pub const ROLES_MEM_ID: MemoryId = MemoryId::new(4);
+pub const ROLE_AUDIT_MEM_ID: MemoryId = MemoryId::new(5);
+
+thread_local! {
+    pub static ROLES: RefCell<RoleStore<Memory>> = RefCell::new(
+        RoleStore::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(ROLES_MEM_ID)),
+            MEMORY_MANAGER.with(|m| m.borrow().get(ROLE_AUDIT_MEM_ID)),
+        )
+    );
+}
+
+/// External canisters this canister talks to. Unset entries fall back to mainnet.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct Configs {
+    pub icp_ledger_id: Option<Principal>,
+    pub cycles_minting_canister_id: Option<Principal>,
+    pub lbry_swap_canister_id: Option<Principal>,
+    pub kong_backend_canister_id: Option<Principal>,
+    pub icpswap_factory_canister_id: Option<Principal>,
+    // Handed to every launched swap canister; theirs defaults to mainnet too.
+    pub xrc_canister_id: Option<Principal>,
+}
+
+impl Configs {
+    pub fn icp_ledger_id(&self) -> Principal {
+        self.icp_ledger_id
+            .unwrap_or_else(|| get_principal(ICP_CANISTER_ID))
+    }
+
+    pub fn cycles_minting_canister_id(&self) -> Principal {
+        self.cycles_minting_canister_id
+            .unwrap_or_else(|| get_principal(CYCLES_MINTING_CANISTER_ID))
+    }
+
+    pub fn lbry_swap_canister_id(&self) -> Principal {
+        self.lbry_swap_canister_id
+            .unwrap_or_else(|| get_principal(LBRY_SWAP_CANISTER_ID))
+    }
+
+    pub fn dex_canister_id(&self, kind: DexKind) -> Principal {
+        let configured = match kind {
+            DexKind::KongSwap => self.kong_backend_canister_id,
+            DexKind::IcpSwap => self.icpswap_factory_canister_id,
+        };
+        configured.unwrap_or_else(|| kind.mainnet_canister_id())
+    }
+
+    pub fn dex(&self, kind: DexKind) -> Dex {
+        Dex::new(kind, self.dex_canister_id(kind))
+    }
+}
+
+impl Storable for Configs {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+pub fn get_config() -> Configs {
+    CONFIGS.with(|c| c.borrow().get().clone())
+}
+
+pub const WASM_MODULES_MEM_ID: MemoryId = MemoryId::new(6);
+pub const UPGRADES_MEM_ID: MemoryId = MemoryId::new(7);
+pub const WASM_UPLOADS_MEM_ID: MemoryId = MemoryId::new(9);
+
+thread_local! {
+    pub static WASM_MODULES: RefCell<StableBTreeMap<(u64, TokenCanister), WasmModule, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(WASM_MODULES_MEM_ID))
+        )
+    );
+
+    pub static UPGRADES: RefCell<StableBTreeMap<u64, UpgradeRun, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(UPGRADES_MEM_ID))
+        )
+    );
+
+    // Wasms uploaded in chunks, until they are committed to `WASM_MODULES`.
+    pub static WASM_UPLOADS: RefCell<StableBTreeMap<(u64, TokenCanister), WasmUpload, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(WASM_UPLOADS_MEM_ID))
+        )
+    );
+}
+
+/// The canisters every launch deploys besides its ledgers, in the order they are upgraded.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TokenCanister {
+    Tokenomics,
+    IcpSwap,
+    Logs,
+}
+
+impl TokenCanister {
+    pub const ALL: [TokenCanister; 3] = [
+        TokenCanister::Tokenomics,
+        TokenCanister::IcpSwap,
+        TokenCanister::Logs,
+    ];
+}
+
+impl Storable for TokenCanister {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(vec![*self as u8])
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        match bytes[0] {
+            0 => TokenCanister::Tokenomics,
+            1 => TokenCanister::IcpSwap,
+            2 => TokenCanister::Logs,
+            other => panic!("Unknown token canister {}", other),
+        }
+    }
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 1,
+        is_fixed_size: true,
+    };
+}
+
+/// An uploaded wasm, keyed by release version and canister. Never replaced, so any
+/// release a token ran can be reinstalled.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct WasmModule {
+    pub wasm: Vec<u8>,
+    pub sha256: Vec<u8>,
+    pub uploaded_by: Principal,
+    pub uploaded_time: u64,
+}
+
+impl Storable for WasmModule {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// A wasm too large for one message, put together chunk by chunk.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct WasmUpload {
+    pub wasm: Vec<u8>,
+    pub uploaded_by: Principal,
+    pub started_time: u64,
+}
+
+impl Storable for WasmUpload {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum UpgradeTarget {
+    Token(u64),
+    All,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum UpgradeStatus {
+    InProgress,
+    // Stopped at a token that failed; resumable once the cause is fixed.
+    Halted,
+    Completed,
+}
+
+/// One token's move between releases within a run. `error` is set once it failed.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TokenUpgrade {
+    pub token_id: u64,
+    pub from_version: u64,
+    pub to_version: u64,
+    pub error: Option<String>,
+}
+
+/// A staged rollout of a release to one or all tokens, keyed by run id.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct UpgradeRun {
+    pub id: u64,
+    pub target: UpgradeTarget,
+    // Release being rolled out or, for a rollback, rolled back.
+    pub version: u64,
+    pub status: UpgradeStatus,
+    pub pending: Vec<TokenUpgrade>,
+    pub upgraded: Vec<TokenUpgrade>,
+    pub failed: Vec<TokenUpgrade>,
+    pub rollback_of: Option<u64>,
+    pub rolled_back_by: Option<u64>,
+    pub started_by: Principal,
+    pub created_time: u64,
+    pub updated_time: u64,
+}
+
+impl Storable for UpgradeRun {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+    const BOUND: Bound = Bound::Unbounded;
+}
@@ -1,4 +1,4 @@
-use candid::{CandidType, Deserialize, Encode, Nat, Principal};
+use candid::{Encode, Nat, Principal};
 use ic_cdk::{
     api::management_canister::main::{
         canister_status, create_canister, install_code, CanisterInstallMode, CreateCanisterArgument,
@@ -15,17 +15,31 @@ use num_bigint::BigUint;
 use icrc_ledger_types::icrc1::transfer::BlockIndex;
 use std::time::Duration;
 
+pub use access_control::PausableOp;
+use access_control::validate_external_canister;
+use dex_adapter::{DexAdapter, DexKind};
+use tokenomics_core::{
+    EmissionCurve, FeeSchedule, LaunchProtection, TgeAllocation, VestingArgs,
+};
+
 use crate::{
-    get_principal, get_self_icp_balance, AddPoolArgs, AddPoolReply, AddPoolResult, AddTokenArgs,
-    AddTokenReply, AddTokenResponse, AddTokenResult, ApproveArgs, ApproveResult, ArchiveOptions,
-    FeatureFlags, IcpSwapInitArgs, InitArgs, LedgerArg, LogsInitArgs, MetadataValue, TokenDetail,
-    TokenInfo, TokenRecord, TokenomicsInitArgs, CHAIN_ID, E8S, ICP_CANISTER_ID, ICP_TRANSFER_FEE,
-    INTITAL_PRIMARY_MINT, KONG_BACKEND_CANISTER, TOKENS,
+    advance_upgrades, get_config, get_self_icp_balance, has_role, refund_failed_launches, register_launch,
+    retry_stalled_launches, run_launch, validate_launch_metadata, validate_launch_tokenomics,
+    ApproveArgs, ApproveResult, ArchiveOptions, Configs, FeatureFlags, IcpSwapInitArgs, InitArgs,
+    LaunchOptions, LaunchParams, LaunchTokenomics, LedgerArg, LogsInitArgs, MetadataValue,
+    Role, TokenRecord, TokenomicsInitArgs, CONFIGS, E8S, ICP_TRANSFER_FEE, TOKENS,
+    UPGRADE_STAGE_INTERVAL,
 };
 
-const CANISTER_CREATION_CYCLES: u128 = 2_000_000_000_000u128;
-const ICP_LEDGER_CANISTER_ID: &str = "ryjl3-tyaaa-aaaaa-aaaba-cai";
-const LBRY_SWAP_CANISTER_ID: &str = "54fqz-5iaaa-aaaap-qkmqa-cai";
+pub(crate) const CANISTER_CREATION_CYCLES: u128 = 2_000_000_000_000u128;
+pub(crate) const LAUNCH_FEE: u64 = 200_000_000;
+const LAUNCH_RETRY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+// What new launches are installed with; `upgrade_token_canisters` can reinstall them as
+// release `BUNDLED_WASM_VERSION`.
+pub(crate) const TOKENOMICS_WASM: &[u8] = include_bytes!("tokenomics.wasm");
+pub(crate) const ICP_SWAP_WASM: &[u8] = include_bytes!("icp_swap.wasm");
+pub(crate) const LOGS_WASM: &[u8] = include_bytes!("logs.wasm");
 
 #[ic_cdk::update]
 async fn create_token(
@@ -43,166 +57,63 @@ async fn create_token(
     initial_secondary_burn: u64,
     halving_step: u64,
     initial_reward_per_burn_unit: u64,
+    options: Option<LaunchOptions>,
 ) -> Result<String, String> {
     let user_principal = ic_cdk::api::caller(); // Get the calling user's principal
-    // payment
-    deposit_icp_in_canister(200_000_000, None)
-        .await
-        .map_err(|e| format!("Failed to deposit ICP: {:?}", e))?;
-
-    let swap_canister_id = create_a_canister(CANISTER_CREATION_CYCLES).await?;
-    let tokenomics_canister_id = create_a_canister(CANISTER_CREATION_CYCLES).await?;
-    let frontend_canister_id = create_a_canister(CANISTER_CREATION_CYCLES).await?;
-    let logs_canister_id = create_a_canister(CANISTER_CREATION_CYCLES).await?;
-
-    // Create primary token
-    // ALEX
-
-    // max supply from user
-    //
-    // Note on Initial Liquidity Pool Token:
-    // This call intentionally uses the hardcoded `INTITAL_PRIMARY_MINT` constant.
-    // This constant represents exactly 1 token plus the standard transfer fee.
-    // This single token is used to seed the initial liquidity pool on the DEX.
-    // Because this amount is negligible and its purpose is purely functional (to create the pool),
-    // it is considered separate from the main tokenomic calculations, which begin with the TGE
-    // and scheduled minting.
-    let primary_token_id = match create_icrc1_canister(
-        primary_token_symbol.clone(),
-        primary_token_name.clone(),
+    let params = LaunchParams {
+        primary_token_name,
+        primary_token_symbol,
         primary_token_description,
-        tokenomics_canister_id,
-        tokenomics_canister_id,
-        INTITAL_PRIMARY_MINT,
         primary_logo,
-        CANISTER_CREATION_CYCLES,
-    )
-    .await
-    {
-        Ok(canister_id) => {
-            ic_cdk::println!("Primary Token ID: {}", canister_id);
-            canister_id.to_string()
-        }
-        Err(e) => return Err(e.to_string()),
-    };
-
-    // Create secondary token
-    let secondary_token_id = match create_icrc1_canister(
-        secondary_token_symbol.clone(),
-        secondary_token_name.clone(),
+        secondary_token_name,
+        secondary_token_symbol,
         secondary_token_description,
-        swap_canister_id,
-        swap_canister_id,
-        0,
         secondary_logo,
-        CANISTER_CREATION_CYCLES,
-    )
-    .await
-    {
-        Ok(canister_id) => {
-            ic_cdk::println!("Secondary Token ID: {}", canister_id);
-            canister_id.to_string()
-        }
-        Err(e) => return Err(e.to_string()),
-    };
-    install_tokenomics_wasm_on_existing_canister(
-        tokenomics_canister_id,
-        Some(get_principal(&primary_token_id)),
-        Some(get_principal(&secondary_token_id)),
-        Some(swap_canister_id),
-        Some(frontend_canister_id),
-        primary_max_supply.into(),
+        primary_max_supply,
+        primary_max_phase_mint,
         initial_primary_mint,
         initial_secondary_burn,
-        primary_max_phase_mint,
         halving_step,
         initial_reward_per_burn_unit,
-    )
-    .await?;
-    install_icp_swap_wasm_on_existing_canister(
-        swap_canister_id,
-        Some(get_principal(&primary_token_id)),
-        Some(get_principal(&secondary_token_id)),
-        Some(tokenomics_canister_id),
-    )
-    .await?;
-
-    install_logs_wasm_on_existing_canister(
-        logs_canister_id,
-        get_principal(&primary_token_id),
-        get_principal(&secondary_token_id),
-        swap_canister_id,
-        tokenomics_canister_id,
-    )
-    .await?;
-
-    match add_token_to_kong_swap(get_principal(&primary_token_id)).await {
-        AddTokenResponse::Ok(_) => (),
-        AddTokenResponse::Err(e) => return Err(format!("Failed to add token to swap: {}", e)),
+        options,
     };
+    // Rejected here rather than at install, after the fee has been taken.
+    validate_launch_metadata(&params)?;
+    validate_launch_tokenomics(&LaunchTokenomics::from(&params)).into_result()?;
+    if let Some(protection) = params.launch_protection() {
+        protection.validate()?;
+    }
+    params.fee_schedule().validate()?;
 
-    approve_tokens_to_spender(
-        get_principal(&primary_token_id),
-        get_principal(KONG_BACKEND_CANISTER),
-        INTITAL_PRIMARY_MINT.into(),
-    )
-    .await?;
+    // payment
+    deposit_icp_in_canister(LAUNCH_FEE, None)
+        .await
+        .map_err(|e| format!("Failed to deposit ICP: {:?}", e))?;
 
-    approve_tokens_to_spender(
-        get_principal(ICP_CANISTER_ID),
-        get_principal(KONG_BACKEND_CANISTER),
-        INTITAL_PRIMARY_MINT.into(),
-    )
-    .await?;
-    ic_cdk::println!("Tokens approved!");
-
-    TOKENS.with(|tokens| {
-        let mut tokens = tokens.borrow_mut();
-        let token_id = tokens.len() as u64 + 1; // Generate a new token ID
-        let token_record = TokenRecord {
-            id: token_id,
-            primary_token_id: get_principal(&primary_token_id),
-            primary_token_name: primary_token_name.clone(),
-            primary_token_symbol: primary_token_symbol.clone(),
-            primary_token_max_supply: primary_max_supply,
-            secondary_token_id: get_principal(&secondary_token_id),
-            secondary_token_name: secondary_token_name.clone(),
-            secondary_token_symbol: secondary_token_symbol.clone(),
-            icp_swap_canister_id: swap_canister_id,
-            tokenomics_canister_id,
-            logs_canister_id,
-            initial_primary_mint,
-            initial_secondary_burn,
-            primary_max_phase_mint,
-            halving_step,
-            caller: user_principal,
-            created_time: ic_cdk::api::time(),
-            liquidity_provided_at: 0,
-            is_live: false,
-        };
-        tokens.insert(token_id, token_record);
-    });
+    // From here on every step is persisted, so a failure can be resumed instead of
+    // leaving orphaned canisters behind.
+    let launch_id = register_launch(user_principal, params);
+
+    run_launch(launch_id).await.map_err(|e| {
+        format!(
+            "{}. The launch will be retried automatically or can be resumed with resume_launch({}).",
+            e, launch_id
+        )
+    })?;
 
     Ok("Tokens created and stored!".to_string())
 }
 
-async fn create_icrc1_canister(
+pub(crate) async fn install_icrc1_ledger(
+    canister_id: Principal,
     token_symbol: String,
     token_name: String,
     token_description: String,
     minting_account_owner: Principal,
     archive_controller: Principal,
     intital_amount: u64,
-    logo: String,
-    cycles: u128,
-) -> Result<String, String> {
-    let create_args = CreateCanisterArgument { settings: None };
-    let canister_id_record = create_canister(create_args, cycles)
-        .await
-        .map_err(|e| format!("Failed to create canister: {:?}", e))?;
-
-    let canister_id = canister_id_record.0.canister_id;
-
+    logo_data_url: String,
+) -> Result<(), String> {
     let wasm_bytes = include_bytes!("ic-icrc1-ledger.wasm");
 
     let minter_account = Account {
@@ -233,7 +144,7 @@ async fn create_icrc1_canister(
             ),
             (
                 "logo".to_string(),
-                MetadataValue::Text(format!("data:image/svg+xml;base64,{}", logo)),
+                MetadataValue::Text(logo_data_url),
             ),
         ],
         feature_flags: Some(FeatureFlags { icrc2: true }),
@@ -264,10 +175,10 @@ async fn create_icrc1_canister(
         .await
         .map_err(|e| format!("Failed to install ICRC-1 token: {:?}", e))?;
 
-    Ok(canister_id.to_string())
+    Ok(())
 }
 
-async fn create_a_canister(cycles: u128) -> Result<Principal, String> {
+pub(crate) async fn create_a_canister(cycles: u128) -> Result<Principal, String> {
     let create_args = CreateCanisterArgument { settings: None };
     let canister_id_record = create_canister(create_args, cycles)
         .await
@@ -277,7 +188,7 @@ async fn create_a_canister(cycles: u128) -> Result<Principal, String> {
     Ok(canister_id)
 }
 
-async fn install_tokenomics_wasm_on_existing_canister(
+pub(crate) async fn install_tokenomics_wasm_on_existing_canister(
     canister_id: Principal,
     primary_token_id: Option<Principal>,
     secondary_token_id: Option<Principal>,
@@ -289,6 +200,9 @@ async fn install_tokenomics_wasm_on_existing_canister(
     max_primary_phase: u64,
     halving_step: u64,
     initial_reward_per_burn_unit: u64,
+    emission_curve: Option<EmissionCurve>,
+    vesting: Option<Vec<VestingArgs>>,
+    allocations: Vec<TgeAllocation>,
 ) -> Result<(), String> {
     let args = TokenomicsInitArgs {
         primary_token_id,
@@ -301,11 +215,14 @@ async fn install_tokenomics_wasm_on_existing_canister(
         max_primary_phase,
         halving_step,
         initial_reward_per_burn_unit,
+        emission_curve,
+        vesting,
+        allocations: Some(allocations),
     };
     let encoded_args = Encode!(&Some(args))
         .map_err(|e: candid::Error| format!("Failed to encode args: {:?}", e))?;
 
-    let wasm_module = include_bytes!("tokenomics.wasm").to_vec(); // Path must be valid in your project
+    let wasm_module = TOKENOMICS_WASM.to_vec();
 
     let install_args = InstallCodeArgument {
         mode: CanisterInstallMode::Install,
@@ -321,23 +238,35 @@ async fn install_tokenomics_wasm_on_existing_canister(
     Ok(())
 }
 
-async fn install_icp_swap_wasm_on_existing_canister(
+pub(crate) async fn install_icp_swap_wasm_on_existing_canister(
     canister_id: Principal,
     primary_token_id: Option<Principal>,
     secondary_token_id: Option<Principal>,
     tokenomics_canister_id: Option<Principal>,
+    dex: Option<DexKind>,
+    launch_protection: Option<LaunchProtection>,
+    fee_schedule: FeeSchedule,
+    creator: Principal,
 ) -> Result<(), String> {
+    let config = get_config();
     let args = IcpSwapInitArgs {
         primary_token_id,
         secondary_token_id,
         tokenomics_canister_id,
-        icp_ledger_id: None, // None means use default (our standard ICP ledger)
+        icp_ledger_id: Some(config.icp_ledger_id()),
+        dex,
+        dex_canister_id: Some(config.dex_canister_id(dex.unwrap_or_default())),
+        lbry_fun_canister_id: Some(ic_cdk::api::id()),
+        xrc_canister_id: config.xrc_canister_id,
+        launch_protection,
+        fee_schedule: Some(fee_schedule),
+        creator: Some(creator),
     };
 
     let encoded_args =
         Encode!(&Some(args)).map_err(|e| format!("Failed to encode args: {:?}", e))?;
 
-    let wasm_module = include_bytes!("icp_swap.wasm").to_vec(); // Path must be valid in your project
+    let wasm_module = ICP_SWAP_WASM.to_vec();
 
     let install_args = InstallCodeArgument {
         mode: CanisterInstallMode::Install,
@@ -353,7 +282,7 @@ async fn install_icp_swap_wasm_on_existing_canister(
     Ok(())
 }
 
-async fn install_logs_wasm_on_existing_canister(
+pub(crate) async fn install_logs_wasm_on_existing_canister(
     canister_id: Principal,
     primary_token_id: Principal,
     secondary_token_id: Principal,
@@ -369,7 +298,7 @@ async fn install_logs_wasm_on_existing_canister(
     let encoded_args =
         Encode!(&args).map_err(|e: candid::Error| format!("Failed to encode args: {:?}", e))?;
 
-    let wasm_module = include_bytes!("logs.wasm").to_vec();
+    let wasm_module = LOGS_WASM.to_vec();
 
     let install_args = InstallCodeArgument {
         mode: CanisterInstallMode::Install,
@@ -385,61 +314,6 @@ async fn install_logs_wasm_on_existing_canister(
     Ok(())
 }
 
-async fn add_token_to_kong_swap(token_id: Principal) -> AddTokenResponse {
-    let args: AddTokenArgs = AddTokenArgs {
-        token: format!("IC.{}", token_id),
-    };
-
-    let result: Result<(AddTokenResult,), _> =
-        ic_cdk::call(get_principal(KONG_BACKEND_CANISTER), "add_token", (args,)).await;
-
-    match result {
-        Ok((AddTokenResult::Ok(AddTokenReply::IC(token_info)),)) => {
-            ic_cdk::println!("Token added: {:?}", token_info);
-            AddTokenResponse::Ok(TokenDetail::IC(TokenInfo {
-                token_id: token_info.token_id,
-                chain: token_info.chain,
-                canister_id: token_info.canister_id,
-                name: token_info.name,
-                symbol: token_info.symbol,
-                decimals: token_info.decimals,
-                fee: 0, // safely handle Nat
-                icrc1: token_info.icrc1,
-                icrc2: token_info.icrc2,
-                icrc3: token_info.icrc3,
-                is_removed: token_info.is_removed,
-            }))
-        }
-        Ok((AddTokenResult::Err(err_msg),)) => {
-            AddTokenResponse::Err(format!("Add token failed: {}", err_msg))
-        }
-        Err(e) => {
-            ic_cdk::println!("Error calling backend: {:?}", e);
-            AddTokenResponse::Err("Call failed".to_string())
-        }
-    }
-}
-
-pub async fn create_pool_on_kong_swap(primary_token_id: Principal) -> Result<AddPoolReply, String> {
-    let args = AddPoolArgs {
-        token_0: format!("{}.{}", CHAIN_ID, primary_token_id),
-        amount_0: (E8S).into(),
-        token_1: format!("{}.{}", CHAIN_ID, ICP_CANISTER_ID), //ICP PAIR
-        amount_1: (10_000_000 as u64).into(),
-        on_kong: true,
-    };
-
-    let (result,): (AddPoolResult,) =
-        ic_cdk::call(get_principal(KONG_BACKEND_CANISTER), "add_pool", (args,))
-            .await
-            .map_err(|e| format!("Call failed: {:?}", e))?;
-
-    match result {
-        AddPoolResult::Ok(reply) => Ok(reply),
-        AddPoolResult::Err(e) => Err(format!("Pool creation failed: {}", e)),
-    }
-}
-
 pub async fn approve_tokens_to_spender(
     ledger_canister_id: Principal,
     spender: Principal,
@@ -499,7 +373,7 @@ async fn deposit_icp_in_canister(
     };
 
     let (result,): (Result<BlockIndex, TransferFromError>,) = ic_cdk::call(
-        get_principal(ICP_CANISTER_ID),
+        get_config().icp_ledger_id(),
         "icrc2_transfer_from",
         (transfer_args,),
     )
@@ -545,7 +419,7 @@ async fn deposit_ksicp_in_canister(
         ::call::<(TransferFromArgs,), (Result<BlockIndex, TransferFromError>,)>(
             // 2. Convert a textual representation of a Principal into an actual `Principal` object. The principal is the one we specified in `dfx.json`.
             //    `expect` will panic if the conversion fails, ensuring the code does not proceed with an invalid principal.
-            Principal::from_text(ICP_CANISTER_ID).expect("Could not decode the principal."),
+            get_config().icp_ledger_id(),
             // 3. Specify the method name on the target canister to be called, in this case, "icrc1_transfer".
             "icrc2_transfer_from",
             // 4. Provide the arguments for the call in a tuple, here `transfer_args` is encapsulated as a single-element tuple.
@@ -559,7 +433,7 @@ async fn deposit_ksicp_in_canister(
     result // Return the inner Result<BlockIndex, TransferFromError>
 }
 
-pub async fn publish_eligible_tokens_on_kongswap() ->Result<String,String>{
+pub async fn publish_eligible_tokens_on_dex() ->Result<String,String>{
     let time = ic_cdk::api::time(); // current time in nanoseconds
     // let twenty_four_hours_in_nanos: u64 = 24 * 60 * 60 * 1_000_000_000;
     let twenty_four_hours_in_nanos: u64 = 1000; // for testing
@@ -580,11 +454,22 @@ pub async fn publish_eligible_tokens_on_kongswap() ->Result<String,String>{
         
         if let Some(mut token) = token_opt {
             if token.created_time + twenty_four_hours_in_nanos <= time && !token.is_live {
-                match create_pool_on_kong_swap(token.primary_token_id).await {
+                let config = get_config();
+                let pool = config
+                    .dex(token.dex.unwrap_or_default())
+                    .create_pool(
+                        token.primary_token_id,
+                        E8S.into(),
+                        config.icp_ledger_id(), //ICP PAIR
+                        (10_000_000 as u64).into(),
+                    )
+                    .await;
+                match pool {
                     Ok(_) => {
                         ic_cdk::println!("Pool created!");
                         token.is_live = true;
                         token.liquidity_provided_at = time;
+                        token.launch_protection_pending = Some(true);
                         
                         TOKENS.with(|tokens| {
                             let mut tokens_map = tokens.borrow_mut();
@@ -597,14 +482,45 @@ pub async fn publish_eligible_tokens_on_kongswap() ->Result<String,String>{
                         ));
                     },
                     Err(e) => {
-                        ic_cdk::print(format!("Failed to create pool on Kong swap: {}", e));
-                        return Err(format!("Failed to create pool on Kong swap: {}", e));
+                        ic_cdk::print(format!("Failed to create pool on DEX: {}", e));
+                        return Err(format!("Failed to create pool on DEX: {}", e));
                     }
                 }
             }
+            if token.launch_protection_pending() {
+                start_launch_protection(&mut token).await;
+            }
         }
     }
-    Ok("Published eligible tokens on KongSwap successfully.".to_string())
+    Ok("Published eligible tokens on DEX successfully.".to_string())
+}
+
+/// Starts the swap canister's launch-protection window from when liquidity was provided.
+/// The token is live either way; a failed call stays pending and is retried on the next run.
+async fn start_launch_protection(token: &mut TokenRecord) {
+    let started: ic_cdk::api::call::CallResult<(Result<candid::Reserved, candid::Reserved>,)> =
+        ic_cdk::call(
+            token.icp_swap_canister_id,
+            "set_liquidity_provided_at",
+            (token.liquidity_provided_at,),
+        )
+        .await;
+    match started {
+        Ok((Ok(_),)) => {
+            token.launch_protection_pending = Some(false);
+            TOKENS.with(|tokens| {
+                tokens.borrow_mut().insert(token.id, token.clone());
+            });
+        }
+        Ok((Err(_),)) => ic_cdk::print(format!(
+            "Swap canister of token {} rejected the launch protection start",
+            token.id
+        )),
+        Err((code, msg)) => ic_cdk::print(format!(
+            "Failed to start launch protection for token {}: {:?} - {}",
+            token.id, code, msg
+        )),
+    }
 }
 
 async fn _process_fee_treasury() -> Result<String, String> {
@@ -625,11 +541,12 @@ async fn _process_fee_treasury() -> Result<String, String> {
         return Ok(log_msg);
     }
 
-    let lbry_swap_principal = Principal::from_text(LBRY_SWAP_CANISTER_ID).unwrap();
+    let config = get_config();
+    let lbry_swap_principal = config.lbry_swap_canister_id();
 
     // 1. Approve the LBRY swap canister to spend our ICP
     match approve_tokens_to_spender(
-        Principal::from_text(ICP_LEDGER_CANISTER_ID).unwrap(),
+        config.icp_ledger_id(),
         lbry_swap_principal,
         balance.into(),
     )
@@ -672,8 +589,7 @@ async fn _process_fee_treasury() -> Result<String, String> {
     }
 }
 
-#[ic_cdk::init]
-fn init() {
+fn setup_timers() {
     // Schedule the treasury processing to run every hour.
     let hourly = Duration::from_secs(60 * 60);
     set_timer_interval(hourly, || {
@@ -685,7 +601,102 @@ fn init() {
     // Schedule publishing eligible tokens to run every hour.
     set_timer_interval(hourly, || {
         ic_cdk::spawn(async {
-            let _ = publish_eligible_tokens_on_kongswap().await;
+            let _ = publish_eligible_tokens_on_dex().await;
         });
     });
-}
\ No newline at end of file
+
+    // Pick up launches that stalled partway through and refund the ones that failed.
+    set_timer_interval(LAUNCH_RETRY_INTERVAL, || {
+        ic_cdk::spawn(async {
+            retry_stalled_launches().await;
+            refund_failed_launches().await;
+        });
+    });
+
+    // Roll out the next stage of a running canister upgrade.
+    set_timer_interval(UPGRADE_STAGE_INTERVAL, || {
+        ic_cdk::spawn(advance_upgrades());
+    });
+}
+
+#[ic_cdk::init]
+fn init(args: Option<Configs>) {
+    if let Some(config) = args {
+        validate_external_canisters(&config).expect("Invalid init args");
+        CONFIGS.with(|c| c.borrow_mut().set(config)).expect("Failed to store configs");
+    }
+    setup_timers();
+}
+
+/// Repoints the external canisters, e.g. at a local replica or PocketIC mocks.
+/// Unset fields are left as they are.
+#[update]
+fn update_external_canisters(args: Configs) -> Result<Configs, String> {
+    if !has_role(ic_cdk::caller(), Role::Controller) {
+        return Err("Only a controller can update external canisters".to_string());
+    }
+    validate_external_canisters(&args)?;
+
+    let mut config = get_config();
+    config.icp_ledger_id = args.icp_ledger_id.or(config.icp_ledger_id);
+    config.cycles_minting_canister_id = args
+        .cycles_minting_canister_id
+        .or(config.cycles_minting_canister_id);
+    config.lbry_swap_canister_id = args.lbry_swap_canister_id.or(config.lbry_swap_canister_id);
+    config.kong_backend_canister_id = args
+        .kong_backend_canister_id
+        .or(config.kong_backend_canister_id);
+    config.icpswap_factory_canister_id = args
+        .icpswap_factory_canister_id
+        .or(config.icpswap_factory_canister_id);
+    config.xrc_canister_id = args.xrc_canister_id.or(config.xrc_canister_id);
+
+    CONFIGS
+        .with(|c| c.borrow_mut().set(config.clone()))
+        .map_err(|e| format!("Failed to store configs: {:?}", e))?;
+    ic_cdk::println!("External canisters updated: {:?}", config);
+    Ok(config)
+}
+
+/// Pauses or resumes one operation on a token's icp_swap. Operators only.
+#[update]
+async fn set_token_paused(token_id: u64, op: PausableOp, paused: bool) -> Result<(), String> {
+    if !has_role(ic_cdk::caller(), Role::Operator) {
+        return Err("Only an operator can pause token operations".to_string());
+    }
+    let token = TOKENS
+        .with(|tokens| tokens.borrow().get(&token_id))
+        .ok_or(format!("Token {} not found", token_id))?;
+
+    let result: ic_cdk::api::call::CallResult<(Result<candid::Reserved, candid::Reserved>,)> =
+        ic_cdk::call(token.icp_swap_canister_id, "set_paused", (op, paused)).await;
+    match result {
+        Ok((Ok(_),)) => Ok(()),
+        Ok((Err(_),)) => Err(format!("icp_swap of token {} refused to set {:?}", token_id, op)),
+        Err((code, msg)) => Err(format!(
+            "Failed to call icp_swap of token {}: {:?} - {}",
+            token_id, code, msg
+        )),
+    }
+}
+
+fn validate_external_canisters(config: &Configs) -> Result<(), String> {
+    for (field, canister_id) in [
+        ("icp_ledger_id", config.icp_ledger_id),
+        ("cycles_minting_canister_id", config.cycles_minting_canister_id),
+        ("lbry_swap_canister_id", config.lbry_swap_canister_id),
+        ("kong_backend_canister_id", config.kong_backend_canister_id),
+        ("icpswap_factory_canister_id", config.icpswap_factory_canister_id),
+        ("xrc_canister_id", config.xrc_canister_id),
+    ] {
+        if let Some(canister_id) = canister_id {
+            validate_external_canister(field, canister_id)?;
+        }
+    }
+    Ok(())
+}
+
+#[ic_cdk::post_upgrade]
+fn post_upgrade() {
+    setup_timers();
+}
@@ -0,0 +1,613 @@
+use candid::{CandidType, Encode, Principal};
+use ic_cdk::{
+    api::management_canister::main::{
+        canister_status, install_code, start_canister, stop_canister, CanisterIdRecord,
+        CanisterInstallMode, CanisterStatusType, InstallCodeArgument,
+    },
+    caller, query, update,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{cell::Cell, time::Duration};
+
+use crate::{
+    guard::*, TokenCanister, TokenRecord, TokenUpgrade, UpgradeRun, UpgradeStatus, UpgradeTarget,
+    WasmModule, WasmUpload, ICP_SWAP_WASM, LOGS_WASM, TOKENOMICS_WASM, TOKENS, UPGRADES,
+    WASM_MODULES, WASM_UPLOADS,
+};
+
+/// Release the wasms bundled into lbry_fun go by. Tokens launched before upgrades existed
+/// run it too, though from an older lbry_fun build; rolling back to it installs today's.
+pub const BUNDLED_WASM_VERSION: u64 = 0;
+/// Tokens upgraded in a rollout's first stage, before any other token is touched.
+pub const UPGRADE_CANARY_SIZE: usize = 1;
+/// Tokens upgraded in every later stage.
+pub const UPGRADE_BATCH_SIZE: usize = 5;
+/// Time between stages, so a bad release shows up before it reaches every token.
+pub const UPGRADE_STAGE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// Cycles each canister must hold to pass a health check.
+pub const MIN_UPGRADE_CYCLES: u128 = 200_000_000_000;
+
+thread_local! {
+    static UPGRADE_IN_FLIGHT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Makes sure only one upgrade stage runs at a time.
+struct UpgradeGuard;
+
+impl UpgradeGuard {
+    fn new() -> Result<Self, String> {
+        UPGRADE_IN_FLIGHT.with(|in_flight| {
+            if in_flight.replace(true) {
+                return Err("An upgrade stage is already running".to_string());
+            }
+            Ok(Self)
+        })
+    }
+}
+
+impl Drop for UpgradeGuard {
+    fn drop(&mut self) {
+        UPGRADE_IN_FLIGHT.with(|in_flight| in_flight.set(false));
+    }
+}
+
+/// An uploaded wasm, without its bytes.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct WasmInfo {
+    pub version: u64,
+    pub canister: TokenCanister,
+    pub sha256: Vec<u8>,
+    pub size: u64,
+    pub uploaded_by: Principal,
+    pub uploaded_time: u64,
+}
+
+impl WasmInfo {
+    fn new(version: u64, canister: TokenCanister, module: &WasmModule) -> Self {
+        WasmInfo {
+            version,
+            canister,
+            sha256: module.sha256.clone(),
+            size: module.wasm.len() as u64,
+            uploaded_by: module.uploaded_by,
+            uploaded_time: module.uploaded_time,
+        }
+    }
+}
+
+/// Stores one canister's wasm for release `version`. A release can be rolled out once
+/// all three of its wasms are uploaded. Wasms over the message size limit go through
+/// `upload_token_wasm_chunk` and `commit_token_wasm` instead.
+#[update(guard = "is_controller_role")]
+fn upload_token_wasm(
+    version: u64,
+    canister: TokenCanister,
+    wasm: Vec<u8>,
+) -> Result<WasmInfo, String> {
+    check_uploadable(version, canister)?;
+    store_wasm(version, canister, wasm)
+}
+
+/// Appends `chunk` to the wasm being uploaded for release `version`, and returns how
+/// many bytes are staged so far.
+#[update(guard = "is_controller_role")]
+fn upload_token_wasm_chunk(
+    version: u64,
+    canister: TokenCanister,
+    chunk: Vec<u8>,
+) -> Result<u64, String> {
+    check_uploadable(version, canister)?;
+    WASM_UPLOADS.with(|uploads| {
+        let mut uploads = uploads.borrow_mut();
+        let mut upload = uploads.get(&(version, canister)).unwrap_or(WasmUpload {
+            wasm: vec![],
+            uploaded_by: caller(),
+            started_time: ic_cdk::api::time(),
+        });
+        upload.wasm.extend_from_slice(&chunk);
+        let staged = upload.wasm.len() as u64;
+        uploads.insert((version, canister), upload);
+        Ok(staged)
+    })
+}
+
+/// Stores the chunks uploaded for release `version` as its wasm once they hash to
+/// `expected_sha256`. On a mismatch the chunks are kept, so a missing one can still be sent.
+#[update(guard = "is_controller_role")]
+fn commit_token_wasm(
+    version: u64,
+    canister: TokenCanister,
+    expected_sha256: Vec<u8>,
+) -> Result<WasmInfo, String> {
+    check_uploadable(version, canister)?;
+    let upload = WASM_UPLOADS
+        .with(|uploads| uploads.borrow().get(&(version, canister)))
+        .ok_or(format!("No chunks uploaded for version {} of the {:?} wasm", version, canister))?;
+    let uploaded = sha256(&upload.wasm);
+    if uploaded != expected_sha256 {
+        return Err(format!(
+            "Uploaded chunks hash to {}, expected {}",
+            hex::encode(&uploaded),
+            hex::encode(&expected_sha256)
+        ));
+    }
+    let info = store_wasm(version, canister, upload.wasm)?;
+    WASM_UPLOADS.with(|uploads| uploads.borrow_mut().remove(&(version, canister)));
+    Ok(info)
+}
+
+/// Drops the chunks uploaded for release `version`, to start its upload over.
+#[update(guard = "is_controller_role")]
+fn cancel_token_wasm_upload(version: u64, canister: TokenCanister) -> Result<(), String> {
+    WASM_UPLOADS
+        .with(|uploads| uploads.borrow_mut().remove(&(version, canister)))
+        .map(|_| ())
+        .ok_or(format!("No chunks uploaded for version {} of the {:?} wasm", version, canister))
+}
+
+fn check_uploadable(version: u64, canister: TokenCanister) -> Result<(), String> {
+    if version == BUNDLED_WASM_VERSION {
+        return Err(format!(
+            "Version {} is the wasm bundled with lbry_fun",
+            BUNDLED_WASM_VERSION
+        ));
+    }
+    if WASM_MODULES.with(|modules| modules.borrow().contains_key(&(version, canister))) {
+        return Err(format!(
+            "Version {} of the {:?} wasm is already uploaded",
+            version, canister
+        ));
+    }
+    Ok(())
+}
+
+fn store_wasm(version: u64, canister: TokenCanister, wasm: Vec<u8>) -> Result<WasmInfo, String> {
+    // Raw or gzipped, as install_code takes either.
+    if !wasm.starts_with(b"\0asm") && !wasm.starts_with(&[0x1f, 0x8b]) {
+        return Err("Not a wasm module".to_string());
+    }
+    let module = WasmModule {
+        sha256: sha256(&wasm),
+        wasm,
+        uploaded_by: caller(),
+        uploaded_time: ic_cdk::api::time(),
+    };
+    let info = WasmInfo::new(version, canister, &module);
+    WASM_MODULES.with(|modules| modules.borrow_mut().insert((version, canister), module));
+    Ok(info)
+}
+
+/// Moves the swap, tokenomics and logs canisters of one token, or of every token, to
+/// release `version`. The first token is upgraded right away as a canary; the rest
+/// follow in stages, and the rollout halts at the first token that fails.
+#[update(guard = "is_controller_role")]
+async fn upgrade_token_canisters(
+    target: UpgradeTarget,
+    version: u64,
+) -> Result<UpgradeRun, String> {
+    check_release(version)?;
+    if let Some(active) = active_upgrade() {
+        return Err(format!("Upgrade run {} is still in progress", active.id));
+    }
+
+    let tokens: Vec<TokenRecord> = match target {
+        UpgradeTarget::Token(token_id) => vec![get_token(token_id)?],
+        UpgradeTarget::All => {
+            TOKENS.with(|tokens| tokens.borrow().iter().map(|(_, token)| token).collect())
+        }
+    };
+    let pending: Vec<TokenUpgrade> = tokens
+        .iter()
+        .filter(|token| token.canister_version() != version)
+        .map(|token| TokenUpgrade {
+            token_id: token.id,
+            from_version: token.canister_version(),
+            to_version: version,
+            error: None,
+        })
+        .collect();
+    if pending.is_empty() {
+        return Err(format!(
+            "Every targeted token already runs version {}",
+            version
+        ));
+    }
+
+    let run_id = register_upgrade(target, version, pending, None);
+    run_upgrade_stage(run_id).await
+}
+
+/// Puts every token a finished or halted run upgraded back on the release it ran before.
+#[update(guard = "is_controller_role")]
+async fn rollback_token_upgrade(run_id: u64) -> Result<UpgradeRun, String> {
+    let mut run = get_upgrade_run(run_id).ok_or(format!("Upgrade run {} not found", run_id))?;
+    if run.status == UpgradeStatus::InProgress {
+        return Err(format!(
+            "Upgrade run {} is still in progress; roll it back once it halts or completes",
+            run_id
+        ));
+    }
+    if let Some(rollback_id) = run.rolled_back_by {
+        return Err(format!(
+            "Upgrade run {} is already rolled back by run {}",
+            run_id, rollback_id
+        ));
+    }
+    if let Some(active) = active_upgrade() {
+        return Err(format!("Upgrade run {} is still in progress", active.id));
+    }
+
+    // Latest first, so the canary is the last token to go back.
+    let pending: Vec<TokenUpgrade> = run
+        .upgraded
+        .iter()
+        .rev()
+        .map(|upgrade| TokenUpgrade {
+            token_id: upgrade.token_id,
+            from_version: upgrade.to_version,
+            to_version: upgrade.from_version,
+            error: None,
+        })
+        .collect();
+    if pending.is_empty() {
+        return Err(format!("Upgrade run {} did not upgrade any token", run_id));
+    }
+
+    let rollback_id = register_upgrade(run.target.clone(), run.version, pending, Some(run_id));
+    run.rolled_back_by = Some(rollback_id);
+    save_upgrade_run(&run);
+    run_upgrade_stage(rollback_id).await
+}
+
+/// Runs the next stage of a run now. A halted run retries the token it stopped at.
+#[update(guard = "is_controller_role")]
+async fn resume_token_upgrade(run_id: u64) -> Result<UpgradeRun, String> {
+    let mut run = get_upgrade_run(run_id).ok_or(format!("Upgrade run {} not found", run_id))?;
+    match run.status {
+        UpgradeStatus::Completed => {
+            return Err(format!("Upgrade run {} is already completed", run_id))
+        }
+        UpgradeStatus::Halted => {
+            if let Some(active) = active_upgrade() {
+                return Err(format!("Upgrade run {} is still in progress", active.id));
+            }
+            if let Some(failed) = run.failed.last() {
+                run.pending.insert(
+                    0,
+                    TokenUpgrade {
+                        error: None,
+                        ..failed.clone()
+                    },
+                );
+            }
+            run.status = UpgradeStatus::InProgress;
+            save_upgrade_run(&run);
+        }
+        UpgradeStatus::InProgress => (),
+    }
+
+    run_upgrade_stage(run_id).await
+}
+
+/// Runs the next stage of the rollout in progress, if there is one.
+pub async fn advance_upgrades() {
+    let Some(run) = active_upgrade() else {
+        return;
+    };
+    match run_upgrade_stage(run.id).await {
+        Ok(run) => ic_cdk::println!(
+            "Upgrade run {} is {:?} with {} tokens pending",
+            run.id,
+            run.status,
+            run.pending.len()
+        ),
+        Err(e) => ic_cdk::println!("Upgrade run {} did not advance: {}", run.id, e),
+    }
+}
+
+/// Upgrades the tokens of one stage, saving the run after each of them.
+async fn run_upgrade_stage(run_id: u64) -> Result<UpgradeRun, String> {
+    let _guard = UpgradeGuard::new()?;
+    let mut run = get_upgrade_run(run_id).ok_or(format!("Upgrade run {} not found", run_id))?;
+    if run.status != UpgradeStatus::InProgress {
+        return Err(format!("Upgrade run {} is {:?}", run_id, run.status));
+    }
+
+    // A rollback skips the canary: the tokens already ran the release it goes back to.
+    let stage_size = if run.upgraded.is_empty() && run.rollback_of.is_none() {
+        UPGRADE_CANARY_SIZE
+    } else {
+        UPGRADE_BATCH_SIZE
+    };
+    for _ in 0..stage_size {
+        if run.pending.is_empty() {
+            break;
+        }
+        let mut upgrade = run.pending.remove(0);
+        match upgrade_token(&upgrade).await {
+            Ok(()) => run.upgraded.push(upgrade),
+            Err(e) => {
+                upgrade.error = Some(e);
+                run.failed.push(upgrade);
+                run.status = UpgradeStatus::Halted;
+            }
+        }
+        run.updated_time = ic_cdk::api::time();
+        save_upgrade_run(&run);
+        if run.status == UpgradeStatus::Halted {
+            return Ok(run);
+        }
+    }
+
+    if run.pending.is_empty() {
+        run.status = UpgradeStatus::Completed;
+        save_upgrade_run(&run);
+    }
+    Ok(run)
+}
+
+/// Upgrades a token's canisters one after the other. If one of them fails, those
+/// already upgraded are put back on `from_version`, so the set never runs mixed releases.
+async fn upgrade_token(upgrade: &TokenUpgrade) -> Result<(), String> {
+    let token = get_token(upgrade.token_id)?;
+    if token.canister_version() != upgrade.from_version {
+        return Err(format!(
+            "Token {} runs version {}, not {}",
+            token.id,
+            token.canister_version(),
+            upgrade.from_version
+        ));
+    }
+
+    // Nothing is touched unless the whole set is healthy. A canister already on the new
+    // release is left over from an interrupted attempt, and upgraded again. Which build
+    // of the bundled release a token runs isn't known, so its modules aren't checked.
+    for canister in TokenCanister::ALL {
+        let expected = match upgrade.from_version {
+            BUNDLED_WASM_VERSION => Vec::new(),
+            from_version => vec![
+                sha256(&wasm_module(from_version, canister)?),
+                sha256(&wasm_module(upgrade.to_version, canister)?),
+            ],
+        };
+        check_health(token.canister_id(canister), &expected)
+            .await
+            .map_err(|e| format!("Pre-upgrade check of {:?} failed: {}", canister, e))?;
+    }
+
+    let mut installed = Vec::new();
+    for canister in TokenCanister::ALL {
+        let canister_id = token.canister_id(canister);
+        let wasm = wasm_module(upgrade.to_version, canister)?;
+        let expected = vec![sha256(&wasm)];
+        let result = match install_upgrade(canister_id, wasm).await {
+            Ok(()) => {
+                installed.push(canister);
+                check_health(canister_id, &expected)
+                    .await
+                    .map_err(|e| format!("Post-upgrade check of {:?} failed: {}", canister, e))
+            }
+            Err(e) => Err(format!("Upgrading {:?} failed: {}", canister, e)),
+        };
+
+        if let Err(e) = result {
+            return Err(
+                match roll_back_token(&token, &installed, upgrade.from_version).await {
+                    Ok(()) => format!("{}; rolled back to version {}", e, upgrade.from_version),
+                    Err(rollback_error) => format!(
+                        "{}; rolling back to version {} failed: {}",
+                        e, upgrade.from_version, rollback_error
+                    ),
+                },
+            );
+        }
+    }
+
+    set_canister_version(token.id, upgrade.to_version)
+}
+
+async fn roll_back_token(
+    token: &TokenRecord,
+    installed: &[TokenCanister],
+    version: u64,
+) -> Result<(), String> {
+    for canister in installed.iter().rev() {
+        let canister_id = token.canister_id(*canister);
+        let wasm = wasm_module(version, *canister)?;
+        let expected = vec![sha256(&wasm)];
+        install_upgrade(canister_id, wasm)
+            .await
+            .map_err(|e| format!("Reinstalling {:?} failed: {}", canister, e))?;
+        check_health(canister_id, &expected)
+            .await
+            .map_err(|e| format!("{:?} is unhealthy after the rollback: {}", canister, e))?;
+    }
+    Ok(())
+}
+
+/// Stops the canister so no call is in flight while its code changes, then starts it
+/// again whatever happened. One left stopped fails its health check.
+async fn install_upgrade(canister_id: Principal, wasm_module: Vec<u8>) -> Result<(), String> {
+    let stopped = stop_canister(CanisterIdRecord { canister_id })
+        .await
+        .map_err(|(_code, msg)| format!("Failed to stop {}: {}", canister_id, msg));
+    let installed = match stopped {
+        Ok(()) => install_code(InstallCodeArgument {
+            mode: CanisterInstallMode::Upgrade(None),
+            canister_id,
+            wasm_module,
+            arg: Encode!().map_err(|e| format!("Failed to encode args: {:?}", e))?,
+        })
+        .await
+        .map_err(|(_code, msg)| format!("Failed to install on {}: {}", canister_id, msg)),
+        Err(e) => Err(e),
+    };
+    if let Err((_code, msg)) = start_canister(CanisterIdRecord { canister_id }).await {
+        ic_cdk::println!("Failed to start {} after its upgrade: {}", canister_id, msg);
+    }
+    installed
+}
+
+/// Checks the canister runs, has cycles to spare, answers calls and, unless `expected`
+/// is empty, runs one of those module hashes.
+async fn check_health(canister_id: Principal, expected: &[Vec<u8>]) -> Result<(), String> {
+    let (status,) = canister_status(CanisterIdRecord { canister_id })
+        .await
+        .map_err(|(_code, msg)| format!("Failed to get canister status: {}", msg))?;
+    if status.status != CanisterStatusType::Running {
+        return Err(format!("{} is {:?}", canister_id, status.status));
+    }
+    if status.cycles < MIN_UPGRADE_CYCLES {
+        return Err(format!(
+            "{} holds {} cycles, below the {} required",
+            canister_id, status.cycles, MIN_UPGRADE_CYCLES
+        ));
+    }
+    let running = status.module_hash.unwrap_or_default();
+    if !expected.is_empty() && !expected.contains(&running) {
+        return Err(format!(
+            "{} runs module {}, which is not the expected one",
+            canister_id,
+            hex::encode(running)
+        ));
+    }
+
+    // Every release exports its interface through `export_candid!`, so asking for it
+    // shows the module is up and serving calls.
+    let _: (String,) = ic_cdk::call(canister_id, "__get_candid_interface_tmp_hack", ())
+        .await
+        .map_err(|(code, msg)| {
+            format!(
+                "{} does not answer calls: {:?} - {}",
+                canister_id, code, msg
+            )
+        })?;
+    Ok(())
+}
+
+fn check_release(version: u64) -> Result<(), String> {
+    for canister in TokenCanister::ALL {
+        let uploaded = version == BUNDLED_WASM_VERSION
+            || WASM_MODULES.with(|modules| modules.borrow().contains_key(&(version, canister)));
+        if !uploaded {
+            return Err(format!(
+                "Version {} has no {:?} wasm uploaded",
+                version, canister
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn wasm_module(version: u64, canister: TokenCanister) -> Result<Vec<u8>, String> {
+    if version == BUNDLED_WASM_VERSION {
+        let wasm = match canister {
+            TokenCanister::Tokenomics => TOKENOMICS_WASM,
+            TokenCanister::IcpSwap => ICP_SWAP_WASM,
+            TokenCanister::Logs => LOGS_WASM,
+        };
+        return Ok(wasm.to_vec());
+    }
+    WASM_MODULES
+        .with(|modules| modules.borrow().get(&(version, canister)))
+        .map(|module| module.wasm)
+        .ok_or(format!(
+            "Version {} has no {:?} wasm uploaded",
+            version, canister
+        ))
+}
+
+fn sha256(bytes: &[u8]) -> Vec<u8> {
+    Sha256::digest(bytes).to_vec()
+}
+
+fn get_token(token_id: u64) -> Result<TokenRecord, String> {
+    TOKENS
+        .with(|tokens| tokens.borrow().get(&token_id))
+        .ok_or(format!("Token {} not found", token_id))
+}
+
+fn set_canister_version(token_id: u64, version: u64) -> Result<(), String> {
+    TOKENS.with(|tokens| {
+        let mut tokens = tokens.borrow_mut();
+        let mut token = tokens
+            .get(&token_id)
+            .ok_or(format!("Token {} not found", token_id))?;
+        token.canister_version = Some(version);
+        tokens.insert(token_id, token);
+        Ok(())
+    })
+}
+
+fn register_upgrade(
+    target: UpgradeTarget,
+    version: u64,
+    pending: Vec<TokenUpgrade>,
+    rollback_of: Option<u64>,
+) -> u64 {
+    let now = ic_cdk::api::time();
+    UPGRADES.with(|upgrades| {
+        let mut upgrades = upgrades.borrow_mut();
+        let run_id = upgrades.last_key_value().map(|(id, _)| id + 1).unwrap_or(1);
+        upgrades.insert(
+            run_id,
+            UpgradeRun {
+                id: run_id,
+                target,
+                version,
+                status: UpgradeStatus::InProgress,
+                pending,
+                upgraded: Vec::new(),
+                failed: Vec::new(),
+                rollback_of,
+                rolled_back_by: None,
+                started_by: caller(),
+                created_time: now,
+                updated_time: now,
+            },
+        );
+        run_id
+    })
+}
+
+fn save_upgrade_run(run: &UpgradeRun) {
+    UPGRADES.with(|upgrades| {
+        upgrades.borrow_mut().insert(run.id, run.clone());
+    });
+}
+
+pub fn get_upgrade_run(run_id: u64) -> Option<UpgradeRun> {
+    UPGRADES.with(|upgrades| upgrades.borrow().get(&run_id))
+}
+
+fn active_upgrade() -> Option<UpgradeRun> {
+    UPGRADES.with(|upgrades| {
+        upgrades
+            .borrow()
+            .iter()
+            .map(|(_, run)| run)
+            .find(|run| run.status == UpgradeStatus::InProgress)
+    })
+}
+
+#[query]
+fn get_upgrade_status(run_id: u64) -> Option<UpgradeRun> {
+    get_upgrade_run(run_id)
+}
+
+#[query]
+fn get_upgrade_runs() -> Vec<UpgradeRun> {
+    UPGRADES.with(|upgrades| upgrades.borrow().iter().map(|(_, run)| run).collect())
+}
+
+#[query]
+fn get_token_wasms() -> Vec<WasmInfo> {
+    WASM_MODULES.with(|modules| {
+        modules
+            .borrow()
+            .iter()
+            .map(|((version, canister), module)| WasmInfo::new(version, canister, &module))
+            .collect()
+    })
+}
@@ -1,119 +1,23 @@
 use candid::{CandidType, Nat, Principal};
 use icrc_ledger_types::icrc1::account::Account;
 use num_traits::ToPrimitive;
+use dex_adapter::DexKind;
+use tokenomics_core::{FeeSchedule, LaunchProtection};
 use serde::{Deserialize, Serialize};
-pub const KONG_BACKEND_CANISTER: &str = "2ipq2-uqaaa-aaaar-qailq-cai";
+
+use crate::get_config;
 pub const ICP_CANISTER_ID: &str = "ryjl3-tyaaa-aaaaa-aaaba-cai";
 pub const INTITAL_PRIMARY_MINT: u64 = 100_010_000; // 1 token (100,000,000) + transfer fee (10,000)
 pub const ICP_TRANSFER_FEE: u64 = 10_000;
 
 pub const E8S:u64=100_000_000;
-pub const CHAIN_ID: &str = "IC";
+pub const CYCLES_MINTING_CANISTER_ID: &str = "rkp4c-7iaaa-aaaaa-aaaca-cai";
+pub const LBRY_SWAP_CANISTER_ID: &str = "54fqz-5iaaa-aaaap-qkmqa-cai";
 
 pub fn get_principal(id: &str) -> Principal {
     Principal::from_text(id).expect(&format!("Invalid principal: {}", id))
 }
 
-#[derive(CandidType, Deserialize, Debug)]
-pub struct AddTokenArgs {
-    pub token: String,
-}
-#[derive(CandidType, Deserialize, Debug)]
-pub enum AddTokenResponse {
-    Ok(TokenDetail),
-    Err(String),
-}
-
-#[derive(CandidType, Deserialize, Debug)]
-pub enum TokenDetail {
-    IC(TokenInfo),
-}
-
-#[derive(CandidType, Deserialize, Debug)]
-pub enum AddTokenResult {
-    Ok(AddTokenReply),
-    Err(String),
-}
-
-#[derive(CandidType, Deserialize, Debug)]
-pub enum AddTokenReply {
-    IC(ICTokenReply),
-}
-
-#[derive(CandidType, Deserialize, Debug)]
-pub struct ICTokenReply {
-    pub token_id: u32,
-    pub chain: String,
-    pub canister_id: String,
-    pub name: String,
-    pub symbol: String,
-    pub decimals: u8,
-    pub fee: Nat,
-    pub icrc1: bool,
-    pub icrc2: bool,
-    pub icrc3: bool,
-    pub is_removed: bool,
-}
-
-#[derive(CandidType, Deserialize, Debug)]
-pub struct TokenInfo {
-    pub token_id: u32,
-    pub chain: String,
-    pub canister_id: String,
-    pub name: String,
-    pub symbol: String,
-    pub decimals: u8,
-    pub fee: u64, // backed by candid::Nat
-    pub icrc1: bool,
-    pub icrc2: bool,
-    pub icrc3: bool,
-    pub is_removed: bool,
-}
-
-//Add Pool
-
-#[derive(CandidType, Deserialize, Debug, Clone)]
-pub struct AddPoolArgs {
-    pub token_0: String,
-    pub amount_0: Nat,
-    pub token_1: String,
-    pub amount_1: Nat,
-    pub on_kong: bool,
-}
-
-#[derive(CandidType, Deserialize, Debug)]
-pub struct AddPoolReply {
-    pub pool_id: u32,
-    pub request_id: u64,
-    pub status: String,
-    pub name: String,
-    pub symbol: String,
-    pub chain_0: String,
-    pub address_0: String,
-    pub symbol_0: String,
-    pub amount_0: u128,
-    pub chain_1: String,
-    pub address_1: String,
-    pub symbol_1: String,
-    pub amount_1: u128,
-    pub lp_fee_bps: u8,
-    pub lp_token_symbol: String,
-    pub add_lp_token_amount: u128,
-    pub transfer_ids: Vec<TransferIdReply>,
-    pub claim_ids: Vec<u64>,
-    pub is_removed: bool,
-    pub ts: u64,
-}
-
-#[derive(CandidType, Deserialize, Debug)]
-pub struct TransferIdReply {}
-
-#[derive(CandidType, Deserialize, Debug)]
-pub enum AddPoolResult {
-    Ok(AddPoolReply),
-    Err(String),
-}
-
 // approve
 #[derive(CandidType, Deserialize, Debug)]
 pub enum ApproveResult {
@@ -143,19 +47,7 @@ pub struct ApproveArgs {
 }
 
 // tokenomics
-#[derive(CandidType, Serialize)]
-pub struct TokenomicsInitArgs {
-    pub primary_token_id: Option<Principal>,
-    pub secondary_token_id: Option<Principal>,
-    pub swap_canister_id: Option<Principal>,
-    pub frontend_canister_id: Option<Principal>,
-    pub max_primary_supply: u64,
-    pub initial_primary_mint: u64,
-    pub initial_secondary_burn: u64,
-    pub max_primary_phase: u64,
-    pub halving_step: u64,
-    pub initial_reward_per_burn_unit: u64,
-}
+pub use tokenomics_core::TokenomicsInitArgs;
 
 #[derive(CandidType)]
 pub struct IcpSwapInitArgs {
@@ -163,6 +55,13 @@ pub struct IcpSwapInitArgs {
     pub secondary_token_id: Option<Principal>,
     pub tokenomics_canister_id: Option<Principal>,
     pub icp_ledger_id: Option<Principal>,
+    pub dex: Option<DexKind>,
+    pub dex_canister_id: Option<Principal>,
+    pub lbry_fun_canister_id: Option<Principal>,
+    pub xrc_canister_id: Option<Principal>,
+    pub launch_protection: Option<LaunchProtection>,
+    pub fee_schedule: Option<FeeSchedule>,
+    pub creator: Option<Principal>,
 }
 
 
@@ -227,8 +126,22 @@ pub enum LedgerArg {
     Upgrade(Option<UpgradeArgs>),
 }
 
+// Cycles minting canister
+#[derive(CandidType, Deserialize, Debug)]
+pub struct IcpXdrConversionRate {
+    pub timestamp_seconds: u64,
+    pub xdr_permyriad_per_icp: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct IcpXdrConversionRateResponse {
+    pub data: IcpXdrConversionRate,
+    pub hash_tree: Vec<u8>,
+    pub certificate: Vec<u8>,
+}
+
 pub async fn get_self_icp_balance(principal: Principal) -> Result<u64, String> {
-    let ledger_canister_id = Principal::from_text(ICP_CANISTER_ID).unwrap();
+    let ledger_canister_id = get_config().icp_ledger_id();
     let args = icrc_ledger_types::icrc1::account::Account {
         owner: principal,
         subaccount: None,
@@ -0,0 +1,221 @@
+use candid::CandidType;
+use serde::Deserialize;
+use tokenomics_core::{
+    generate_emission_schedule, simulate_mint, unallocated_tge, validate_allocations,
+    validate_vesting, EmissionCurve, TgeAllocation, TokenomicsSchedule, VestingArgs,
+    MAX_TGE_ALLOCATIONS,
+};
+
+use crate::LaunchParams;
+
+/// Fewest epochs a launch should be able to mint through before hitting max supply.
+pub const MIN_RECOMMENDED_EPOCHS: u32 = 5;
+/// Largest share of max supply the TGE allocation should take, in basis points.
+pub const MAX_RECOMMENDED_TGE_SHARE_BPS: u64 = 2_000;
+const BASIS_POINTS: u128 = 10_000;
+
+/// The `create_token` arguments that shape the tokenomics schedule, in the same units.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct LaunchTokenomics {
+    pub primary_max_supply: u64,
+    pub primary_max_phase_mint: u64,
+    pub initial_primary_mint: u64,
+    pub initial_secondary_burn: u64,
+    pub halving_step: u64,
+    pub initial_reward_per_burn_unit: u64,
+    pub emission_curve: Option<EmissionCurve>,
+    pub vesting: Option<Vec<VestingArgs>>,
+    pub allocations: Option<Vec<TgeAllocation>>,
+}
+
+/// Combinations `create_token` refuses.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum LaunchParamsError {
+    MustBePositive { field: String },
+    // The TGE allocation leaves nothing to mint by burning.
+    TgeExceedsMaxSupply { initial_primary_mint: u64, primary_max_supply: u64 },
+    InvalidEmissionCurve { reason: String },
+    InvalidVesting { reason: String },
+    InvalidAllocations { reason: String },
+    // The parameters produce no burn thresholds, so nothing could ever be minted.
+    EmptySchedule,
+    // Thresholds no longer fit in a u64.
+    ScheduleOverflow { threshold_index: u32 },
+    // `mint_primary` would overflow somewhere along the schedule.
+    MintOverflow { details: String },
+}
+
+/// Combinations `create_token` accepts but that are likely a mistake.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum LaunchParamsWarning {
+    TooFewEpochs { epochs: u32, recommended_min: u32 },
+    LargeTgeShare { share_bps: u64, recommended_max_bps: u64 },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct LaunchValidation {
+    pub errors: Vec<LaunchParamsError>,
+    pub warnings: Vec<LaunchParamsWarning>,
+    // Epochs that can mint before max supply is reached.
+    pub epochs: u32,
+}
+
+impl LaunchValidation {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// `Err` with every error, for endpoints that return `Result<_, String>`.
+    pub fn into_result(self) -> Result<Self, String> {
+        if self.is_valid() {
+            return Ok(self);
+        }
+        let errors: Vec<String> = self.errors.iter().map(|e| format!("{:?}", e)).collect();
+        Err(format!("Invalid launch parameters: {}", errors.join(", ")))
+    }
+}
+
+impl LaunchTokenomics {
+    pub fn emission_curve(&self) -> EmissionCurve {
+        self.emission_curve.clone().unwrap_or(EmissionCurve::Halving {
+            halving_step: self.halving_step,
+        })
+    }
+
+    /// The schedule tokenomics would be installed with.
+    pub fn schedule(&self) -> TokenomicsSchedule {
+        generate_emission_schedule(
+            self.initial_secondary_burn,
+            self.initial_reward_per_burn_unit,
+            self.primary_max_supply,
+            &self.emission_curve(),
+        )
+    }
+}
+
+impl From<&LaunchParams> for LaunchTokenomics {
+    fn from(params: &LaunchParams) -> Self {
+        LaunchTokenomics {
+            primary_max_supply: params.primary_max_supply,
+            primary_max_phase_mint: params.primary_max_phase_mint,
+            initial_primary_mint: params.initial_primary_mint,
+            initial_secondary_burn: params.initial_secondary_burn,
+            halving_step: params.halving_step,
+            initial_reward_per_burn_unit: params.initial_reward_per_burn_unit,
+            emission_curve: params.emission_curve(),
+            vesting: params.vesting(),
+            allocations: params.allocations(),
+        }
+    }
+}
+
+/// Runs the checks tokenomics would otherwise only hit at install or while minting.
+pub fn validate_launch_tokenomics(params: &LaunchTokenomics) -> LaunchValidation {
+    let mut validation = LaunchValidation::default();
+
+    let positive_fields = [
+        ("primary_max_supply", params.primary_max_supply),
+        ("primary_max_phase_mint", params.primary_max_phase_mint),
+        ("initial_primary_mint", params.initial_primary_mint),
+        ("initial_secondary_burn", params.initial_secondary_burn),
+        ("initial_reward_per_burn_unit", params.initial_reward_per_burn_unit),
+    ];
+    for (field, value) in positive_fields {
+        if value == 0 {
+            validation.errors.push(LaunchParamsError::MustBePositive {
+                field: field.to_string(),
+            });
+        }
+    }
+    if params.initial_primary_mint >= params.primary_max_supply {
+        validation.errors.push(LaunchParamsError::TgeExceedsMaxSupply {
+            initial_primary_mint: params.initial_primary_mint,
+            primary_max_supply: params.primary_max_supply,
+        });
+    }
+    if let Err(reason) = params.emission_curve().validate() {
+        validation
+            .errors
+            .push(LaunchParamsError::InvalidEmissionCurve { reason });
+    }
+    if let Some(vesting) = &params.vesting {
+        if let Err(reason) = validate_vesting(vesting, params.initial_primary_mint) {
+            validation
+                .errors
+                .push(LaunchParamsError::InvalidVesting { reason });
+        }
+    }
+    if let Some(allocations) = &params.allocations {
+        let vesting = params.vesting.as_deref().unwrap_or_default();
+        if let Err(reason) =
+            validate_allocations(allocations, vesting, params.initial_primary_mint)
+        {
+            validation
+                .errors
+                .push(LaunchParamsError::InvalidAllocations { reason });
+        } else if allocations.len() >= MAX_TGE_ALLOCATIONS
+            && unallocated_tge(allocations, vesting, params.initial_primary_mint) > 0
+        {
+            // The creator's remainder is installed as one more allocation.
+            validation.errors.push(LaunchParamsError::InvalidAllocations {
+                reason: format!(
+                    "At most {} TGE allocations are allowed, counting the creator's remainder.",
+                    MAX_TGE_ALLOCATIONS
+                ),
+            });
+        }
+    }
+    if !validation.is_valid() {
+        return validation;
+    }
+
+    let schedule = params.schedule();
+    let thresholds = &schedule.secondary_burn_thresholds;
+    let Some(last_threshold) = thresholds.last().copied() else {
+        validation.errors.push(LaunchParamsError::EmptySchedule);
+        return validation;
+    };
+    if let Some(index) = thresholds.windows(2).position(|pair| pair[0] >= pair[1]) {
+        validation.errors.push(LaunchParamsError::ScheduleOverflow {
+            threshold_index: index as u32 + 1,
+        });
+        return validation;
+    }
+
+    // Burning the whole schedule at once is the largest mint tokenomics can be asked for.
+    let simulation = match simulate_mint(
+        &schedule,
+        0,
+        0,
+        last_threshold,
+        params.primary_max_phase_mint,
+    ) {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            validation.errors.push(LaunchParamsError::MintOverflow {
+                details: format!("{:?}", e),
+            });
+            return validation;
+        }
+    };
+
+    validation.epochs =
+        simulation.epochs_to_mint(params.primary_max_supply - params.initial_primary_mint);
+    if validation.epochs < MIN_RECOMMENDED_EPOCHS {
+        validation.warnings.push(LaunchParamsWarning::TooFewEpochs {
+            epochs: validation.epochs,
+            recommended_min: MIN_RECOMMENDED_EPOCHS,
+        });
+    }
+
+    let share_bps = (params.initial_primary_mint as u128 * BASIS_POINTS
+        / params.primary_max_supply as u128) as u64;
+    if share_bps > MAX_RECOMMENDED_TGE_SHARE_BPS {
+        validation.warnings.push(LaunchParamsWarning::LargeTgeShare {
+            share_bps,
+            recommended_max_bps: MAX_RECOMMENDED_TGE_SHARE_BPS,
+        });
+    }
+
+    validation
+}
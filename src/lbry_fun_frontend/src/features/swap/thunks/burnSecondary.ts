@@ -4,6 +4,7 @@ import { getActorSwap, getICRCActor } from "@/features/auth/utils/authUtils";
 import getCanisterBal from "@/features/icp-ledger/thunks/getCanisterBal";
 import getCanisterArchivedBal from "./getCanisterArchivedBal";
 import { ErrorMessage, getErrorMessage } from "../utlis/erorrs";
+import { minAmountOut } from "../utlis/slippage";
 import { RootState } from "@/store";
 
 // Define the async thunk
@@ -69,7 +70,21 @@ const burnSecondary = createAsyncThunk<
       const actorSwap = await getActorSwap(
         state.swap.activeSwapPool?.[1].icp_swap_canister_id
       );
-      const result = await actorSwap.burn_secondary(amountFormat, []);
+      const quote = await actorSwap.quote_burn_secondary(amountFormat);
+      if ("Err" in quote) {
+        return rejectWithValue(getErrorMessage(quote.Err));
+      }
+      const result = await actorSwap.burn_secondary(
+        amountFormat,
+        [],
+        [
+          {
+            icp: minAmountOut(quote.Ok.icp),
+            primary: minAmountOut(quote.Ok.primary),
+          },
+        ],
+        []
+      );
       if ("Ok" in result) {
         dispatch(getCanisterBal());
         dispatch(getCanisterArchivedBal());
@@ -65,7 +65,8 @@ const stakePrimary = createAsyncThunk<
       }
 
       const actorSwap = await getActorSwap(icp_swap_canister_id);
-      const result = await actorSwap.stake_primary(amountFormat, []);
+      // No lock period: a flexible stake that can be unstaked at any time.
+      const result = await actorSwap.stake_primary(amountFormat, [], []);
       console.log("result is is ", result);
       if ("Ok" in result) return "success";
       if ("Err" in result) {
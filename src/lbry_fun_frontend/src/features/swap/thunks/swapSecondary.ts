@@ -5,6 +5,7 @@ import {
   getIcpLedgerActor,
 } from "@/features/auth/utils/authUtils";
 import { ErrorMessage, getErrorMessage } from "../utlis/erorrs";
+import { minAmountOut } from "../utlis/slippage";
 // Define the async thunk
 const swapSecondary = createAsyncThunk<
   string, // This is the return type of the thunk's payload
@@ -57,7 +58,16 @@ const swapSecondary = createAsyncThunk<
         }
       }
 
-      const result = await actorSwap.swap(amountFormat, []);
+      const quote = await actorSwap.quote_swap(amountFormat);
+      if ("Err" in quote) {
+        return rejectWithValue(getErrorMessage(quote.Err));
+      }
+      const result = await actorSwap.swap(
+        amountFormat,
+        [],
+        [minAmountOut(quote.Ok)],
+        []
+      );
       if ("Ok" in result) return "success";
       if ("Err" in result) {
         const errorMessage = getErrorMessage(result.Err);
@@ -0,0 +1,7 @@
+// How far below the quote a swap or burn may pay out before the canister rejects it.
+export const SLIPPAGE_TOLERANCE_BPS = BigInt(100);
+
+const BASIS_POINTS = BigInt(10000);
+
+export const minAmountOut = (quoted: bigint): bigint =>
+  (quoted * (BASIS_POINTS - SLIPPAGE_TOLERANCE_BPS)) / BASIS_POINTS;
@@ -7,6 +7,7 @@ import previewTokenomics from '../thunk/previewTokenomics.thunk';
 import { RootState } from '@/store';
 import { TailSpin } from 'react-loader-spinner';
 import { GraphData } from '../lbryFunSlice';
+import { EmissionCurve } from '../../../../../declarations/lbry_fun/lbry_fun.did';
 
 interface TokenomicsGraphsBackendProps {
   primaryMaxSupply: string;
@@ -14,6 +15,7 @@ interface TokenomicsGraphsBackendProps {
   initialSecondaryBurn: string;
   halvingStep: string;
   initialRewardPerBurnUnit: string;
+  emissionCurve?: EmissionCurve;
 }
 
 const E8S = 100_000_000;
@@ -88,6 +90,7 @@ const TokenomicsGraphsBackend: React.FC<TokenomicsGraphsBackendProps> = ({
   initialSecondaryBurn,
   halvingStep,
   initialRewardPerBurnUnit,
+  emissionCurve,
 }) => {
   const dispatch = useAppDispatch();
   const { previewGraphData, previewLoading, previewError } = useAppSelector((state: RootState) => state.lbryFun);
@@ -139,9 +142,10 @@ const TokenomicsGraphsBackend: React.FC<TokenomicsGraphsBackendProps> = ({
             initial_secondary_burn,
             halving_step,
             initial_reward_per_burn_unit,
+            emission_curve: emissionCurve,
         }}));
     }
-  }, [primaryMaxSupply, tgeAllocation, initialSecondaryBurn, halvingStep, initialRewardPerBurnUnit, dispatch]);
+  }, [primaryMaxSupply, tgeAllocation, initialSecondaryBurn, halvingStep, initialRewardPerBurnUnit, emissionCurve, dispatch]);
 
   const {
     cumulativeSupplyData,
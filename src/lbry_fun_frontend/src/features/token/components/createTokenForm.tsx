@@ -18,6 +18,7 @@ import { useNavigate } from 'react-router-dom';
 import { RootState } from "@/store";
 import TokenomicsGraphsBackend from './TokenomicsGraphsBackend';
 import TooltipIcon from './TooltipIcon';
+import { EmissionCurve } from '../../../../../declarations/lbry_fun/lbry_fun.did';
 
 // Define E8S constant for conversion
 const E8S = 100_000_000;
@@ -37,6 +38,8 @@ export interface TokenFormValues {
   primary_max_phase_mint: string;
   halving_step: string;
   initial_reward_per_burn_unit: string;
+  // Halving by `halving_step` when unset.
+  emission_curve?: EmissionCurve;
 }
 
 interface FormErrors {
@@ -709,6 +712,7 @@ const CreateTokenForm: React.FC = () => {
               initialSecondaryBurn={form.initial_secondary_burn}
               halvingStep={form.halving_step}
               initialRewardPerBurnUnit={form.initial_reward_per_burn_unit}
+              emissionCurve={form.emission_curve}
           />
 
         {/* Submit Button Section */}
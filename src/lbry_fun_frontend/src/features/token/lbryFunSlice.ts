@@ -18,6 +18,8 @@ export interface GraphData {
   cost_to_mint_data_y: number[];
   cumulative_usd_cost_data_x: string[];
   cumulative_usd_cost_data_y: number[];
+  tge_allocation_data_x: string[];
+  tge_allocation_data_y: string[];
 }
 
 // Define the interface for our node state
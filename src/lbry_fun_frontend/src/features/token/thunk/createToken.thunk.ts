@@ -74,7 +74,23 @@ const createToken = createAsyncThunk<
         BigInt(formData.initial_primary_mint),
         BigInt(formData.initial_secondary_burn),
         BigInt(formData.halving_step),
-        BigInt(formData.initial_reward_per_burn_unit)
+        BigInt(formData.initial_reward_per_burn_unit),
+        formData.emission_curve ||
+        formData.vesting?.length ||
+        formData.allocations?.length ||
+        formData.launch_protection ||
+        formData.fee_schedule
+          ? [
+              {
+                dex: [],
+                emission_curve: formData.emission_curve ? [formData.emission_curve] : [],
+                vesting: formData.vesting?.length ? [formData.vesting] : [],
+                allocations: formData.allocations?.length ? [formData.allocations] : [],
+                launch_protection: formData.launch_protection ? [formData.launch_protection] : [],
+                fee_schedule: formData.fee_schedule ? [formData.fee_schedule] : [],
+              },
+            ]
+          : []
       );
 
       if ("Ok" in result) {
@@ -1,6 +1,7 @@
 import { createAsyncThunk } from "@reduxjs/toolkit";
 import { getLbryFunActor } from "@/features/auth/utils/authUtils";
 import { GraphData } from "../lbryFunSlice";
+import { EmissionCurve, TgeAllocation } from "../../../../../declarations/lbry_fun/lbry_fun.did";
 
 export interface PreviewArgs {
     primary_max_supply: bigint;
@@ -8,6 +9,8 @@ export interface PreviewArgs {
     initial_secondary_burn: bigint;
     halving_step: bigint;
     initial_reward_per_burn_unit: bigint;
+    emission_curve?: EmissionCurve;
+    allocations?: TgeAllocation[];
 }
 
 const previewTokenomics = createAsyncThunk<GraphData, { args: PreviewArgs }, { rejectValue: { title: string, message: string } }>(
@@ -21,6 +24,8 @@ const previewTokenomics = createAsyncThunk<GraphData, { args: PreviewArgs }, { r
                 initial_secondary_burn: args.initial_secondary_burn,
                 halving_step: args.halving_step,
                 initial_reward_per_burn_unit: args.initial_reward_per_burn_unit,
+                emission_curve: args.emission_curve ? [args.emission_curve] : [],
+                allocations: args.allocations?.length ? [args.allocations] : [],
             });
 
             const serializablePayload: GraphData = {
@@ -32,6 +37,8 @@ const previewTokenomics = createAsyncThunk<GraphData, { args: PreviewArgs }, { r
                 cost_to_mint_data_y: result.cost_to_mint_data_y,
                 cumulative_usd_cost_data_x: Array.from(result.cumulative_usd_cost_data_x, (v) => v.toString()),
                 cumulative_usd_cost_data_y: result.cumulative_usd_cost_data_y,
+                tge_allocation_data_x: result.tge_allocation_data_x,
+                tge_allocation_data_y: Array.from(result.tge_allocation_data_y, (v) => v.toString()),
             };
 
             return serializablePayload;
@@ -1,3 +1,9 @@
+use access_control::Role;
+
+use crate::ROLES;
+
+access_control::role_guards!(ROLES);
+
 pub fn is_canister() -> Result<(), String> {
     if ic_cdk::api::caller() == ic_cdk::api::id() {
         Ok(())
@@ -6,3 +12,10 @@ pub fn is_canister() -> Result<(), String> {
     }
 }
 
+/// The log timer, or an operator taking a snapshot by hand.
+pub fn is_canister_or_operator() -> Result<(), String> {
+    if ic_cdk::api::caller() == ic_cdk::api::id() {
+        return Ok(());
+    }
+    ROLES.with(|roles| roles.borrow().require(ic_cdk::api::caller(), Role::Operator))
+}
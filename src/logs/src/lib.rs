@@ -11,6 +11,9 @@ pub use queries::*;
 mod guard;
 pub use guard::{*};
 
+mod roles;
+pub use roles::*;
+
 pub mod utils;
 use candid::Nat;
 mod script;
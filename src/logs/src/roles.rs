@@ -0,0 +1,5 @@
+pub use access_control::{Role, RoleAction, RoleChange};
+
+use crate::{guard::*, ROLES};
+
+access_control::role_endpoints!(ROLES);
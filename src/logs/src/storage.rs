@@ -1,3 +1,4 @@
+use access_control::RoleStore;
 use candid::{CandidType, Nat, Principal};
 use candid::{Decode, Deserialize, Encode};
 use std::borrow::Cow;
@@ -13,6 +14,8 @@ use ic_stable_structures::{
 };
 pub const LOGS_MEM_ID: MemoryId = MemoryId::new(0);
 pub const CONFIGS_MEM_ID: MemoryId = MemoryId::new(1);
+pub const ROLES_MEM_ID: MemoryId = MemoryId::new(2);
+pub const ROLE_AUDIT_MEM_ID: MemoryId = MemoryId::new(3);
 
 thread_local! {
 
@@ -37,6 +40,13 @@ thread_local! {
             }
         ).unwrap()
     );
+
+    pub static ROLES: RefCell<RoleStore<Memory>> = RefCell::new(
+        RoleStore::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(ROLES_MEM_ID)),
+            MEMORY_MANAGER.with(|m| m.borrow().get(ROLE_AUDIT_MEM_ID)),
+        )
+    );
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
@@ -9,7 +9,7 @@ use crate::{
     Log, LOGS,
 };
 
-#[update(guard = "is_canister")]
+#[update(guard = "is_canister_or_operator")]
 pub async fn register_log() -> Result<String, String> {
     let primary_token_supply = get_primary_token_supply().await?;
     let secondary_token_supply = get_secondary_token_supply().await?;
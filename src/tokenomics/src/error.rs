@@ -55,6 +55,26 @@ pub enum ExecutionError {
     }, NoMorePrimaryCanbeMinted {
         reason: String,
     },
+    SlippageExceeded {
+        min_amount_out: u64,
+        amount_out: u64,
+    },
+    NothingToClaim {
+        reason: String,
+    },
+    Unauthorized {
+        reason: String,
+    },
+    AirdropUnavailable {
+        airdrop_id: u64,
+        reason: String,
+    },
+    InvalidMerkleProof {
+        airdrop_id: u64,
+    },
+    AlreadyClaimed {
+        airdrop_id: u64,
+    },
 }
 
 impl ExecutionError {
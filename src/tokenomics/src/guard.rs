@@ -1,4 +1,6 @@
-use crate::get_config;
+use crate::{get_config, ROLES};
+
+access_control::role_guards!(ROLES);
 
 pub fn is_allowed() -> Result<(), String> {
     let swap_canister_id = get_config().swap_canister_id;
@@ -11,6 +11,8 @@ mod update;
 pub use update::*;
 mod guard;
 pub use guard::*;
+mod roles;
+pub use roles::*;
 mod utils;
 use ic_cdk::api::call::CallResult;
 pub use utils::*;
@@ -1,5 +1,5 @@
 use crate::{
-    get_current_threshold_index_mem, get_principal, get_total_secondary_burned_mem, Configs, Logs, TokenLogs, TokenomicsSchedule, CONFIGS, LOGS, TOKENOMICS, TOKEN_LOGS
+    calculate_primary_mint, simulate_primary_mint, AirdropRecord, AIRDROPS, AIRDROP_CLAIMS, get_current_threshold_index_mem, get_vesting_schedules_of, TgeAllocationRecord, VestingSchedule, TGE_ALLOCATIONS, VESTING_SCHEDULES, ExecutionError, get_principal, get_total_secondary_burned_mem, Configs, Logs, TokenLogs, TokenomicsSchedule, CONFIGS, LOGS, TOKENOMICS, TOKEN_LOGS
 };
 use candid::{CandidType, Nat, Principal};
 use ic_cdk::{
@@ -7,6 +7,8 @@ use ic_cdk::{
     caller, query, update,
 };
 use serde::Deserialize;
+pub use tokenomics_core::BurnSimulation;
+use tokenomics_core::E8S;
 
 #[derive(CandidType, Deserialize, Clone)]
 pub struct TransactionRecord {
@@ -107,6 +109,54 @@ pub fn get_current_secondary_threshold() -> u64 {
     secondary_burn_thresholds[current_threshold as usize]
 }
 
+#[query]
+pub fn simulate_burn(amount: u64) -> Result<BurnSimulation, ExecutionError> {
+    simulate_primary_mint(amount, caller())
+}
+
+/// Vesting schedules of `beneficiary`, or all of them when unset.
+#[query]
+pub fn get_vesting_schedules(beneficiary: Option<Principal>) -> Vec<VestingSchedule> {
+    match beneficiary {
+        Some(beneficiary) => get_vesting_schedules_of(beneficiary),
+        None => VESTING_SCHEDULES.with(|schedules| {
+            schedules.borrow().iter().map(|(_, schedule)| schedule).collect()
+        }),
+    }
+}
+
+#[query]
+pub fn get_tge_allocations() -> Vec<TgeAllocationRecord> {
+    TGE_ALLOCATIONS.with(|records| records.borrow().iter().map(|(_, record)| record).collect())
+}
+
+#[query]
+pub fn get_airdrops() -> Vec<AirdropRecord> {
+    AIRDROPS.with(|airdrops| airdrops.borrow().iter().map(|(_, airdrop)| airdrop).collect())
+}
+
+/// What `claimant` claimed from an airdrop, if anything.
+#[query]
+pub fn get_airdrop_claim(airdrop_id: u64, claimant: Principal) -> Option<u64> {
+    AIRDROP_CLAIMS.with(|claims| claims.borrow().get(&(airdrop_id, claimant)))
+}
+
+/// Primary `claim_vested` would mint for `beneficiary` now, in e8s.
+#[query]
+pub fn get_claimable_vested(beneficiary: Principal) -> u64 {
+    let now = ic_cdk::api::time();
+    get_vesting_schedules_of(beneficiary)
+        .iter()
+        .fold(0u64, |total, schedule| total.saturating_add(schedule.claimable(now)))
+        .saturating_mul(E8S as u64)
+}
+
+/// Primary `mint_primary` would mint for `secondary_burn` now, before the supply cap.
+#[query]
+pub fn quote_mint_primary(secondary_burn: u64) -> Result<u64, ExecutionError> {
+    calculate_primary_mint(secondary_burn, caller()).map(|(primary, _)| primary)
+}
+
 #[query]
 pub fn get_max_stats() -> (u64, u64) {
     let secondary_burn_thresholds=get_tokenomics_schedule().secondary_burn_thresholds;
@@ -1,53 +1,99 @@
 use candid::{CandidType, Principal};
-use ic_cdk::init;
+use ic_cdk::{init, update};
 use serde::Deserialize;
 
-use crate::{Configs, TokenomicsSchedule, CONFIGS, TOKENOMICS};
+use access_control::validate_external_canister;
+use tokenomics_core::{
+    validate_allocations, validate_vesting, AllocationBucket, EmissionCurve,
+    TgeAllocation, E8S,
+};
 
-#[derive(CandidType, Deserialize, Clone, Default)]
-pub struct InitArgs {
-    pub primary_token_id: Option<Principal>,
-    pub secondary_token_id: Option<Principal>,
-    pub swap_canister_id: Option<Principal>,
-    pub frontend_canister_id:Option<Principal>,
-    pub max_primary_supply: u64,
-    pub initial_primary_mint: u64,
-    pub initial_secondary_burn: u64,
-    pub max_primary_phase:u64,
-    pub halving_step: u64,
-    pub initial_reward_per_burn_unit: u64,
-}
+use crate::{
+    get_config, has_role, AirdropRecord, Configs, Role, TgeAllocationRecord, VestingSchedule, AIRDROPS, CONFIGS,
+    TGE_ALLOCATIONS, TOKENOMICS, VESTING_SCHEDULES,
+};
+
+pub use tokenomics_core::TokenomicsInitArgs as InitArgs;
 
 fn initialize_globals(args: InitArgs) {
+    let schedule = args.schedule();
+    let configs = Configs {
+        primary_token_id: args.primary_token_id.unwrap_or(Principal::anonymous()),
+        secondary_token_id: args.secondary_token_id.unwrap_or(Principal::anonymous()),
+        swap_canister_id: args.swap_canister_id.unwrap_or(Principal::anonymous()),
+        frontend_canister_id:args.frontend_canister_id.unwrap_or(Principal::anonymous()),
+        max_primary_supply: args.max_primary_supply,
+        initial_primary_mint: args.initial_primary_mint,
+        initial_secondary_burn: args.initial_secondary_burn,
+        max_primary_phase:args.max_primary_phase,
+        halving_step: args.halving_step,
+        emission_curve: args.emission_curve,
+    };
+
     CONFIGS.with(|c| {
         let mut config = c.borrow_mut();
-        config
-            .set(Configs {
-                primary_token_id: args.primary_token_id.unwrap_or(Principal::anonymous()),
-                secondary_token_id: args.secondary_token_id.unwrap_or(Principal::anonymous()),
-                swap_canister_id: args.swap_canister_id.unwrap_or(Principal::anonymous()),
-                frontend_canister_id:args.frontend_canister_id.unwrap_or(Principal::anonymous()),
-                max_primary_supply: args.max_primary_supply,
-                initial_primary_mint: args.initial_primary_mint,
-                initial_secondary_burn: args.initial_secondary_burn,
-                max_primary_phase:args.max_primary_phase,
-                halving_step: args.halving_step,
-            })
-            .unwrap();
+        config.set(configs).unwrap();
     });
 
-    // Generate and store tokenomics schedule
-    let schedule = generate_tokenomics_schedule(
-        args.initial_secondary_burn,
-        args.initial_reward_per_burn_unit,
-        args.max_primary_supply,
-        args.halving_step,
-    );
-
     TOKENOMICS.with(|s| {
         let mut store = s.borrow_mut();
         store.set(schedule).expect("Failed to store schedule");
     });
+
+    let allocations = args.allocations.unwrap_or_default();
+    let vesting = args
+        .vesting
+        .unwrap_or_default()
+        .into_iter()
+        .chain(allocations.iter().filter_map(TgeAllocation::vesting_args));
+
+    let start_time = ic_cdk::api::time();
+    VESTING_SCHEDULES.with(|schedules| {
+        let mut schedules = schedules.borrow_mut();
+        for (id, vesting) in vesting.enumerate() {
+            schedules.insert(id as u64, VestingSchedule {
+                id: id as u64,
+                beneficiary: vesting.beneficiary,
+                amount: vesting.amount,
+                claimed: 0,
+                start_time,
+                cliff_secs: vesting.cliff_secs,
+                duration_secs: vesting.duration_secs,
+            });
+        }
+    });
+
+    let (airdrops, unvested): (Vec<_>, Vec<_>) = allocations
+        .into_iter()
+        .filter(|allocation| allocation.vesting.is_none())
+        .partition(|allocation| allocation.bucket == AllocationBucket::Airdrop);
+
+    AIRDROPS.with(|records| {
+        let mut records = records.borrow_mut();
+        for (id, allocation) in airdrops.into_iter().enumerate() {
+            records.insert(id as u64, AirdropRecord {
+                id: id as u64,
+                owner: allocation.recipient,
+                // Fits: `init` checked the whole TGE allocation converts to e8s.
+                amount: allocation.amount.saturating_mul(E8S as u64),
+                claimed: 0,
+                merkle_root: None,
+                expires_at: None,
+                reclaimed: false,
+            });
+        }
+    });
+
+    TGE_ALLOCATIONS.with(|records| {
+        let mut records = records.borrow_mut();
+        for (id, allocation) in unvested.into_iter().enumerate() {
+            records.insert(id as u64, TgeAllocationRecord {
+                id: id as u64,
+                allocation,
+                minted: false,
+            });
+        }
+    });
 }
 
 #[init]
@@ -80,6 +126,11 @@ fn init(args: Option<InitArgs>) {
                 );
             }
 
+            // Allocations are minted, and airdrops claimed, in e8s.
+            if init_args.initial_primary_mint.checked_mul(E8S as u64).is_none() {
+                ic_cdk::trap("Initialization failed: 'tge_allocation' is too large to mint in e8s.");
+            }
+
             if init_args.initial_secondary_burn == 0 {
                 ic_cdk::trap(
                     "Initialization failed: 'initial_secondary_burn' must be greater than 0.",
@@ -95,12 +146,29 @@ fn init(args: Option<InitArgs>) {
                     "Initialization failed: 'initial_reward_per_burn_unit' must be greater than 0.",
                 );
             }
-            if init_args.halving_step < 25 || init_args.halving_step > 90 {
-                ic_cdk::trap(
-                    "Initialization failed: 'halving_step' must be between 25 and 90.",
-                );
+            let emission_curve = init_args.emission_curve.clone().unwrap_or(
+                EmissionCurve::Halving {
+                    halving_step: init_args.halving_step,
+                },
+            );
+            if let Err(e) = emission_curve.validate() {
+                ic_cdk::trap(&format!("Initialization failed: {}", e));
             }
-            
+            if let Some(vesting) = &init_args.vesting {
+                if let Err(e) = validate_vesting(vesting, init_args.initial_primary_mint) {
+                    ic_cdk::trap(&format!("Initialization failed: {}", e));
+                }
+            }
+            if let Some(allocations) = &init_args.allocations {
+                if let Err(e) = validate_allocations(
+                    allocations,
+                    init_args.vesting.as_deref().unwrap_or_default(),
+                    init_args.initial_primary_mint,
+                ) {
+                    ic_cdk::trap(&format!("Initialization failed: {}", e));
+                }
+            }
+
 
             initialize_globals(init_args);
         }
@@ -110,92 +178,32 @@ fn init(args: Option<InitArgs>) {
     }
 }
 
-/// Generates the tokenomics schedule based on the provided parameters.
-///
-/// # Philosophy: "Ideal" Schedule vs. Real-World Execution
-///
-/// This function calculates an "ideal" or "perfect" tokenomics schedule that maps out the
-/// entire minting process based on the `max_primary_supply`. It deliberately does not
-/// account for the TGE (Token Generation Event) allocation, nor the single token used
-/// to initially seed the liquidity pool.
-///
-/// In practice, the TGE is minted separately, and the live minting process (driven by
-/// burning the secondary token) begins from that point. The overall `max_primary_supply`
-/// acts as a hard cap. If the TGE plus the scheduled minting reaches this cap, the process
-/// stops.
-///
-/// This design means the on-chain schedule represents a "perfect" reference model of minting
-/// rewards over time. The hard cap is the ultimate enforcer, which may render the final,
-/// less rewarding epochs of this pre-calculated schedule unreachable if the supply is exhausted
-/// sooner.
-fn generate_tokenomics_schedule(
-    initial_secondary_burn: u64,
-    initial_reward_per_burn_unit: u64,
-    max_primary_supply: u64,
-    halving_step: u64,
-) -> TokenomicsSchedule {
-    let mut secondary_thresholds = Vec::new();
-    let mut primary_rewards = Vec::new();
-
-    let mut current_burn = initial_secondary_burn as u128;
-    let mut last_burn = 0u128;
-    let mut total_minted = 0u128;
-    let mut primary_per_threshold = initial_reward_per_burn_unit as u128;
-
-    let mut one_reward_mode = false;
-    const E8S: u128 = 100_000_000;
-
-    while total_minted < max_primary_supply as u128 {
-        let in_slot_burn = current_burn - last_burn;
-
-        let reward_e8s = (primary_per_threshold * in_slot_burn * 10000);
-        let reward = reward_e8s / E8S;
-
-        // This block handles the "One Reward Mode", a special final phase of minting.
-        // It is triggered when the decaying reward per unit has reached its absolute minimum
-        // value (1 e8s of the primary token). At this point, the standard halving logic
-        // ceases.
-        if one_reward_mode {
-            // Remaining mint allowed
-            let remaining_mint = max_primary_supply as u128 - total_minted;
-
-            // This calculation determines the amount of secondary token that must be burned
-            // to mint the entire remaining primary supply.
-            let final_burn = remaining_mint * 10000;
-
-            // Final cumulative burn value
-            let final_threshold = last_burn + final_burn;
-
-            secondary_thresholds.push(final_threshold as u64);
-            primary_rewards.push(1);
-
-            total_minted += remaining_mint;
-            break;
-        }
-
-        if reward == 0 {
-            break;
-        }
-
-        secondary_thresholds.push(current_burn as u64);
-        primary_rewards.push(primary_per_threshold as u64);
-
-        total_minted += reward;
-        last_burn = current_burn;
-        current_burn = current_burn * 2;
-
-        if primary_per_threshold > 1 {
-            primary_per_threshold =
-                std::cmp::max(1, (primary_per_threshold * halving_step as u128) / 100);
-        }
+/// Canisters this one trusts that a controller can repoint, e.g. at PocketIC mocks.
+/// Unset fields are left as they are.
+#[derive(CandidType, Deserialize, Clone, Default)]
+pub struct ExternalCanisters {
+    pub swap_canister_id: Option<Principal>,
+    pub frontend_canister_id: Option<Principal>,
+}
 
-        if primary_per_threshold == 1 {
-            one_reward_mode = true;
-        }
+#[update]
+fn update_external_canisters(args: ExternalCanisters) -> Result<Configs, String> {
+    if !has_role(ic_cdk::caller(), Role::Controller) {
+        return Err("You are unauthorized to call this method.".to_string());
     }
 
-    TokenomicsSchedule {
-        secondary_burn_thresholds: secondary_thresholds,
-        primary_mint_per_threshold: primary_rewards,
+    let mut config = get_config();
+    if let Some(swap_canister_id) = args.swap_canister_id {
+        validate_external_canister("swap_canister_id", swap_canister_id)?;
+        config.swap_canister_id = swap_canister_id;
     }
-}
\ No newline at end of file
+    if let Some(frontend_canister_id) = args.frontend_canister_id {
+        validate_external_canister("frontend_canister_id", frontend_canister_id)?;
+        config.frontend_canister_id = frontend_canister_id;
+    }
+
+    CONFIGS.with(|c| c.borrow_mut().set(config.clone()))
+        .map_err(|e| format!("Failed to store configs: {:?}", e))?;
+    Ok(config)
+}
+
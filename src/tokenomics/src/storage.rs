@@ -1,3 +1,4 @@
+use access_control::RoleStore;
 use candid::{CandidType, Decode, Encode, Principal};
 use ic_stable_structures::storable::Bound;
 use ic_stable_structures::StableCell;
@@ -11,6 +12,8 @@ use std::borrow::Cow;
 use std::cell::RefCell;
 
 use crate::ExecutionError;
+pub use tokenomics_core::{EmissionCurve, TgeAllocation, TokenomicsSchedule, VestingArgs};
+use tokenomics_core::vested_amount;
 type Memory = VirtualMemory<DefaultMemoryImpl>;
 
 pub const TOTAL_SECONDARY_BURNED_MEM_ID: MemoryId = MemoryId::new(0);
@@ -19,6 +22,12 @@ pub const TOKEN_LOGS_MEM_ID: MemoryId = MemoryId::new(2);
 pub const LOGS_COUNTER_ID: MemoryId = MemoryId::new(3);
 pub const CONFIGS_MEM_ID: MemoryId = MemoryId::new(4);
 pub const TOKENOMICS_MEM_ID: MemoryId = MemoryId::new(5);
+pub const VESTING_SCHEDULES_MEM_ID: MemoryId = MemoryId::new(6);
+pub const TGE_ALLOCATIONS_MEM_ID: MemoryId = MemoryId::new(7);
+pub const AIRDROPS_MEM_ID: MemoryId = MemoryId::new(8);
+pub const AIRDROP_CLAIMS_MEM_ID: MemoryId = MemoryId::new(9);
+pub const ROLES_MEM_ID: MemoryId = MemoryId::new(10);
+pub const ROLE_AUDIT_MEM_ID: MemoryId = MemoryId::new(11);
 
 thread_local! {
     //Tokenomics
@@ -49,7 +58,8 @@ thread_local! {
                 max_primary_supply:0,
                  initial_primary_mint: 0,
                  initial_secondary_burn: 0,max_primary_phase:0,
-                 halving_step: 0
+                 halving_step: 0,
+                 emission_curve: None,
             }
         ).unwrap()
     );
@@ -60,6 +70,26 @@ thread_local! {
 
         ).unwrap()
     );
+    pub static VESTING_SCHEDULES: RefCell<StableBTreeMap<u64, VestingSchedule, Memory>> = RefCell::new(
+        StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(VESTING_SCHEDULES_MEM_ID)))
+    );
+    pub static TGE_ALLOCATIONS: RefCell<StableBTreeMap<u64, TgeAllocationRecord, Memory>> = RefCell::new(
+        StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(TGE_ALLOCATIONS_MEM_ID)))
+    );
+    pub static AIRDROPS: RefCell<StableBTreeMap<u64, AirdropRecord, Memory>> = RefCell::new(
+        StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(AIRDROPS_MEM_ID)))
+    );
+    // Amount claimed, by airdrop id and claimant.
+    pub static AIRDROP_CLAIMS: RefCell<StableBTreeMap<(u64, Principal), u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(AIRDROP_CLAIMS_MEM_ID)))
+    );
+
+    pub static ROLES: RefCell<RoleStore<Memory>> = RefCell::new(
+        RoleStore::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(ROLES_MEM_ID)),
+            MEMORY_MANAGER.with(|m| m.borrow().get(ROLE_AUDIT_MEM_ID)),
+        )
+    );
 
 
 
@@ -97,12 +127,6 @@ pub enum TokenLogType {
     Error { error: ExecutionError },
 }
 
-#[derive(CandidType, Deserialize, Clone, Debug, Default)]
-pub struct TokenomicsSchedule {
-    pub secondary_burn_thresholds: Vec<u64>,
-    pub primary_mint_per_threshold: Vec<u64>,
-}
-
 #[derive(CandidType, Deserialize, Clone, Debug)]
 pub struct Configs {
     pub primary_token_id: Principal,
@@ -113,10 +137,44 @@ pub struct Configs {
     pub initial_primary_mint: u64,
     pub initial_secondary_burn: u64,
     pub max_primary_phase:u64,
-    pub halving_step: u64
+    pub halving_step: u64,
+    // Halving by `halving_step` when unset.
+    pub emission_curve: Option<EmissionCurve>,
 }
 
-impl Storable for TokenLogs {
+impl Configs {
+    pub fn emission_curve(&self) -> EmissionCurve {
+        self.emission_curve.clone().unwrap_or(EmissionCurve::Halving {
+            halving_step: self.halving_step,
+        })
+    }
+}
+
+/// A TGE allocation minted to `beneficiary` as it vests, through `claim_vested`.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct VestingSchedule {
+    pub id: u64,
+    pub beneficiary: Principal,
+    // Whole tokens, like `claimed`; minted in e8s.
+    pub amount: u64,
+    pub claimed: u64,
+    // Launch time, in nanoseconds.
+    pub start_time: u64,
+    pub cliff_secs: u64,
+    pub duration_secs: u64,
+}
+
+impl VestingSchedule {
+    pub fn vested(&self, now: u64) -> u64 {
+        vested_amount(self.amount, self.start_time, self.cliff_secs, self.duration_secs, now)
+    }
+
+    pub fn claimable(&self, now: u64) -> u64 {
+        self.vested(now).saturating_sub(self.claimed)
+    }
+}
+
+impl Storable for VestingSchedule {
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
         Cow::Owned(Encode!(self).unwrap())
     }
@@ -127,7 +185,59 @@ impl Storable for TokenLogs {
 
     const BOUND: Bound = Bound::Unbounded;
 }
-impl Storable for Configs {
+
+/// A TGE allocation minted at launch by `mint_tge_allocations`.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TgeAllocationRecord {
+    pub id: u64,
+    pub allocation: TgeAllocation,
+    pub minted: bool,
+}
+
+impl Storable for TgeAllocationRecord {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// An `Airdrop` TGE allocation, held back until its `owner` commits a Merkle root of who
+/// gets what. Claimants are minted their share through `claim_airdrop` until
+/// `expires_at`; after that the owner can reclaim the rest. Unlike the allocation it
+/// comes from, `amount`, `claimed` and the amounts in the Merkle leaves are in e8s.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct AirdropRecord {
+    pub id: u64,
+    // Recipient of the allocation funding the airdrop.
+    pub owner: Principal,
+    pub amount: u64,
+    pub claimed: u64,
+    pub merkle_root: Option<Vec<u8>>,
+    // In nanoseconds. Set together with `merkle_root`.
+    pub expires_at: Option<u64>,
+    pub reclaimed: bool,
+}
+
+impl AirdropRecord {
+    /// Primary still owed by this airdrop.
+    pub fn unclaimed(&self) -> u64 {
+        if self.reclaimed {
+            return 0;
+        }
+        self.amount - self.claimed
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+}
+
+impl Storable for AirdropRecord {
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
         Cow::Owned(Encode!(self).unwrap())
     }
@@ -139,7 +249,18 @@ impl Storable for Configs {
     const BOUND: Bound = Bound::Unbounded;
 }
 
-impl Storable for TokenomicsSchedule {
+impl Storable for TokenLogs {
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+impl Storable for Configs {
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
         Cow::Owned(Encode!(self).unwrap())
     }
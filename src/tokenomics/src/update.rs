@@ -14,29 +14,46 @@ use crate::{
     add_to_total_secondary_burned,
     fetch_total_minted_primary,
     get_current_threshold_index,
+    get_airdrop,
     get_principal,
     get_total_secondary_burn,
+    get_reserved_primary,
+    get_unminted_tge_allocations,
+    get_vesting_schedule,
+    get_vesting_schedules_of,
+    primary_to_e8s,
+    save_airdrop,
+    save_tge_allocation,
+    save_vesting_schedule,
     update_to_current_threshold,
 };
 use candid::Principal;
 use ic_ledger_types::Subaccount;
 use icrc_ledger_types::icrc1::account::Account;
 use icrc_ledger_types::icrc1::transfer::{ BlockIndex, TransferArg, TransferError };
+use tokenomics_core::{
+    airdrop_leaf,
+    simulate_mint,
+    verify_merkle_proof,
+    BurnSimulation,
+    MerkleHash,
+    MintMathError,
+    MAX_MERKLE_PROOF_LEN,
+};
+
+/// Longest an airdrop can stay open for claims, one year.
+pub const MAX_AIRDROP_DURATION_SECS: u64 = 365 * 24 * 60 * 60;
 
 #[ic_cdk::update(guard = "is_allowed")]
 pub async fn mint_primary(
     secondary_burn: u64,
     actual_caller: Principal,
-    to_subaccount: Option<Subaccount>
+    to_subaccount: Option<Subaccount>,
+    min_primary_out: Option<u64>
 ) -> Result<String, ExecutionError> {
     let mut random_users: (Principal, Principal);
     let mut minted_primary: u64 = 0;
-    let mut phase_mint_primary: u64 = 0;
-    let mut total_burned_secondary: u64 = get_total_secondary_burn();
     let max_primary_supply=get_config().max_primary_supply;
-    let max_primary_phase=get_config().max_primary_phase;
-    let primary_mint_per_threshold=get_tokenomics_schedule().primary_mint_per_threshold;
-    let secondary_burn_thresholds=get_tokenomics_schedule().secondary_burn_thresholds;
 
 
     register_info_log(
@@ -44,270 +61,10 @@ pub async fn mint_primary(
         "mint_primary",
         &format!("Processing primary minting aginst {} secondary ", secondary_burn)
     );
-    if
-        total_burned_secondary.checked_add(secondary_burn).ok_or_else(|| {
-            ExecutionError::new_with_log(
-                actual_caller,
-                "mint_primary",
-                ExecutionError::AdditionOverflow {
-                    operation: DEFAULT_ADDITION_OVERFLOW_ERROR.to_string(),
-                    details: format!(
-                        "total_burned_secondary: {} with secondary_burn: {}",
-                        total_burned_secondary,
-                        secondary_burn
-                    ),
-                }
-            )
-        })? > secondary_burn_thresholds[secondary_burn_thresholds.len() - 1]
-    {
-        return Err(
-            ExecutionError::new_with_log(
-                actual_caller,
-                "mint_primary",
-                ExecutionError::MaxMintPrimaryReached {
-                    reason: "Max primary reached,minting stopped !".to_string(),
-                }
-            )
-        );
-    }
-
-    let mut current_threshold_index: u32 = get_current_threshold_index();
-    let tentative_total: u64 = total_burned_secondary.checked_add(secondary_burn).ok_or_else(|| {
-        ExecutionError::new_with_log(actual_caller, "mint_primary", ExecutionError::AdditionOverflow {
-            operation: DEFAULT_ADDITION_OVERFLOW_ERROR.to_string(),
-            details: format!(
-                "total_burned_secondary: {} with secondary_burn: {}",
-                total_burned_secondary,
-                secondary_burn
-            ),
-        })
-    })?;
-
-    if tentative_total > secondary_burn_thresholds[current_threshold_index as usize] {
-        let mut secondary_processed: u64 = 0;
-
-        while tentative_total > secondary_burn_thresholds[current_threshold_index as usize] {
-            let secondary_mint_primary_with_current_threshold: u64 = if
-                total_burned_secondary < secondary_burn_thresholds[current_threshold_index as usize]
-            {
-                secondary_burn_thresholds[current_threshold_index as usize] - total_burned_secondary
-            } else {
-                secondary_burn.checked_sub(secondary_processed).ok_or_else(|| {
-                    ExecutionError::new_with_log(
-                        actual_caller,
-                        "mint_primary",
-                        ExecutionError::Underflow {
-                            operation: DEFAULT_UNDERFLOW_ERROR.to_string(),
-                            details: format!(
-                                "secondary_burn: {} with secondary_processed: {}",
-                                secondary_burn,
-                                secondary_processed
-                            ),
-                        }
-                    )
-                })?
-            };
-
-            let mut slot_mint = primary_mint_per_threshold[current_threshold_index as usize].checked_mul(
-                secondary_mint_primary_with_current_threshold
-            ).ok_or_else(||
-                ExecutionError::new_with_log(
-                    actual_caller,
-                    "mint_primary",
-                    ExecutionError::MultiplicationOverflow {
-                        operation: DEFAULT_MULTIPLICATION_OVERFLOW_ERROR.to_string(),
-                        details: format!(
-                            "primary_mint_per_threshold[current_threshold_index]: {} with secondary_mint_primary_with_current_threshold: {}",
-                            primary_mint_per_threshold[current_threshold_index as usize],
-                            secondary_mint_primary_with_current_threshold
-                        ),
-                    }
-                )
-            )?;
-
-            slot_mint = slot_mint.checked_mul(10000).ok_or_else(|| {
-                ExecutionError::new_with_log(
-                    actual_caller,
-                    "mint_primary",
-                    ExecutionError::MultiplicationOverflow {
-                        operation: DEFAULT_MULTIPLICATION_OVERFLOW_ERROR.to_string(),
-                        details: format!("slot_mint: {} with {}", slot_mint, 10000),
-                    }
-                )
-            })?;
-
-            phase_mint_primary = phase_mint_primary.checked_add(slot_mint).ok_or_else(|| {
-                ExecutionError::new_with_log(
-                    actual_caller,
-                    "mint_primary",
-                    ExecutionError::AdditionOverflow {
-                        operation: DEFAULT_ADDITION_OVERFLOW_ERROR.to_string(),
-                        details: format!(
-                            "slot_mint: {} with phase_mint_primary: {}",
-                            slot_mint,
-                            phase_mint_primary
-                        ),
-                    }
-                )
-            })?;
-            secondary_processed = secondary_processed
-                .checked_add(secondary_mint_primary_with_current_threshold)
-                .ok_or_else(|| {
-                    ExecutionError::new_with_log(
-                        actual_caller,
-                        "mint_primary",
-                        ExecutionError::AdditionOverflow {
-                            operation: DEFAULT_ADDITION_OVERFLOW_ERROR.to_string(),
-                            details: format!(
-                                "secondary_processed: {} with secondary_mint_primary_with_current_threshold: {}",
-                                secondary_processed,
-                                secondary_mint_primary_with_current_threshold
-                            ),
-                        }
-                    )
-                })?;
-            total_burned_secondary = total_burned_secondary
-                .checked_add(secondary_mint_primary_with_current_threshold)
-                .ok_or_else(||
-                    ExecutionError::new_with_log(
-                        actual_caller,
-                        "mint_primary",
-                        ExecutionError::AdditionOverflow {
-                            operation: DEFAULT_ADDITION_OVERFLOW_ERROR.to_string(),
-                            details: format!(
-                                "total_burned_secondary: {} with secondary_mint_primary_with_current_threshold: {}",
-                                total_burned_secondary,
-                                secondary_mint_primary_with_current_threshold
-                            ),
-                        }
-                    )
-                )?;
-            current_threshold_index += 1;
-            if current_threshold_index > (secondary_burn_thresholds.len() as u32) - 1 {
-                current_threshold_index = (secondary_burn_thresholds.len() as u32) - 1;
-            }
-        }
-
-        if secondary_burn > secondary_processed {
-            let secondary_mint_primary_with_current_threshold: u64 = secondary_burn
-                .checked_sub(secondary_processed)
-                .ok_or_else(|| {
-                    ExecutionError::new_with_log(
-                        actual_caller,
-                        "mint_primary",
-                        ExecutionError::Underflow {
-                            operation: DEFAULT_UNDERFLOW_ERROR.to_string(),
-                            details: format!(
-                                "secondary_burn: {} with secondary_processed: {}",
-                                secondary_burn,
-                                secondary_processed
-                            ),
-                        }
-                    )
-                })?;
-
-            let mut slot_mint = primary_mint_per_threshold[current_threshold_index as usize].checked_mul(
-                secondary_mint_primary_with_current_threshold
-            ).ok_or_else(||
-                ExecutionError::new_with_log(
-                    actual_caller,
-                    "mint_primary",
-                    ExecutionError::MultiplicationOverflow {
-                        operation: DEFAULT_MULTIPLICATION_OVERFLOW_ERROR.to_string(),
-                        details: format!(
-                            "primary_mint_per_threshold[current_threshold_index]: {} with secondary_mint_primary_with_current_threshold:{}",
-                            primary_mint_per_threshold[current_threshold_index as usize],
-                            secondary_mint_primary_with_current_threshold
-                        ),
-                    }
-                )
-            )?;
-            slot_mint = slot_mint.checked_mul(10000).ok_or_else(|| {
-                ExecutionError::new_with_log(
-                    actual_caller,
-                    "mint_primary",
-                    ExecutionError::MultiplicationOverflow {
-                        operation: DEFAULT_MULTIPLICATION_OVERFLOW_ERROR.to_string(),
-                        details: format!("slot_mint: {} with {}", slot_mint, 10000),
-                    }
-                )
-            })?;
-
-            phase_mint_primary = phase_mint_primary.checked_add(slot_mint).ok_or_else(|| {
-                ExecutionError::new_with_log(
-                    actual_caller,
-                    "mint_primary",
-                    ExecutionError::AdditionOverflow {
-                        operation: DEFAULT_ADDITION_OVERFLOW_ERROR.to_string(),
-                        details: format!(
-                            "phase_mint_primary: {} with slot_mint: {}",
-                            phase_mint_primary,
-                            slot_mint
-                        ),
-                    }
-                )
-            })?;
-
-            secondary_processed.checked_add(secondary_mint_primary_with_current_threshold).ok_or_else(|| {
-                ExecutionError::new_with_log(
-                    actual_caller,
-                    "mint_primary",
-                    ExecutionError::AdditionOverflow {
-                        operation: DEFAULT_ADDITION_OVERFLOW_ERROR.to_string(),
-                        details: format!(
-                            "secondary_processed: {} with secondary_mint_primary_with_current_threshold: {}",
-                            secondary_processed,
-                            secondary_mint_primary_with_current_threshold
-                        ),
-                    }
-                )
-            })?;
-        }
-    } else {
-        phase_mint_primary = primary_mint_per_threshold[current_threshold_index as usize].checked_mul(
-            secondary_burn
-        ).ok_or_else(|| {
-            ExecutionError::new_with_log(
-                actual_caller,
-                "mint_primary",
-                ExecutionError::MultiplicationOverflow {
-                    operation: DEFAULT_MULTIPLICATION_OVERFLOW_ERROR.to_string(),
-                    details: format!(
-                        "phase_mint_primary: {} with secondary_burn:{}",
-                        phase_mint_primary,
-                        secondary_burn
-                    ),
-                }
-            )
-        })?;
-        phase_mint_primary = phase_mint_primary.checked_mul(10000).ok_or_else(|| {
-            ExecutionError::new_with_log(
-                actual_caller,
-                "mint_primary",
-                ExecutionError::MultiplicationOverflow {
-                    operation: DEFAULT_MULTIPLICATION_OVERFLOW_ERROR.to_string(),
-                    details: format!("phase_mint_primary: {} with {}", phase_mint_primary, 10000),
-                }
-            )
-        })?;
-    }
-
-    // Check for maximum primary per transaction (50 primary = 500_000 after multiplication by 10000)
-    let max_phase_in_e8s=max_primary_phase*100_000_000;
-    if phase_mint_primary >  max_phase_in_e8s{
-        return Err(
-            ExecutionError::new_with_log(
-                actual_caller,
-                "mint_primary",
-                ExecutionError::MaxPrimaryPerTrnxReached {
-                    reason: format!(
-                        "This would mint {} primary which exceeds the maximum of 50 primary per transaction",
-                        (phase_mint_primary as f64) / 10000.0
-                    ),
-                }
-            )
-        );
-    }
+    let (phase_mint_primary, current_threshold_index) = calculate_primary_mint(
+        secondary_burn,
+        actual_caller
+    )?;
 
     let mut total_primary_minted = 0;
 
@@ -340,6 +97,9 @@ pub async fn mint_primary(
             ),
         })
     })?;
+    // Unclaimed vesting and unminted TGE allocations are not on the ledger yet but
+    // already count against the cap.
+    let remaining_primary = remaining_primary.saturating_sub(get_reserved_primary());
     let primary_to_mint = phase_mint_primary
         .min(remaining_primary);
 
@@ -354,6 +114,20 @@ pub async fn mint_primary(
             )
         );
     }
+    if let Some(min_primary_out) = min_primary_out {
+        if primary_to_mint < min_primary_out {
+            return Err(
+                ExecutionError::new_with_log(
+                    actual_caller,
+                    "mint_primary",
+                    ExecutionError::SlippageExceeded {
+                        min_amount_out: min_primary_out,
+                        amount_out: primary_to_mint,
+                    }
+                )
+            );
+        }
+    }
 
     let primary_per_recipient = primary_to_mint;
     match
@@ -405,6 +179,410 @@ pub async fn mint_primary(
     Ok("Minted primary ".to_string() + &minted_primary.to_string())
 }
 
+/// Primary minted for `secondary_burn` at the current threshold, before the supply cap,
+/// and the threshold index reached afterwards. Does not change state.
+pub(crate) fn calculate_primary_mint(
+    secondary_burn: u64,
+    actual_caller: Principal
+) -> Result<(u64, u32), ExecutionError> {
+    let simulation = simulate_primary_mint(secondary_burn, actual_caller)?;
+    if simulation.max_mint_primary_reached {
+        return Err(
+            ExecutionError::new_with_log(
+                actual_caller,
+                "mint_primary",
+                ExecutionError::MaxMintPrimaryReached {
+                    reason: "Max primary reached,minting stopped !".to_string(),
+                }
+            )
+        );
+    }
+
+    // Check for maximum primary per transaction (50 primary = 500_000 after multiplication by 10000)
+    if simulation.max_primary_phase_exceeded {
+        return Err(
+            ExecutionError::new_with_log(
+                actual_caller,
+                "mint_primary",
+                ExecutionError::MaxPrimaryPerTrnxReached {
+                    reason: format!(
+                        "This would mint {} primary which exceeds the maximum of 50 primary per transaction",
+                        (simulation.primary_minted as f64) / 10000.0
+                    ),
+                }
+            )
+        );
+    }
+
+    Ok((simulation.primary_minted, simulation.threshold_index_after))
+}
+
+/// Walks the burn thresholds `secondary_burn` would cross, recording what each one mints.
+pub(crate) fn simulate_primary_mint(
+    secondary_burn: u64,
+    actual_caller: Principal
+) -> Result<BurnSimulation, ExecutionError> {
+    simulate_mint(
+        &get_tokenomics_schedule(),
+        get_total_secondary_burn(),
+        get_current_threshold_index(),
+        secondary_burn,
+        get_config().max_primary_phase
+    ).map_err(|e| {
+        let error = match e {
+            MintMathError::AdditionOverflow(details) =>
+                ExecutionError::AdditionOverflow {
+                    operation: DEFAULT_ADDITION_OVERFLOW_ERROR.to_string(),
+                    details,
+                },
+            MintMathError::MultiplicationOverflow(details) =>
+                ExecutionError::MultiplicationOverflow {
+                    operation: DEFAULT_MULTIPLICATION_OVERFLOW_ERROR.to_string(),
+                    details,
+                },
+            MintMathError::Underflow(details) =>
+                ExecutionError::Underflow {
+                    operation: DEFAULT_UNDERFLOW_ERROR.to_string(),
+                    details,
+                },
+        };
+        ExecutionError::new_with_log(actual_caller, "mint_primary", error)
+    })
+}
+
+/// Mints everything that has vested for the caller since their last claim, and returns
+/// the e8s minted.
+#[ic_cdk::update]
+pub async fn claim_vested(to_subaccount: Option<Subaccount>) -> Result<u64, ExecutionError> {
+    let caller = ic_cdk::caller();
+    let now = ic_cdk::api::time();
+
+    // Claims are recorded before minting so a concurrent call cannot claim them again.
+    let mut claims: Vec<(VestingSchedule, u64)> = Vec::new();
+    let mut total_claimed: u64 = 0;
+    for mut schedule in get_vesting_schedules_of(caller) {
+        let claimable = schedule.claimable(now);
+        if claimable == 0 {
+            continue;
+        }
+        total_claimed = total_claimed.checked_add(claimable).ok_or_else(|| {
+            ExecutionError::new_with_log(caller, "claim_vested", ExecutionError::AdditionOverflow {
+                operation: DEFAULT_ADDITION_OVERFLOW_ERROR.to_string(),
+                details: format!(
+                    "total_claimed: {} with claimable: {}",
+                    total_claimed,
+                    claimable
+                ),
+            })
+        })?;
+        schedule.claimed += claimable;
+        claims.push((schedule, claimable));
+    }
+    if total_claimed == 0 {
+        return Err(
+            ExecutionError::new_with_log(caller, "claim_vested", ExecutionError::NothingToClaim {
+                reason: "Nothing has vested since the last claim".to_string(),
+            })
+        );
+    }
+    // Schedules vest whole tokens; the ledger mints e8s.
+    let total_claimed_e8s = primary_to_e8s(total_claimed).ok_or_else(|| {
+        ExecutionError::new_with_log(caller, "claim_vested", ExecutionError::MultiplicationOverflow {
+            operation: DEFAULT_MULTIPLICATION_OVERFLOW_ERROR.to_string(),
+            details: format!("total_claimed: {} in e8s", total_claimed),
+        })
+    })?;
+    for (schedule, _) in &claims {
+        save_vesting_schedule(schedule);
+    }
+
+    match mint_primary_internal(total_claimed_e8s, caller, to_subaccount.map(|s| s.0)).await {
+        Ok(_) => {
+            register_info_log(
+                caller,
+                "claim_vested",
+                &format!("Minted {}(e8s) vested primary to {}", total_claimed_e8s, caller)
+            );
+            Ok(total_claimed_e8s)
+        }
+        Err(e) => {
+            // Hand the claims back so they can be retried. Re-read, other claims may have
+            // landed while the mint was in flight.
+            for (schedule, claimable) in claims {
+                if let Some(mut schedule) = get_vesting_schedule(schedule.id) {
+                    schedule.claimed -= claimable;
+                    save_vesting_schedule(&schedule);
+                }
+            }
+            Err(
+                ExecutionError::new_with_log(caller, "claim_vested", ExecutionError::MintFailed {
+                    token: "primary".to_string(),
+                    amount: total_claimed_e8s,
+                    reason: "Vested primary could not be minted".to_string(),
+                    details: e,
+                })
+            )
+        }
+    }
+}
+
+/// Mints the TGE allocations that neither vest nor fund an airdrop, and returns the e8s
+/// minted; allocations themselves are in whole tokens. Safe to call again: allocations
+/// already minted are skipped, so a launch can retry it until everything is out.
+#[ic_cdk::update]
+pub async fn mint_tge_allocations() -> Result<u64, ExecutionError> {
+    let caller = ic_cdk::caller();
+    let mut total_minted: u64 = 0;
+
+    for mut record in get_unminted_tge_allocations() {
+        let allocation = record.allocation.clone();
+        let amount_e8s = primary_to_e8s(allocation.amount).ok_or_else(|| {
+            ExecutionError::new_with_log(caller, "mint_tge_allocations", ExecutionError::MultiplicationOverflow {
+                operation: DEFAULT_MULTIPLICATION_OVERFLOW_ERROR.to_string(),
+                details: format!("allocation {} amount: {} in e8s", record.id, allocation.amount),
+            })
+        })?;
+
+        // Marked first so a concurrent call does not mint it too.
+        record.minted = true;
+        save_tge_allocation(&record);
+
+        if let Err(e) = mint_primary_internal(amount_e8s, allocation.recipient, None).await {
+            record.minted = false;
+            save_tge_allocation(&record);
+            return Err(
+                ExecutionError::new_with_log(caller, "mint_tge_allocations", ExecutionError::MintFailed {
+                    token: "primary".to_string(),
+                    amount: amount_e8s,
+                    reason: format!("TGE allocation {} could not be minted", record.id),
+                    details: e,
+                })
+            );
+        }
+        register_info_log(
+            caller,
+            "mint_tge_allocations",
+            &format!(
+                "Minted {}(e8s) {:?} allocation to {}",
+                amount_e8s,
+                allocation.bucket,
+                allocation.recipient
+            )
+        );
+        total_minted = total_minted.saturating_add(amount_e8s);
+    }
+    Ok(total_minted)
+}
+
+/// Opens an airdrop for claims against `merkle_root`, built over `airdrop_leaf`s, until
+/// `expires_at` (in nanoseconds). Only its owner can commit, and only once.
+#[ic_cdk::update]
+pub fn commit_airdrop(
+    airdrop_id: u64,
+    merkle_root: Vec<u8>,
+    expires_at: u64
+) -> Result<AirdropRecord, ExecutionError> {
+    let caller = ic_cdk::caller();
+    let now = ic_cdk::api::time();
+    let mut airdrop = airdrop_of_owner(airdrop_id, caller, "commit_airdrop")?;
+
+    let reason = if airdrop.merkle_root.is_some() {
+        Some("The Merkle root is already committed".to_string())
+    } else if airdrop.reclaimed {
+        Some("The airdrop was reclaimed".to_string())
+    } else if merkle_root.len() != 32 {
+        Some("The Merkle root must be 32 bytes".to_string())
+    } else if expires_at <= now || expires_at - now > MAX_AIRDROP_DURATION_SECS * 1_000_000_000 {
+        Some(format!("Expiry must be in the next {} seconds", MAX_AIRDROP_DURATION_SECS))
+    } else {
+        None
+    };
+    if let Some(reason) = reason {
+        return Err(
+            ExecutionError::new_with_log(caller, "commit_airdrop", ExecutionError::AirdropUnavailable {
+                airdrop_id,
+                reason,
+            })
+        );
+    }
+
+    airdrop.merkle_root = Some(merkle_root);
+    airdrop.expires_at = Some(expires_at);
+    save_airdrop(&airdrop);
+    register_info_log(
+        caller,
+        "commit_airdrop",
+        &format!("Committed airdrop {} of {}(e8s) until {}", airdrop_id, airdrop.amount, expires_at)
+    );
+    Ok(airdrop)
+}
+
+/// Mints the caller's share of an airdrop. `amount`, in e8s, and `proof` come from the
+/// list the owner built the Merkle root from; each principal can claim once.
+#[ic_cdk::update]
+pub async fn claim_airdrop(
+    airdrop_id: u64,
+    amount: u64,
+    proof: Vec<Vec<u8>>,
+    to_subaccount: Option<Subaccount>
+) -> Result<u64, ExecutionError> {
+    let caller = ic_cdk::caller();
+    let now = ic_cdk::api::time();
+    let unavailable = |reason: &str| {
+        ExecutionError::new_with_log(caller, "claim_airdrop", ExecutionError::AirdropUnavailable {
+            airdrop_id,
+            reason: reason.to_string(),
+        })
+    };
+
+    let mut airdrop = get_airdrop(airdrop_id).ok_or_else(|| unavailable("Airdrop not found"))?;
+    let root: MerkleHash = airdrop.merkle_root
+        .as_deref()
+        .and_then(|root| root.try_into().ok())
+        .ok_or_else(|| unavailable("The airdrop is not open yet"))?;
+    if airdrop.reclaimed || airdrop.is_expired(now) {
+        return Err(unavailable("The airdrop has expired"));
+    }
+    if AIRDROP_CLAIMS.with(|claims| claims.borrow().contains_key(&(airdrop_id, caller))) {
+        return Err(
+            ExecutionError::new_with_log(caller, "claim_airdrop", ExecutionError::AlreadyClaimed {
+                airdrop_id,
+            })
+        );
+    }
+
+    let proof: Option<Vec<MerkleHash>> = proof
+        .iter()
+        .map(|node| node.as_slice().try_into().ok())
+        .collect();
+    let valid = match proof {
+        Some(proof) =>
+            amount > 0 &&
+                proof.len() <= MAX_MERKLE_PROOF_LEN &&
+                verify_merkle_proof(&root, airdrop_leaf(caller, amount), &proof),
+        None => false,
+    };
+    if !valid {
+        return Err(
+            ExecutionError::new_with_log(caller, "claim_airdrop", ExecutionError::InvalidMerkleProof {
+                airdrop_id,
+            })
+        );
+    }
+    // A root promising more than the allocation cannot overdraw it.
+    if amount > airdrop.unclaimed() {
+        return Err(unavailable("The airdrop does not have enough left for this claim"));
+    }
+
+    // Recorded before minting so the claim cannot be made twice.
+    airdrop.claimed += amount;
+    save_airdrop(&airdrop);
+    AIRDROP_CLAIMS.with(|claims| claims.borrow_mut().insert((airdrop_id, caller), amount));
+
+    match mint_primary_internal(amount, caller, to_subaccount.map(|s| s.0)).await {
+        Ok(_) => {
+            register_info_log(
+                caller,
+                "claim_airdrop",
+                &format!("Minted {}(e8s) from airdrop {} to {}", amount, airdrop_id, caller)
+            );
+            Ok(amount)
+        }
+        Err(e) => {
+            // Re-read, other claims may have landed while the mint was in flight.
+            if let Some(mut airdrop) = get_airdrop(airdrop_id) {
+                airdrop.claimed -= amount;
+                save_airdrop(&airdrop);
+            }
+            AIRDROP_CLAIMS.with(|claims| claims.borrow_mut().remove(&(airdrop_id, caller)));
+            Err(
+                ExecutionError::new_with_log(caller, "claim_airdrop", ExecutionError::MintFailed {
+                    token: "primary".to_string(),
+                    amount,
+                    reason: "Airdrop claim could not be minted".to_string(),
+                    details: e,
+                })
+            )
+        }
+    }
+}
+
+/// Mints whatever is left of an airdrop back to its owner, once it has expired or if
+/// it was never committed.
+#[ic_cdk::update]
+pub async fn reclaim_airdrop(
+    airdrop_id: u64,
+    to_subaccount: Option<Subaccount>
+) -> Result<u64, ExecutionError> {
+    let caller = ic_cdk::caller();
+    let now = ic_cdk::api::time();
+    let mut airdrop = airdrop_of_owner(airdrop_id, caller, "reclaim_airdrop")?;
+
+    if airdrop.merkle_root.is_some() && !airdrop.is_expired(now) {
+        return Err(
+            ExecutionError::new_with_log(caller, "reclaim_airdrop", ExecutionError::AirdropUnavailable {
+                airdrop_id,
+                reason: "The airdrop is still open for claims".to_string(),
+            })
+        );
+    }
+    let unclaimed = airdrop.unclaimed();
+    if unclaimed == 0 {
+        return Err(
+            ExecutionError::new_with_log(caller, "reclaim_airdrop", ExecutionError::NothingToClaim {
+                reason: "Nothing is left to reclaim".to_string(),
+            })
+        );
+    }
+
+    airdrop.reclaimed = true;
+    save_airdrop(&airdrop);
+
+    match mint_primary_internal(unclaimed, caller, to_subaccount.map(|s| s.0)).await {
+        Ok(_) => {
+            register_info_log(
+                caller,
+                "reclaim_airdrop",
+                &format!("Reclaimed {}(e8s) from airdrop {}", unclaimed, airdrop_id)
+            );
+            Ok(unclaimed)
+        }
+        Err(e) => {
+            airdrop.reclaimed = false;
+            save_airdrop(&airdrop);
+            Err(
+                ExecutionError::new_with_log(caller, "reclaim_airdrop", ExecutionError::MintFailed {
+                    token: "primary".to_string(),
+                    amount: unclaimed,
+                    reason: "Unclaimed airdrop could not be minted".to_string(),
+                    details: e,
+                })
+            )
+        }
+    }
+}
+
+fn airdrop_of_owner(
+    airdrop_id: u64,
+    caller: Principal,
+    function: &str
+) -> Result<AirdropRecord, ExecutionError> {
+    let airdrop = get_airdrop(airdrop_id).ok_or_else(|| {
+        ExecutionError::new_with_log(caller, function, ExecutionError::AirdropUnavailable {
+            airdrop_id,
+            reason: "Airdrop not found".to_string(),
+        })
+    })?;
+    if airdrop.owner != caller {
+        return Err(
+            ExecutionError::new_with_log(caller, function, ExecutionError::Unauthorized {
+                reason: "Only the airdrop owner can do this".to_string(),
+            })
+        );
+    }
+    Ok(airdrop)
+}
+
 async fn mint_primary_internal(
     minted_primary: u64,
     destination: Principal,
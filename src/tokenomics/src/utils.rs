@@ -16,7 +16,14 @@ use crate::{
     LOGS,
     TOKEN_LOGS,
     TOKEN_LOG_COUNTER,
+    AirdropRecord,
+    AIRDROPS,
+    TgeAllocationRecord,
+    TGE_ALLOCATIONS,
+    VESTING_SCHEDULES,
+    VestingSchedule,
 };
+use tokenomics_core::E8S;
 
 pub const TOKEN_LOGS_LIMIT: u64 = 100_000;
 
@@ -36,6 +43,83 @@ pub(crate) fn add_to_total_secondary_burned(amount: u64) -> Result<(), Execution
     return Ok(());
 }
 
+/// `amount` whole primary tokens in e8s, the unit the ledger mints in. Launch amounts
+/// such as TGE allocations are given in whole tokens.
+pub(crate) fn primary_to_e8s(amount: u64) -> Option<u64> {
+    amount.checked_mul(E8S as u64)
+}
+
+pub(crate) fn get_vesting_schedules_of(beneficiary: Principal) -> Vec<VestingSchedule> {
+    VESTING_SCHEDULES.with(|schedules| {
+        schedules
+            .borrow()
+            .iter()
+            .filter(|(_, schedule)| schedule.beneficiary == beneficiary)
+            .map(|(_, schedule)| schedule)
+            .collect()
+    })
+}
+
+pub(crate) fn get_vesting_schedule(id: u64) -> Option<VestingSchedule> {
+    VESTING_SCHEDULES.with(|schedules| schedules.borrow().get(&id))
+}
+
+pub(crate) fn save_vesting_schedule(schedule: &VestingSchedule) {
+    VESTING_SCHEDULES.with(|schedules| {
+        schedules.borrow_mut().insert(schedule.id, schedule.clone());
+    });
+}
+
+pub(crate) fn get_unminted_tge_allocations() -> Vec<TgeAllocationRecord> {
+    TGE_ALLOCATIONS.with(|records| {
+        records
+            .borrow()
+            .iter()
+            .filter(|(_, record)| !record.minted)
+            .map(|(_, record)| record)
+            .collect()
+    })
+}
+
+pub(crate) fn save_tge_allocation(record: &TgeAllocationRecord) {
+    TGE_ALLOCATIONS.with(|records| {
+        records.borrow_mut().insert(record.id, record.clone());
+    });
+}
+
+pub(crate) fn get_airdrop(id: u64) -> Option<AirdropRecord> {
+    AIRDROPS.with(|airdrops| airdrops.borrow().get(&id))
+}
+
+pub(crate) fn save_airdrop(airdrop: &AirdropRecord) {
+    AIRDROPS.with(|airdrops| {
+        airdrops.borrow_mut().insert(airdrop.id, airdrop.clone());
+    });
+}
+
+/// Primary still owed to vesting beneficiaries, TGE recipients and airdrop claimants, in
+/// e8s. Burn minting has to leave room for it under `max_primary_supply`.
+pub(crate) fn get_reserved_primary() -> u64 {
+    let unclaimed_vesting = VESTING_SCHEDULES.with(|schedules| {
+        schedules
+            .borrow()
+            .iter()
+            .map(|(_, schedule)| (schedule.amount - schedule.claimed).saturating_mul(E8S as u64))
+            .fold(0u64, |total, unclaimed| total.saturating_add(unclaimed))
+    });
+    let unclaimed_airdrops = AIRDROPS.with(|airdrops| {
+        airdrops
+            .borrow()
+            .iter()
+            .fold(unclaimed_vesting, |total, (_, airdrop)| total.saturating_add(airdrop.unclaimed()))
+    });
+    get_unminted_tge_allocations()
+        .iter()
+        .fold(unclaimed_airdrops, |total, record| {
+            total.saturating_add(record.allocation.amount.saturating_mul(E8S as u64))
+        })
+}
+
 pub(crate) fn update_to_current_threshold(index: u32) {
     let current_index = get_current_threshold_index();
     if current_index != index {
@@ -0,0 +1,109 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+use crate::VestingArgs;
+
+/// Most TGE allocations one launch can make.
+pub const MAX_TGE_ALLOCATIONS: usize = 32;
+
+/// What a TGE allocation is for. `Airdrop` allocations fund a Merkle airdrop their
+/// recipient opens on tokenomics; the others are only labels for reporting.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AllocationBucket {
+    Creator,
+    Team,
+    Treasury,
+    Airdrop,
+    Community,
+}
+
+/// Release terms for a vested allocation, counted from the launch.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VestingTerms {
+    pub cliff_secs: u64,
+    pub duration_secs: u64,
+}
+
+/// Part of the TGE allocation going to `recipient`: minted at launch, or held back by
+/// tokenomics and released along `vesting`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TgeAllocation {
+    pub bucket: AllocationBucket,
+    pub recipient: Principal,
+    pub amount: u64,
+    pub vesting: Option<VestingTerms>,
+}
+
+impl TgeAllocation {
+    /// The vesting schedule tokenomics keeps for this allocation, if it vests.
+    pub fn vesting_args(&self) -> Option<VestingArgs> {
+        self.vesting.map(|terms| VestingArgs {
+            beneficiary: self.recipient,
+            amount: self.amount,
+            cliff_secs: terms.cliff_secs,
+            duration_secs: terms.duration_secs,
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.recipient == Principal::anonymous() {
+            return Err("Allocation 'recipient' cannot be anonymous.".to_string());
+        }
+        if self.amount == 0 {
+            return Err("Allocation 'amount' must be greater than 0.".to_string());
+        }
+        // Airdrops are released through their own claims.
+        if self.bucket == AllocationBucket::Airdrop && self.vesting.is_some() {
+            return Err("Airdrop allocations cannot vest.".to_string());
+        }
+        match self.vesting_args() {
+            Some(vesting) => vesting.validate(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Checks every allocation and that, with the standalone `vesting` schedules, they fit
+/// in `tge_allocation`.
+pub fn validate_allocations(
+    allocations: &[TgeAllocation],
+    vesting: &[VestingArgs],
+    tge_allocation: u64,
+) -> Result<(), String> {
+    if allocations.len() > MAX_TGE_ALLOCATIONS {
+        return Err(format!(
+            "At most {} TGE allocations are allowed.",
+            MAX_TGE_ALLOCATIONS
+        ));
+    }
+    let mut total: u64 = 0;
+    for allocation in allocations {
+        allocation.validate()?;
+        total = total
+            .checked_add(allocation.amount)
+            .ok_or("Allocation amounts overflow.".to_string())?;
+    }
+    for schedule in vesting {
+        total = total
+            .checked_add(schedule.amount)
+            .ok_or("Allocation amounts overflow.".to_string())?;
+    }
+    if total > tge_allocation {
+        return Err(format!(
+            "Allocations total {} which exceeds the TGE allocation of {}.",
+            total, tge_allocation
+        ));
+    }
+    Ok(())
+}
+
+/// The part of `tge_allocation` neither `allocations` nor the standalone `vesting`
+/// schedules take. A launch hands it to its creator as one more allocation.
+pub fn unallocated_tge(allocations: &[TgeAllocation], vesting: &[VestingArgs], tge_allocation: u64) -> u64 {
+    let allocated = allocations
+        .iter()
+        .map(|allocation| allocation.amount)
+        .chain(vesting.iter().map(|schedule| schedule.amount))
+        .fold(0u64, |total, amount| total.saturating_add(amount));
+    tge_allocation.saturating_sub(allocated)
+}
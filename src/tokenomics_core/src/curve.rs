@@ -0,0 +1,103 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Halving percentages tokenomics accepts at install.
+pub const MIN_HALVING_STEP: u64 = 25;
+pub const MAX_HALVING_STEP: u64 = 90;
+/// Longest `StepTable` accepted; the schedule never has more thresholds than this anyway.
+pub const MAX_STEP_TABLE_LEN: usize = 128;
+
+const BASIS_POINTS: u64 = 10_000;
+
+/// How the primary reward per burn unit decays from one burn threshold to the next.
+///
+/// Every curve starts at `initial_reward_per_burn_unit` on the first threshold. Rates
+/// never go up and never drop below 1; once a curve reaches 1 the schedule switches to
+/// its final one-reward threshold, as with halving.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum EmissionCurve {
+    /// Each threshold pays `halving_step` percent of the previous one.
+    Halving { halving_step: u64 },
+    /// Each threshold pays `decrement` less than the previous one.
+    Linear { decrement: u64 },
+    /// Each threshold pays `factor_bps` basis points of the previous one.
+    Exponential { factor_bps: u64 },
+    /// Explicit rates for the thresholds after the first; the last one repeats.
+    StepTable { rates: Vec<u64> },
+    /// Logistic decay: rates stay near the initial one, fall around threshold `midpoint`
+    /// and flatten out again. `steepness_bps` is the slope in basis points per threshold.
+    Sigmoid { midpoint: u64, steepness_bps: u64 },
+}
+
+impl EmissionCurve {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            EmissionCurve::Halving { halving_step } => {
+                if !(MIN_HALVING_STEP..=MAX_HALVING_STEP).contains(halving_step) {
+                    return Err(format!(
+                        "'halving_step' must be between {} and {}.",
+                        MIN_HALVING_STEP, MAX_HALVING_STEP
+                    ));
+                }
+            }
+            EmissionCurve::Linear { decrement } => {
+                if *decrement == 0 {
+                    return Err("'decrement' must be greater than 0.".to_string());
+                }
+            }
+            EmissionCurve::Exponential { factor_bps } => {
+                if *factor_bps == 0 || *factor_bps >= BASIS_POINTS {
+                    return Err(format!(
+                        "'factor_bps' must be between 1 and {}.",
+                        BASIS_POINTS - 1
+                    ));
+                }
+            }
+            EmissionCurve::StepTable { rates } => {
+                if rates.is_empty() || rates.len() > MAX_STEP_TABLE_LEN {
+                    return Err(format!(
+                        "'rates' must have between 1 and {} entries.",
+                        MAX_STEP_TABLE_LEN
+                    ));
+                }
+                if rates.contains(&0) {
+                    return Err("'rates' must all be greater than 0.".to_string());
+                }
+                if rates.windows(2).any(|pair| pair[0] < pair[1]) {
+                    return Err("'rates' must not increase.".to_string());
+                }
+            }
+            EmissionCurve::Sigmoid { steepness_bps, .. } => {
+                if *steepness_bps == 0 {
+                    return Err("'steepness_bps' must be greater than 0.".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The rate for threshold `index` (counting from 0), given the rate of the one before.
+    pub fn next_rate(&self, index: usize, initial_rate: u128, previous_rate: u128) -> u128 {
+        let rate = match self {
+            EmissionCurve::Halving { halving_step } => previous_rate * *halving_step as u128 / 100,
+            EmissionCurve::Linear { decrement } => previous_rate.saturating_sub(*decrement as u128),
+            EmissionCurve::Exponential { factor_bps } => {
+                previous_rate * *factor_bps as u128 / BASIS_POINTS as u128
+            }
+            EmissionCurve::StepTable { rates } => rates
+                .get(index.saturating_sub(1))
+                .or(rates.last())
+                .map_or(previous_rate, |rate| *rate as u128),
+            EmissionCurve::Sigmoid {
+                midpoint,
+                steepness_bps,
+            } => {
+                let steepness = *steepness_bps as f64 / BASIS_POINTS as f64;
+                let logistic = |x: f64| 1.0 / (1.0 + (steepness * (x - *midpoint as f64)).exp());
+                // Scaled so threshold 0 pays exactly the initial rate.
+                (initial_rate as f64 * logistic(index as f64) / logistic(0.0)) as u128
+            }
+        };
+        rate.clamp(1, previous_rate.max(1))
+    }
+}
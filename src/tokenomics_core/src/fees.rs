@@ -0,0 +1,79 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+pub const FEE_BASIS_POINTS: u64 = 10_000;
+/// Bounds every launch's fee schedule has to stay within, in basis points.
+pub const MIN_PROTOCOL_FEE_BPS: u64 = 100;
+pub const MAX_PROTOCOL_FEE_BPS: u64 = 1_000;
+pub const MAX_LP_SHARE_BPS: u64 = 7_000;
+pub const MIN_STAKER_SHARE_BPS: u64 = 2_500;
+pub const MAX_CREATOR_SHARE_BPS: u64 = 2_000;
+pub const MAX_RELEASE_RATE_BPS: u64 = 1_000;
+
+/// How a swap canister splits the ICP it releases on each reward run. The shares are
+/// in basis points of the released amount and add up to 100%; `release_rate_bps` is
+/// the part of the available ICP released per run.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FeeSchedule {
+    pub protocol_fee_bps: u64,
+    pub lp_share_bps: u64,
+    pub staker_share_bps: u64,
+    pub creator_share_bps: u64,
+    pub release_rate_bps: u64,
+}
+
+impl Default for FeeSchedule {
+    /// The split every swap canister used before fee schedules were configurable.
+    fn default() -> Self {
+        FeeSchedule {
+            protocol_fee_bps: 100,
+            lp_share_bps: 4_950,
+            staker_share_bps: 4_950,
+            creator_share_bps: 0,
+            release_rate_bps: 100,
+        }
+    }
+}
+
+impl FeeSchedule {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_PROTOCOL_FEE_BPS..=MAX_PROTOCOL_FEE_BPS).contains(&self.protocol_fee_bps) {
+            return Err(format!(
+                "'protocol_fee_bps' must be between {} and {}.",
+                MIN_PROTOCOL_FEE_BPS, MAX_PROTOCOL_FEE_BPS
+            ));
+        }
+        if self.lp_share_bps > MAX_LP_SHARE_BPS {
+            return Err(format!("'lp_share_bps' must be at most {}.", MAX_LP_SHARE_BPS));
+        }
+        if self.staker_share_bps < MIN_STAKER_SHARE_BPS {
+            return Err(format!(
+                "'staker_share_bps' must be at least {}.",
+                MIN_STAKER_SHARE_BPS
+            ));
+        }
+        if self.creator_share_bps > MAX_CREATOR_SHARE_BPS {
+            return Err(format!(
+                "'creator_share_bps' must be at most {}.",
+                MAX_CREATOR_SHARE_BPS
+            ));
+        }
+        if self.release_rate_bps == 0 || self.release_rate_bps > MAX_RELEASE_RATE_BPS {
+            return Err(format!(
+                "'release_rate_bps' must be between 1 and {}.",
+                MAX_RELEASE_RATE_BPS
+            ));
+        }
+        let total = self.protocol_fee_bps
+            + self.lp_share_bps
+            + self.staker_share_bps
+            + self.creator_share_bps;
+        if total != FEE_BASIS_POINTS {
+            return Err(format!(
+                "Fee shares add up to {} basis points instead of {}.",
+                total, FEE_BASIS_POINTS
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+use candid::{CandidType, Principal};
+use serde::Deserialize;
+
+use crate::{
+    generate_emission_schedule, EmissionCurve, TgeAllocation, TokenomicsSchedule, VestingArgs,
+};
+
+/// What `lbry_fun` installs `tokenomics` with.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct TokenomicsInitArgs {
+    pub primary_token_id: Option<Principal>,
+    pub secondary_token_id: Option<Principal>,
+    pub swap_canister_id: Option<Principal>,
+    pub frontend_canister_id: Option<Principal>,
+    pub max_primary_supply: u64,
+    pub initial_primary_mint: u64,
+    pub initial_secondary_burn: u64,
+    pub max_primary_phase: u64,
+    pub halving_step: u64,
+    pub initial_reward_per_burn_unit: u64,
+    // Halving by `halving_step` when unset.
+    pub emission_curve: Option<EmissionCurve>,
+    // Parts of `initial_primary_mint` held back and released over time.
+    pub vesting: Option<Vec<VestingArgs>>,
+    // Who gets `initial_primary_mint`. Vested allocations become vesting schedules and
+    // `Airdrop` ones fund airdrops; the rest is minted by `mint_tge_allocations`.
+    pub allocations: Option<Vec<TgeAllocation>>,
+}
+
+impl TokenomicsInitArgs {
+    pub fn emission_curve(&self) -> EmissionCurve {
+        self.emission_curve.clone().unwrap_or(EmissionCurve::Halving {
+            halving_step: self.halving_step,
+        })
+    }
+
+    /// The schedule `tokenomics` stores at install.
+    pub fn schedule(&self) -> TokenomicsSchedule {
+        generate_emission_schedule(
+            self.initial_secondary_burn,
+            self.initial_reward_per_burn_unit,
+            self.max_primary_supply,
+            &self.emission_curve(),
+        )
+    }
+}
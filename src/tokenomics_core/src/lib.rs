@@ -0,0 +1,43 @@
+//! Tokenomics math shared by `tokenomics` and `lbry_fun`.
+//!
+//! `tokenomics` stores the schedule its [`TokenomicsInitArgs`] describe at install time
+//! and mints along it with [`simulate_mint`]. `lbry_fun` previews a launch with
+//! [`preview_tokenomics`], which walks the same schedule, so what a launcher is shown is
+//! what gets deployed. Both take the launch's [`EmissionCurve`], halving by default.
+//! TGE allocations held back by `tokenomics` are released with [`vested_amount`], and
+//! airdrops funded from them are claimed against a [`merkle_root`]. `icp_swap` caps
+//! early buyers with the launch's [`LaunchProtection`], splits its rewards along its
+//! [`FeeSchedule`] and shares the stakers' part through a [`RewardIndex`].
+
+pub mod allocation;
+pub mod curve;
+pub mod fees;
+pub mod install;
+pub mod merkle;
+pub mod mint;
+pub mod preview;
+pub mod protection;
+pub mod rewards;
+pub mod schedule;
+pub mod vesting;
+
+pub use allocation::{
+    unallocated_tge, validate_allocations, AllocationBucket, TgeAllocation, VestingTerms,
+    MAX_TGE_ALLOCATIONS,
+};
+pub use curve::EmissionCurve;
+pub use fees::FeeSchedule;
+pub use install::TokenomicsInitArgs;
+pub use merkle::{
+    airdrop_leaf, merkle_proof, merkle_root, verify_merkle_proof, MerkleHash, MAX_MERKLE_PROOF_LEN,
+};
+pub use mint::{simulate_mint, BurnSimulation, MintMathError, ThresholdMint};
+pub use preview::{preview_tokenomics, GraphData, PreviewArgs};
+pub use protection::LaunchProtection;
+pub use rewards::{RewardIndex, REWARD_SCALING_FACTOR};
+pub use schedule::{generate_emission_schedule, generate_tokenomics_schedule, TokenomicsSchedule};
+pub use vesting::{validate_vesting, vested_amount, VestingArgs};
+
+pub const E8S: u128 = 100_000_000;
+/// Primary e8s minted per secondary burned, per unit of the threshold's reward rate.
+pub const PRIMARY_MINT_SCALE: u128 = 10_000;
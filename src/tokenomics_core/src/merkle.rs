@@ -0,0 +1,81 @@
+use candid::Principal;
+use sha2::{Digest, Sha256};
+
+pub type MerkleHash = [u8; 32];
+
+/// Longest proof accepted, enough for a tree of 2^64 leaves.
+pub const MAX_MERKLE_PROOF_LEN: usize = 64;
+
+// Domain separation so a leaf can never be passed off as an inner node.
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+/// The leaf committing to `recipient` being owed `amount` e8s.
+pub fn airdrop_leaf(recipient: Principal, amount: u64) -> MerkleHash {
+    let recipient = recipient.as_slice();
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX, recipient.len() as u8]);
+    hasher.update(recipient);
+    hasher.update(amount.to_be_bytes());
+    hasher.finalize().into()
+}
+
+/// Pairs are hashed in sorted order, so proofs do not need to say which side each
+/// sibling is on.
+fn hash_pair(a: &MerkleHash, b: &MerkleHash) -> MerkleHash {
+    let (low, high) = if a <= b { (a, b) } else { (b, a) };
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(low);
+    hasher.update(high);
+    hasher.finalize().into()
+}
+
+/// Whether `proof` links `leaf` to `root`.
+pub fn verify_merkle_proof(root: &MerkleHash, leaf: MerkleHash, proof: &[MerkleHash]) -> bool {
+    if proof.len() > MAX_MERKLE_PROOF_LEN {
+        return false;
+    }
+    proof.iter().fold(leaf, |node, sibling| hash_pair(&node, sibling)) == *root
+}
+
+// Each level of the tree, leaves first. An odd node out is carried up unchanged.
+fn merkle_levels(leaves: &[MerkleHash]) -> Vec<Vec<MerkleHash>> {
+    let mut levels = vec![leaves.to_vec()];
+    while levels.last().is_some_and(|level| level.len() > 1) {
+        let next = levels
+            .last()
+            .unwrap()
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => hash_pair(a, b),
+                [a] => *a,
+                _ => unreachable!(),
+            })
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+/// Root of the tree over `leaves`, as committed with an airdrop.
+pub fn merkle_root(leaves: &[MerkleHash]) -> Option<MerkleHash> {
+    merkle_levels(leaves).last()?.first().copied()
+}
+
+/// Proof for the leaf at `index`, as passed to `claim_airdrop`.
+pub fn merkle_proof(leaves: &[MerkleHash], index: usize) -> Option<Vec<MerkleHash>> {
+    if index >= leaves.len() {
+        return None;
+    }
+    let levels = merkle_levels(leaves);
+    let mut index = index;
+    let mut proof = Vec::new();
+    for level in &levels[..levels.len() - 1] {
+        if let Some(sibling) = level.get(index ^ 1) {
+            proof.push(*sibling);
+        }
+        index /= 2;
+    }
+    Some(proof)
+}
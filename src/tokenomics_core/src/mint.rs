@@ -0,0 +1,169 @@
+use candid::CandidType;
+use serde::Deserialize;
+
+use crate::{TokenomicsSchedule, E8S, PRIMARY_MINT_SCALE};
+
+/// Primary minted while the burn sat in one threshold.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ThresholdMint {
+    pub threshold_index: u32,
+    pub secondary_burned: u64,
+    pub primary_minted: u64,
+}
+
+/// What minting for a burn would do, without doing it.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BurnSimulation {
+    pub secondary_burn: u64,
+    // Total over `thresholds`, before the supply cap.
+    pub primary_minted: u64,
+    pub thresholds: Vec<ThresholdMint>,
+    pub threshold_index_after: u32,
+    // Secondary that can still be burned before minting stops.
+    pub remaining_secondary_capacity: u64,
+    // Whether minting would fail with `MaxMintPrimaryReached`.
+    pub max_mint_primary_reached: bool,
+    // Whether it would fail with `MaxPrimaryPerTrnxReached`.
+    pub max_primary_phase_exceeded: bool,
+}
+
+impl BurnSimulation {
+    /// Thresholds it takes to mint `mintable` whole primary tokens, counting the one that
+    /// reaches them, or all of them if it never does.
+    pub fn epochs_to_mint(&self, mintable: u64) -> u32 {
+        let mintable_e8s = (mintable as u128).saturating_mul(E8S);
+        let mut minted_e8s = 0u128;
+        let mut epochs = 0;
+        for threshold in &self.thresholds {
+            epochs += 1;
+            minted_e8s += threshold.primary_minted as u128;
+            if minted_e8s >= mintable_e8s {
+                break;
+            }
+        }
+        epochs
+    }
+}
+
+/// Arithmetic failures while walking the thresholds; each carries the operands.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MintMathError {
+    AdditionOverflow(String),
+    MultiplicationOverflow(String),
+    Underflow(String),
+}
+
+/// Walks the burn thresholds `secondary_burn` would cross from `total_burned_secondary`
+/// and `current_threshold_index`, recording what each one mints.
+///
+/// `max_primary_phase` is the per-transaction cap in whole primary tokens.
+pub fn simulate_mint(
+    schedule: &TokenomicsSchedule,
+    total_burned_secondary: u64,
+    current_threshold_index: u32,
+    secondary_burn: u64,
+    max_primary_phase: u64,
+) -> Result<BurnSimulation, MintMathError> {
+    let thresholds = &schedule.secondary_burn_thresholds;
+    let rates = &schedule.primary_mint_per_threshold;
+    let last_index = thresholds.len().saturating_sub(1) as u32;
+    let last_threshold = thresholds.last().copied().unwrap_or(0);
+
+    let mut simulation = BurnSimulation {
+        secondary_burn,
+        primary_minted: 0,
+        thresholds: Vec::new(),
+        threshold_index_after: current_threshold_index,
+        remaining_secondary_capacity: last_threshold.saturating_sub(total_burned_secondary),
+        max_mint_primary_reached: false,
+        max_primary_phase_exceeded: false,
+    };
+
+    let tentative_total = total_burned_secondary.checked_add(secondary_burn).ok_or_else(|| {
+        MintMathError::AdditionOverflow(format!(
+            "total_burned_secondary: {} with secondary_burn: {}",
+            total_burned_secondary, secondary_burn
+        ))
+    })?;
+    if thresholds.is_empty() || tentative_total > last_threshold {
+        simulation.max_mint_primary_reached = true;
+        return Ok(simulation);
+    }
+
+    let mut total_burned = total_burned_secondary;
+    let mut index = current_threshold_index.min(last_index);
+
+    if tentative_total > thresholds[index as usize] {
+        let mut secondary_processed: u64 = 0;
+
+        while tentative_total > thresholds[index as usize] {
+            let threshold = thresholds[index as usize];
+            let in_threshold = if total_burned < threshold {
+                threshold - total_burned
+            } else {
+                secondary_burn.checked_sub(secondary_processed).ok_or_else(|| {
+                    MintMathError::Underflow(format!(
+                        "secondary_burn: {} with secondary_processed: {}",
+                        secondary_burn, secondary_processed
+                    ))
+                })?
+            };
+            record_threshold_mint(&mut simulation, rates, index, in_threshold)?;
+
+            secondary_processed = secondary_processed.checked_add(in_threshold).ok_or_else(|| {
+                MintMathError::AdditionOverflow(format!(
+                    "secondary_processed: {} with in_threshold: {}",
+                    secondary_processed, in_threshold
+                ))
+            })?;
+            total_burned = total_burned.checked_add(in_threshold).ok_or_else(|| {
+                MintMathError::AdditionOverflow(format!(
+                    "total_burned_secondary: {} with in_threshold: {}",
+                    total_burned, in_threshold
+                ))
+            })?;
+            index = (index + 1).min(last_index);
+        }
+
+        if secondary_burn > secondary_processed {
+            record_threshold_mint(&mut simulation, rates, index, secondary_burn - secondary_processed)?;
+        }
+    } else {
+        record_threshold_mint(&mut simulation, rates, index, secondary_burn)?;
+    }
+
+    simulation.threshold_index_after = index;
+    simulation.max_primary_phase_exceeded =
+        (simulation.primary_minted as u128) > (max_primary_phase as u128) * crate::E8S;
+    Ok(simulation)
+}
+
+fn record_threshold_mint(
+    simulation: &mut BurnSimulation,
+    rates: &[u64],
+    index: u32,
+    secondary_burned: u64,
+) -> Result<(), MintMathError> {
+    let rate = rates[index as usize];
+    let primary_minted = rate
+        .checked_mul(secondary_burned)
+        .and_then(|minted| minted.checked_mul(PRIMARY_MINT_SCALE as u64))
+        .ok_or_else(|| {
+            MintMathError::MultiplicationOverflow(format!(
+                "primary_mint_per_threshold[{}]: {} with secondary_burned: {}",
+                index, rate, secondary_burned
+            ))
+        })?;
+    simulation.primary_minted = simulation.primary_minted.checked_add(primary_minted).ok_or_else(|| {
+        MintMathError::AdditionOverflow(format!(
+            "primary_minted: {} with threshold mint: {}",
+            simulation.primary_minted, primary_minted
+        ))
+    })?;
+    simulation.thresholds.push(ThresholdMint {
+        threshold_index: index,
+        secondary_burned,
+        primary_minted,
+    });
+    Ok(())
+}
@@ -1,21 +1,26 @@
 use candid::{CandidType, Deserialize};
 
-#[derive(CandidType, Deserialize, Clone, Default)]
-pub struct TokenomicsSchedule {
-    pub secondary_burn_thresholds: Vec<u64>,
-    pub primary_mint_per_threshold: Vec<u64>,
-}
+use std::collections::BTreeMap;
+
+use crate::{
+    generate_emission_schedule, AllocationBucket, EmissionCurve, TgeAllocation,
+    TokenomicsSchedule, E8S, PRIMARY_MINT_SCALE,
+};
 
-#[derive(CandidType, Deserialize, Clone)]
+#[derive(CandidType, Deserialize, Clone, Debug)]
 pub struct PreviewArgs {
     pub primary_max_supply: u64,
     pub tge_allocation: u64,
     pub initial_secondary_burn: u64,
     pub halving_step: u64,
     pub initial_reward_per_burn_unit: u64,
+    // Halving by `halving_step` when unset.
+    pub emission_curve: Option<EmissionCurve>,
+    // How `tge_allocation` is split, in whole tokens like it. The rest goes to the creator.
+    pub allocations: Option<Vec<TgeAllocation>>,
 }
 
-#[derive(CandidType, Deserialize, Clone, Default)]
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
 pub struct GraphData {
     pub cumulative_supply_data_x: Vec<u64>,
     pub cumulative_supply_data_y: Vec<u64>,
@@ -25,20 +30,41 @@ pub struct GraphData {
     pub cost_to_mint_data_y: Vec<f64>,
     pub cumulative_usd_cost_data_x: Vec<u64>,
     pub cumulative_usd_cost_data_y: Vec<f64>,
+    // TGE allocation per bucket, in e8s.
+    pub tge_allocation_data_x: Vec<String>,
+    pub tge_allocation_data_y: Vec<u64>,
 }
 
 const SECONDARY_BURN_USD_COST: f64 = 0.005;
-const E8S: u128 = 100_000_000;
+
+impl PreviewArgs {
+    pub fn emission_curve(&self) -> EmissionCurve {
+        self.emission_curve.clone().unwrap_or(EmissionCurve::Halving {
+            halving_step: self.halving_step,
+        })
+    }
+
+    /// The schedule tokenomics would be installed with for these launch parameters.
+    pub fn schedule(&self) -> TokenomicsSchedule {
+        generate_emission_schedule(
+            self.initial_secondary_burn,
+            self.initial_reward_per_burn_unit,
+            self.primary_max_supply,
+            &self.emission_curve(),
+        )
+    }
+}
 
 pub fn preview_tokenomics(args: PreviewArgs) -> GraphData {
-    let schedule = generate_tokenomics_schedule(
-        args.initial_secondary_burn,
-        args.initial_reward_per_burn_unit,
-        args.primary_max_supply,
-        args.halving_step,
-    );
+    let schedule = args.schedule();
 
     let mut graph_data = GraphData::default();
+    for (bucket, amount_e8s) in tge_allocation_by_bucket(&args) {
+        graph_data
+            .tge_allocation_data_x
+            .push(format!("{:?}", bucket));
+        graph_data.tge_allocation_data_y.push(amount_e8s as u64);
+    }
     if schedule.secondary_burn_thresholds.is_empty() {
         return graph_data;
     }
@@ -87,7 +113,7 @@ pub fn preview_tokenomics(args: PreviewArgs) -> GraphData {
 
         let potential_primary_mint_e8s = epoch_secondary_burn_capacity
             .saturating_mul(reward_rate)
-            .saturating_mul(10000);
+            .saturating_mul(PRIMARY_MINT_SCALE);
         let remaining_to_mint_e8s =
             max_primary_supply_e8s.saturating_sub(total_primary_minted_e8s);
 
@@ -96,7 +122,7 @@ pub fn preview_tokenomics(args: PreviewArgs) -> GraphData {
                 let secondary_needed_to_cap = if reward_rate > 0 {
                     remaining_to_mint_e8s
                         .saturating_div(reward_rate)
-                        .saturating_div(10000)
+                        .saturating_div(PRIMARY_MINT_SCALE)
                 } else {
                     0
                 };
@@ -160,60 +186,19 @@ pub fn preview_tokenomics(args: PreviewArgs) -> GraphData {
     graph_data
 }
 
-fn generate_tokenomics_schedule(
-    initial_secondary_burn: u64,
-    initial_reward_per_burn_unit: u64,
-    max_primary_supply: u64,
-    halving_step: u64,
-) -> TokenomicsSchedule {
-    let mut secondary_thresholds = Vec::new();
-    let mut primary_rewards = Vec::new();
-
-    let mut burn_for_epoch = initial_secondary_burn as u128;
-    let mut cumulative_burn = 0u128;
-    let mut total_minted = 0u128;
-    let mut primary_per_threshold = initial_reward_per_burn_unit as u128;
-    let mut one_reward_mode = false;
-    const E8S: u128 = 100_000_000;
-
-    while total_minted < max_primary_supply as u128 {
-        let in_slot_burn = burn_for_epoch;
-        let reward_e8s = primary_per_threshold * in_slot_burn * 10000;
-        let reward = reward_e8s / E8S;
-
-        if one_reward_mode {
-            let remaining_mint = max_primary_supply as u128 - total_minted;
-            let final_burn = remaining_mint * 10000;
-            let final_threshold = cumulative_burn + final_burn;
-            secondary_thresholds.push(final_threshold as u64);
-            primary_rewards.push(1);
-            total_minted += remaining_mint;
-            break;
-        }
-
-        if reward == 0 {
-            break;
-        }
-
-        cumulative_burn += burn_for_epoch;
-        secondary_thresholds.push(cumulative_burn as u64);
-        primary_rewards.push(primary_per_threshold as u64);
-
-        total_minted += reward;
-        burn_for_epoch *= 2;
-
-        if primary_per_threshold > 1 {
-            primary_per_threshold =
-                std::cmp::max(1, (primary_per_threshold * halving_step as u128) / 100);
-        }
-
-        if primary_per_threshold == 1 {
-            one_reward_mode = true;
-        }
+fn tge_allocation_by_bucket(args: &PreviewArgs) -> BTreeMap<AllocationBucket, u128> {
+    let mut buckets = BTreeMap::new();
+    let mut allocated = 0u128;
+    for allocation in args.allocations.iter().flatten() {
+        let amount_e8s = (allocation.amount as u128).saturating_mul(E8S);
+        *buckets.entry(allocation.bucket).or_insert(0) += amount_e8s;
+        allocated += amount_e8s;
     }
-
-    TokenomicsSchedule {
-        secondary_burn_thresholds: secondary_thresholds,
-        primary_mint_per_threshold: primary_rewards,
+    let remainder = (args.tge_allocation as u128)
+        .saturating_mul(E8S)
+        .saturating_sub(allocated);
+    if remainder > 0 {
+        *buckets.entry(AllocationBucket::Creator).or_insert(0) += remainder;
     }
-} 
\ No newline at end of file
+    buckets
+}
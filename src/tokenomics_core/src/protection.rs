@@ -0,0 +1,58 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Longest launch-protection window accepted, one week.
+pub const MAX_LAUNCH_PROTECTION_SECS: u64 = 7 * 24 * 60 * 60;
+/// Longest the window waits for liquidity after install before it starts anyway, one week.
+pub const MAX_LIQUIDITY_DELAY_SECS: u64 = 7 * 24 * 60 * 60;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// Per-principal limits on a token's swap canister while it launches. They apply from
+/// install until `window_secs` after liquidity is provided on the DEX, or after
+/// `MAX_LIQUIDITY_DELAY_SECS` past install if liquidity takes longer. Unset caps do not
+/// limit that operation.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LaunchProtection {
+    pub window_secs: u64,
+    // ICP each principal can swap for secondary, in e8s.
+    pub max_icp_swapped: Option<u64>,
+    // Secondary each principal can burn, in e8s.
+    pub max_secondary_burned: Option<u64>,
+}
+
+impl LaunchProtection {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.window_secs == 0 || self.window_secs > MAX_LAUNCH_PROTECTION_SECS {
+            return Err(format!(
+                "Launch protection 'window_secs' must be between 1 and {}.",
+                MAX_LAUNCH_PROTECTION_SECS
+            ));
+        }
+        if self.max_icp_swapped.is_none() && self.max_secondary_burned.is_none() {
+            return Err("Launch protection must cap swaps, burns or both.".to_string());
+        }
+        if self.max_icp_swapped == Some(0) || self.max_secondary_burned == Some(0) {
+            return Err("Launch protection caps must be greater than 0.".to_string());
+        }
+        Ok(())
+    }
+
+    /// When the window closes, in nanoseconds.
+    pub fn ends_at(&self, installed_at: u64, liquidity_provided_at: Option<u64>) -> u64 {
+        let latest_start =
+            installed_at.saturating_add(MAX_LIQUIDITY_DELAY_SECS.saturating_mul(NANOS_PER_SEC));
+        liquidity_provided_at
+            .map_or(latest_start, |start| start.min(latest_start))
+            .saturating_add(self.window_secs.saturating_mul(NANOS_PER_SEC))
+    }
+
+    pub fn is_active(
+        &self,
+        installed_at: u64,
+        liquidity_provided_at: Option<u64>,
+        now: u64,
+    ) -> bool {
+        now < self.ends_at(installed_at, liquidity_provided_at)
+    }
+}
@@ -0,0 +1,60 @@
+use candid::CandidType;
+use serde::Deserialize;
+
+/// `RewardIndex::reward_per_weight` is scaled by this, so small rewards shared across a
+/// large weight still accrue.
+pub const REWARD_SCALING_FACTOR: u128 = 1_000_000_000_000;
+
+/// Global reward accumulator: reward earned per unit of stake weight since launch,
+/// scaled by `REWARD_SCALING_FACTOR`, and the weight it is shared across.
+///
+/// A stake is checkpointed at `reward_per_weight` whenever it is settled and earns
+/// `weight * (reward_per_weight - checkpoint)` until the next settle, so distributing
+/// never has to touch the stakes. Anything that changes a stake's weight, including a
+/// lock boost running out, has to settle it first.
+#[derive(CandidType, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RewardIndex {
+    pub reward_per_weight: u128,
+    pub total_weight: u128,
+}
+
+impl RewardIndex {
+    /// Reward a stake of `weight` checkpointed at `checkpoint` has earned since. Stakes
+    /// that were never checkpointed have nothing pending.
+    pub fn pending(&self, weight: u128, checkpoint: Option<u128>) -> u64 {
+        let Some(checkpoint) = checkpoint else {
+            return 0;
+        };
+        (weight * self.reward_per_weight.saturating_sub(checkpoint) / REWARD_SCALING_FACTOR)
+            as u64
+    }
+
+    /// Settles a stake of `weight` checkpointed at `checkpoint` and counts it at
+    /// `new_weight` from here on. Returns what it earned since the checkpoint; the stake's
+    /// new checkpoint is the current `reward_per_weight`.
+    pub fn settle(&mut self, weight: Option<u128>, checkpoint: Option<u128>, new_weight: u128) -> u64 {
+        let weight = weight.unwrap_or(0);
+        let pending = self.pending(weight, checkpoint);
+        self.total_weight = self.total_weight.saturating_sub(weight) + new_weight;
+        pending
+    }
+
+    /// Raises `reward_per_weight` for every stake at once.
+    pub fn add(&mut self, reward_per_weight: u128) {
+        self.reward_per_weight += reward_per_weight;
+    }
+}
+
+#[cfg(feature = "stable-structures")]
+impl ic_stable_structures::Storable for RewardIndex {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Owned(candid::encode_one(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        candid::decode_one(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: ic_stable_structures::storable::Bound =
+        ic_stable_structures::storable::Bound::Unbounded;
+}
@@ -0,0 +1,131 @@
+use candid::CandidType;
+use serde::Deserialize;
+
+use crate::{EmissionCurve, E8S, PRIMARY_MINT_SCALE};
+
+/// Cumulative secondary burn thresholds and the primary reward rate below each of them.
+#[derive(CandidType, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TokenomicsSchedule {
+    pub secondary_burn_thresholds: Vec<u64>,
+    pub primary_mint_per_threshold: Vec<u64>,
+}
+
+#[cfg(feature = "stable-structures")]
+impl ic_stable_structures::Storable for TokenomicsSchedule {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        std::borrow::Cow::Owned(candid::encode_one(self).unwrap())
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        candid::decode_one(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: ic_stable_structures::storable::Bound =
+        ic_stable_structures::storable::Bound::Unbounded;
+}
+
+/// Generates the tokenomics schedule based on the provided parameters.
+///
+/// # Philosophy: "Ideal" Schedule vs. Real-World Execution
+///
+/// This function calculates an "ideal" or "perfect" tokenomics schedule that maps out the
+/// entire minting process based on the `max_primary_supply`. It deliberately does not
+/// account for the TGE (Token Generation Event) allocation, nor the single token used
+/// to initially seed the liquidity pool.
+///
+/// In practice, the TGE is minted separately, and the live minting process (driven by
+/// burning the secondary token) begins from that point. The overall `max_primary_supply`
+/// acts as a hard cap. If the TGE plus the scheduled minting reaches this cap, the process
+/// stops.
+///
+/// This design means the on-chain schedule represents a "perfect" reference model of minting
+/// rewards over time. The hard cap is the ultimate enforcer, which may render the final,
+/// less rewarding epochs of this pre-calculated schedule unreachable if the supply is exhausted
+/// sooner.
+pub fn generate_tokenomics_schedule(
+    initial_secondary_burn: u64,
+    initial_reward_per_burn_unit: u64,
+    max_primary_supply: u64,
+    halving_step: u64,
+) -> TokenomicsSchedule {
+    generate_emission_schedule(
+        initial_secondary_burn,
+        initial_reward_per_burn_unit,
+        max_primary_supply,
+        &EmissionCurve::Halving { halving_step },
+    )
+}
+
+/// Same as [`generate_tokenomics_schedule`], with the reward rate decaying along `curve`
+/// instead of by a fixed halving step. Thresholds still double.
+pub fn generate_emission_schedule(
+    initial_secondary_burn: u64,
+    initial_reward_per_burn_unit: u64,
+    max_primary_supply: u64,
+    curve: &EmissionCurve,
+) -> TokenomicsSchedule {
+    let mut secondary_thresholds = Vec::new();
+    let mut primary_rewards = Vec::new();
+
+    let mut current_burn = initial_secondary_burn as u128;
+    let mut last_burn = 0u128;
+    let mut total_minted = 0u128;
+    let mut primary_per_threshold = initial_reward_per_burn_unit as u128;
+
+    let mut one_reward_mode = false;
+
+    while total_minted < max_primary_supply as u128 {
+        let in_slot_burn = current_burn - last_burn;
+
+        let reward_e8s = primary_per_threshold * in_slot_burn * PRIMARY_MINT_SCALE;
+        let reward = reward_e8s / E8S;
+
+        // This block handles the "One Reward Mode", a special final phase of minting.
+        // It is triggered when the decaying reward per unit has reached its absolute minimum
+        // value (1 e8s of the primary token). At this point, the emission curve no longer
+        // applies.
+        if one_reward_mode {
+            // Remaining mint allowed
+            let remaining_mint = max_primary_supply as u128 - total_minted;
+
+            // This calculation determines the amount of secondary token that must be burned
+            // to mint the entire remaining primary supply.
+            let final_burn = remaining_mint * PRIMARY_MINT_SCALE;
+
+            // Final cumulative burn value
+            let final_threshold = last_burn + final_burn;
+
+            secondary_thresholds.push(final_threshold as u64);
+            primary_rewards.push(1);
+            break;
+        }
+
+        if reward == 0 {
+            break;
+        }
+
+        secondary_thresholds.push(current_burn as u64);
+        primary_rewards.push(primary_per_threshold as u64);
+
+        total_minted += reward;
+        last_burn = current_burn;
+        current_burn *= 2;
+
+        if primary_per_threshold > 1 {
+            primary_per_threshold = curve.next_rate(
+                secondary_thresholds.len(),
+                initial_reward_per_burn_unit as u128,
+                primary_per_threshold,
+            );
+        }
+
+        if primary_per_threshold == 1 {
+            one_reward_mode = true;
+        }
+    }
+
+    TokenomicsSchedule {
+        secondary_burn_thresholds: secondary_thresholds,
+        primary_mint_per_threshold: primary_rewards,
+    }
+}
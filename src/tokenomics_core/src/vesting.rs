@@ -0,0 +1,84 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+/// Most vesting schedules one launch can attach to its TGE allocation.
+pub const MAX_VESTING_SCHEDULES: usize = 32;
+/// Longest vesting period accepted, ten years.
+pub const MAX_VESTING_DURATION_SECS: u64 = 10 * 365 * 24 * 60 * 60;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// Part of the TGE allocation held back for `beneficiary`: nothing is released before
+/// `cliff_secs`, then `amount` vests linearly until `duration_secs`, both counted from
+/// the launch.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VestingArgs {
+    pub beneficiary: Principal,
+    pub amount: u64,
+    pub cliff_secs: u64,
+    pub duration_secs: u64,
+}
+
+impl VestingArgs {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.beneficiary == Principal::anonymous() {
+            return Err("Vesting 'beneficiary' cannot be anonymous.".to_string());
+        }
+        if self.amount == 0 {
+            return Err("Vesting 'amount' must be greater than 0.".to_string());
+        }
+        if self.duration_secs == 0 || self.duration_secs > MAX_VESTING_DURATION_SECS {
+            return Err(format!(
+                "Vesting 'duration_secs' must be between 1 and {}.",
+                MAX_VESTING_DURATION_SECS
+            ));
+        }
+        if self.cliff_secs > self.duration_secs {
+            return Err("Vesting 'cliff_secs' cannot be longer than 'duration_secs'.".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Checks every schedule and that together they fit in `tge_allocation`.
+pub fn validate_vesting(vesting: &[VestingArgs], tge_allocation: u64) -> Result<(), String> {
+    if vesting.len() > MAX_VESTING_SCHEDULES {
+        return Err(format!(
+            "At most {} vesting schedules are allowed.",
+            MAX_VESTING_SCHEDULES
+        ));
+    }
+    let mut total: u64 = 0;
+    for schedule in vesting {
+        schedule.validate()?;
+        total = total
+            .checked_add(schedule.amount)
+            .ok_or("Vesting amounts overflow.".to_string())?;
+    }
+    if total > tge_allocation {
+        return Err(format!(
+            "Vesting amounts total {} which exceeds the TGE allocation of {}.",
+            total, tge_allocation
+        ));
+    }
+    Ok(())
+}
+
+/// How much of `amount` has vested at `now`, for a schedule that started at `start`.
+/// Both times are in nanoseconds.
+pub fn vested_amount(
+    amount: u64,
+    start: u64,
+    cliff_secs: u64,
+    duration_secs: u64,
+    now: u64,
+) -> u64 {
+    let elapsed_secs = now.saturating_sub(start) / NANOS_PER_SEC;
+    if elapsed_secs < cliff_secs {
+        return 0;
+    }
+    if elapsed_secs >= duration_secs {
+        return amount;
+    }
+    (amount as u128 * elapsed_secs as u128 / duration_secs as u128) as u64
+}
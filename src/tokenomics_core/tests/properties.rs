@@ -0,0 +1,368 @@
+use proptest::prelude::*;
+use candid::Principal;
+use std::collections::BTreeSet;
+use tokenomics_core::{
+    airdrop_leaf, merkle_proof, merkle_root, preview_tokenomics, simulate_mint,
+    verify_merkle_proof, vested_amount, EmissionCurve, LaunchProtection, PreviewArgs,
+    RewardIndex, TokenomicsInitArgs, E8S, REWARD_SCALING_FACTOR,
+};
+use tokenomics_core::protection::{MAX_LAUNCH_PROTECTION_SECS, MAX_LIQUIDITY_DELAY_SECS};
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+// Curves `EmissionCurve::validate` accepts.
+fn emission_curve() -> impl Strategy<Value = EmissionCurve> {
+    prop_oneof![
+        (25..=90u64).prop_map(|halving_step| EmissionCurve::Halving { halving_step }),
+        (1..=10_000u64).prop_map(|decrement| EmissionCurve::Linear { decrement }),
+        (1..10_000u64).prop_map(|factor_bps| EmissionCurve::Exponential { factor_bps }),
+        prop::collection::vec(1..=100_000u64, 1..=32).prop_map(|mut rates| {
+            rates.sort_unstable_by(|a, b| b.cmp(a));
+            EmissionCurve::StepTable { rates }
+        }),
+        (0..=64u64, 1..=50_000u64).prop_map(|(midpoint, steepness_bps)| {
+            EmissionCurve::Sigmoid {
+                midpoint,
+                steepness_bps,
+            }
+        }),
+    ]
+}
+
+// Launch parameters in the ranges `create_token` is used with; `halving_step` is
+// limited to what tokenomics accepts at install.
+fn preview_args() -> impl Strategy<Value = PreviewArgs> {
+    (
+        1..=100_000_000u64,
+        1..=1_000_000u64,
+        25..=90u64,
+        1..=100_000u64,
+        prop::option::of(emission_curve()),
+    )
+        .prop_flat_map(|(max_supply, burn, step, reward, curve)| {
+            (0..=max_supply).prop_map(move |tge| PreviewArgs {
+                primary_max_supply: max_supply,
+                tge_allocation: tge,
+                initial_secondary_burn: burn,
+                halving_step: step,
+                initial_reward_per_burn_unit: reward,
+                emission_curve: curve.clone(),
+                allocations: None,
+            })
+        })
+}
+
+const STAKERS: usize = 4;
+
+// What stakers do between reward distributions. Amounts are whole tokens, in e8s.
+#[derive(Clone, Debug)]
+enum StakeOp {
+    Stake { staker: usize, amount: u64 },
+    Unstake { staker: usize, amount: u64 },
+    // Adds `boost` to the staker's weight for `secs`.
+    Lock { staker: usize, boost: u64, secs: u64 },
+    Wait { secs: u64 },
+    Distribute { reward: u64 },
+}
+
+fn stake_ops() -> impl Strategy<Value = Vec<StakeOp>> {
+    let tokens = || (1..=10_000u64).prop_map(|tokens| tokens * E8S as u64);
+    prop::collection::vec(
+        prop_oneof![
+            (0..STAKERS, tokens()).prop_map(|(staker, amount)| StakeOp::Stake { staker, amount }),
+            (0..STAKERS, tokens())
+                .prop_map(|(staker, amount)| StakeOp::Unstake { staker, amount }),
+            (0..STAKERS, tokens(), 1..=100u64)
+                .prop_map(|(staker, boost, secs)| StakeOp::Lock { staker, boost, secs }),
+            (1..=100u64).prop_map(|secs| StakeOp::Wait { secs }),
+            (1..=1_000_000_000_000u64).prop_map(|reward| StakeOp::Distribute { reward }),
+        ],
+        1..=64,
+    )
+}
+
+// A stake the way icp_swap keeps it, plus what it is owed.
+#[derive(Default)]
+struct Staker {
+    amount: u64,
+    // (boost, expiry)
+    locks: Vec<(u64, u64)>,
+    reward: u64,
+    checkpoint: Option<u128>,
+    weight: Option<u128>,
+    settles: u64,
+    // Every distribution's share at the weight the stake really had then, scaled by
+    // `REWARD_SCALING_FACTOR`.
+    owed_scaled: u128,
+}
+
+impl Staker {
+    fn weight_at(&self, now: u64) -> u128 {
+        self.amount as u128
+            + self
+                .locks
+                .iter()
+                .filter(|(_, expiry)| *expiry > now)
+                .map(|(boost, _)| *boost as u128)
+                .sum::<u128>()
+    }
+
+    // What `settle_stake` does.
+    fn settle(&mut self, index: &mut RewardIndex, now: u64) {
+        let weight = self.weight_at(now);
+        self.reward += index.settle(self.weight, self.checkpoint, weight);
+        self.checkpoint = Some(index.reward_per_weight);
+        self.weight = Some(weight);
+        self.settles += 1;
+    }
+}
+
+proptest! {
+    #[test]
+    fn preview_uses_the_deployed_schedule(args in preview_args()) {
+        // The args lbry_fun installs tokenomics with for the same launch, and the schedule
+        // tokenomics stores from them.
+        let init_args = TokenomicsInitArgs {
+            max_primary_supply: args.primary_max_supply,
+            initial_primary_mint: args.tge_allocation,
+            initial_secondary_burn: args.initial_secondary_burn,
+            halving_step: args.halving_step,
+            initial_reward_per_burn_unit: args.initial_reward_per_burn_unit,
+            emission_curve: args.emission_curve.clone(),
+            ..Default::default()
+        };
+        let stored = init_args.schedule();
+        let graph = preview_tokenomics(args.clone());
+        let Some(last_threshold) = stored.secondary_burn_thresholds.last().copied() else {
+            prop_assert!(graph.minted_per_epoch_data_y.is_empty());
+            return Ok(());
+        };
+
+        // Every epoch the preview draws is what minting along the stored schedule gives.
+        let simulation = simulate_mint(&stored, 0, 0, last_threshold, u64::MAX / E8S as u64)
+            .expect("in-range schedules do not overflow");
+        let epochs = graph.minted_per_epoch_data_y.len();
+        prop_assert!(epochs <= simulation.thresholds.len());
+        for (epoch, minted) in graph.minted_per_epoch_data_y.iter().enumerate() {
+            let threshold = &simulation.thresholds[epoch];
+            let burned = graph.cumulative_supply_data_x[epoch + 1];
+            if epoch + 1 < epochs {
+                prop_assert_eq!(threshold.primary_minted, *minted);
+                prop_assert_eq!(burned, stored.secondary_burn_thresholds[epoch]);
+            } else {
+                // The preview stops the last epoch at the supply cap.
+                prop_assert!(threshold.primary_minted >= *minted);
+                prop_assert!(last_threshold >= burned);
+            }
+        }
+    }
+
+    #[test]
+    fn generated_curves_are_valid(curve in emission_curve()) {
+        prop_assert!(curve.validate().is_ok());
+    }
+
+    #[test]
+    fn schedule_is_well_formed(args in preview_args()) {
+        let schedule = args.schedule();
+        let thresholds = &schedule.secondary_burn_thresholds;
+        let rates = &schedule.primary_mint_per_threshold;
+
+        prop_assert_eq!(thresholds.len(), rates.len());
+        prop_assert!(thresholds.windows(2).all(|pair| pair[0] < pair[1]));
+        prop_assert!(rates.windows(2).all(|pair| pair[0] >= pair[1]));
+        prop_assert!(rates.iter().all(|rate| *rate >= 1));
+    }
+
+    #[test]
+    fn preview_epochs_match_the_mint_math(args in preview_args()) {
+        let schedule = args.schedule();
+        let graph = preview_tokenomics(args.clone());
+        let Some(last_threshold) = schedule.secondary_burn_thresholds.last().copied() else {
+            prop_assert!(graph.minted_per_epoch_data_y.is_empty());
+            return Ok(());
+        };
+
+        // Burning the whole schedule at once walks every threshold.
+        let simulation = simulate_mint(&schedule, 0, 0, last_threshold, u64::MAX / E8S as u64)
+            .expect("in-range schedules do not overflow");
+        prop_assert!(!simulation.max_mint_primary_reached);
+        prop_assert_eq!(simulation.thresholds.len(), schedule.secondary_burn_thresholds.len());
+
+        let epochs = graph.minted_per_epoch_data_y.len();
+        prop_assert!(epochs <= simulation.thresholds.len());
+        for (epoch, minted) in graph.minted_per_epoch_data_y.iter().enumerate() {
+            let threshold = &simulation.thresholds[epoch];
+            prop_assert_eq!(threshold.threshold_index as usize, epoch);
+            let burned = graph.cumulative_supply_data_x[epoch + 1]
+                - graph.cumulative_supply_data_x[epoch];
+            if epoch + 1 < epochs {
+                prop_assert_eq!(threshold.primary_minted, *minted);
+                prop_assert_eq!(threshold.secondary_burned, burned);
+            } else {
+                // The preview stops the last epoch at the supply cap.
+                prop_assert!(threshold.primary_minted >= *minted);
+                prop_assert!(threshold.secondary_burned >= burned);
+            }
+        }
+    }
+
+    #[test]
+    fn epochs_to_mint_counts_the_preview_epochs(args in preview_args()) {
+        prop_assume!(args.tge_allocation < args.primary_max_supply);
+        let schedule = args.schedule();
+        let Some(last_threshold) = schedule.secondary_burn_thresholds.last().copied() else {
+            return Ok(());
+        };
+
+        // What lbry_fun reports as a launch's epochs, against the epochs the preview draws
+        // before the supply cap; supplies are whole tokens, thresholds mint e8s.
+        let simulation = simulate_mint(&schedule, 0, 0, last_threshold, u64::MAX / E8S as u64)
+            .expect("in-range schedules do not overflow");
+        let epochs =
+            simulation.epochs_to_mint(args.primary_max_supply - args.tge_allocation);
+        let graph = preview_tokenomics(args);
+        prop_assert_eq!(epochs as usize, graph.minted_per_epoch_data_y.len());
+    }
+
+    #[test]
+    fn launch_protection_closes_even_without_liquidity(
+        window_secs in 1..=MAX_LAUNCH_PROTECTION_SECS,
+        installed_at in 0..=1u64 << 62,
+        liquidity_delay_secs in prop::option::of(0..=2 * MAX_LIQUIDITY_DELAY_SECS),
+    ) {
+        let protection = LaunchProtection {
+            window_secs,
+            max_icp_swapped: Some(1),
+            max_secondary_burned: None,
+        };
+        let liquidity_provided_at =
+            liquidity_delay_secs.map(|secs| installed_at + secs * NANOS_PER_SEC);
+        let ends_at = protection.ends_at(installed_at, liquidity_provided_at);
+
+        prop_assert!(protection.is_active(installed_at, liquidity_provided_at, installed_at));
+        prop_assert!(!protection.is_active(installed_at, liquidity_provided_at, ends_at));
+        prop_assert!(
+            ends_at <= installed_at + (MAX_LIQUIDITY_DELAY_SECS + window_secs) * NANOS_PER_SEC
+        );
+        if let Some(provided_at) = liquidity_provided_at {
+            prop_assert!(ends_at <= provided_at + window_secs * NANOS_PER_SEC);
+        }
+    }
+
+    #[test]
+    fn vesting_releases_monotonically(
+        amount in 1..=u64::MAX,
+        (cliff_secs, duration_secs) in (1..=400_000_000u64)
+            .prop_flat_map(|duration| (0..=duration, Just(duration))),
+        start in 0..=1u64 << 62,
+        (earlier, later) in (0..=500_000_000u64).prop_flat_map(|later| (0..=later, Just(later))),
+    ) {
+        let at = |secs: u64| {
+            vested_amount(amount, start, cliff_secs, duration_secs, start + secs * NANOS_PER_SEC)
+        };
+
+        prop_assert!(at(earlier) <= at(later));
+        prop_assert!(at(later) <= amount);
+        if cliff_secs > 0 {
+            prop_assert_eq!(at(cliff_secs - 1), 0);
+        }
+        prop_assert_eq!(at(duration_secs), amount);
+    }
+
+    #[test]
+    fn every_airdrop_leaf_proves_against_the_root(
+        recipients in prop::collection::vec(
+            (prop::collection::vec(any::<u8>(), 0..=29), 1..=u64::MAX),
+            1..=64,
+        ),
+    ) {
+        let leaves: Vec<_> = recipients
+            .iter()
+            .map(|(bytes, amount)| airdrop_leaf(Principal::from_slice(bytes), *amount))
+            .collect();
+        let root = merkle_root(&leaves).unwrap();
+
+        for (index, (bytes, amount)) in recipients.iter().enumerate() {
+            let proof = merkle_proof(&leaves, index).unwrap();
+            prop_assert!(verify_merkle_proof(&root, leaves[index], &proof));
+            // The same proof does not vouch for a different amount.
+            let inflated = airdrop_leaf(Principal::from_slice(bytes), amount.wrapping_add(1));
+            prop_assert!(!verify_merkle_proof(&root, inflated, &proof));
+        }
+    }
+
+    #[test]
+    fn reward_index_pays_each_stake_its_share(ops in stake_ops()) {
+        let mut index = RewardIndex::default();
+        let mut stakers: Vec<Staker> = (0..STAKERS).map(|_| Staker::default()).collect();
+        let mut expiries = BTreeSet::new();
+        let mut distributed = 0u128;
+        let mut now = 0u64;
+
+        for op in ops {
+            match op {
+                StakeOp::Stake { staker, amount } => {
+                    stakers[staker].settle(&mut index, now);
+                    stakers[staker].amount += amount;
+                    stakers[staker].settle(&mut index, now);
+                }
+                StakeOp::Unstake { staker, amount } => {
+                    stakers[staker].settle(&mut index, now);
+                    stakers[staker].amount -= amount.min(stakers[staker].amount);
+                    stakers[staker].settle(&mut index, now);
+                }
+                StakeOp::Lock { staker, boost, secs } => {
+                    stakers[staker].settle(&mut index, now);
+                    stakers[staker].locks.push((boost, now + secs));
+                    expiries.insert((now + secs, staker));
+                    stakers[staker].settle(&mut index, now);
+                }
+                StakeOp::Wait { secs } => now += secs,
+                StakeOp::Distribute { reward } => {
+                    // Expired boosts are settled away before anything is shared.
+                    while let Some(&(expiry, staker)) = expiries.first() {
+                        if expiry > now {
+                            break;
+                        }
+                        expiries.pop_first();
+                        stakers[staker].settle(&mut index, now);
+                    }
+                    let weights: Vec<u128> =
+                        stakers.iter().map(|staker| staker.weight_at(now)).collect();
+                    prop_assert_eq!(index.total_weight, weights.iter().sum::<u128>());
+                    if index.total_weight == 0 {
+                        continue;
+                    }
+
+                    let reward_per_weight =
+                        reward as u128 * REWARD_SCALING_FACTOR / index.total_weight;
+                    index.add(reward_per_weight);
+                    distributed += reward as u128;
+                    for (staker, weight) in stakers.iter_mut().zip(weights) {
+                        staker.owed_scaled += weight * reward_per_weight;
+                    }
+                }
+            }
+        }
+
+        let mut paid = 0u128;
+        for staker in &mut stakers {
+            // What queries show before the stake is touched is what settling pays.
+            let pending = index.pending(staker.weight.unwrap_or(0), staker.checkpoint);
+            let reward = staker.reward;
+            staker.settle(&mut index, now);
+            prop_assert_eq!(staker.reward, reward + pending);
+
+            // Each settle rounds down by less than one e8.
+            let reward_scaled = staker.reward as u128 * REWARD_SCALING_FACTOR;
+            prop_assert!(reward_scaled <= staker.owed_scaled);
+            prop_assert!(
+                staker.owed_scaled - reward_scaled
+                    < staker.settles as u128 * REWARD_SCALING_FACTOR
+            );
+            paid += staker.reward as u128;
+        }
+        prop_assert!(paid <= distributed);
+    }
+}
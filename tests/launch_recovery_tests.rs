@@ -0,0 +1,395 @@
+use candid::{decode_one, Encode, Nat, Principal, CandidType, Deserialize};
+use pocket_ic::PocketIc;
+use num_traits::cast::ToPrimitive;
+use std::time::Duration;
+
+// Constants
+const E8S: u64 = 100_000_000;
+const ICP_TRANSFER_FEE: u64 = 10_000;
+const LAUNCH_FEE: u64 = 200_000_000;
+// Enough for the swap and tokenomics canisters of a launch, but not the third one.
+const STARVED_LBRY_FUN_CYCLES: u128 = 4_500_000_000_000;
+const MAX_LAUNCH_ATTEMPTS: u32 = 5;
+const LAUNCH_RETRY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+// 1x1 PNG header, enough for the logo check.
+const LOGO: &str = "iVBORw0KGgo=";
+
+// Include WASM files
+const LBRY_FUN_WASM: &[u8] = include_bytes!("../target/wasm32-unknown-unknown/release/lbry_fun.wasm");
+const ICP_LEDGER_WASM: &[u8] = include_bytes!("../src/lbry_fun/src/ic-icrc1-ledger.wasm");
+const MOCK_CMC_WASM: &[u8] = include_bytes!("mock_cmc.wasm");
+
+use crate::individual_canister_tests::{LedgerArg, InitArgs, FeatureFlags, ArchiveOptions, Account};
+
+#[derive(CandidType, Deserialize)]
+struct Configs {
+    icp_ledger_id: Option<Principal>,
+    cycles_minting_canister_id: Option<Principal>,
+    lbry_swap_canister_id: Option<Principal>,
+    kong_backend_canister_id: Option<Principal>,
+    icpswap_factory_canister_id: Option<Principal>,
+    xrc_canister_id: Option<Principal>,
+}
+
+// Every field is optional, so an empty record leaves all launch options at their defaults.
+#[derive(CandidType, Deserialize)]
+struct LaunchOptions {}
+
+#[derive(CandidType, Deserialize, Debug, PartialEq)]
+enum LaunchStep {
+    CreateSwapCanister,
+    CreateTokenomicsCanister,
+    CreateFrontendCanister,
+    CreateLogsCanister,
+    CreatePrimaryLedger,
+    InstallPrimaryLedger,
+    CreateSecondaryLedger,
+    InstallSecondaryLedger,
+    InstallTokenomics,
+    MintTgeAllocations,
+    InstallIcpSwap,
+    InstallLogs,
+    AddTokenToDex,
+    RecordToken,
+}
+
+#[derive(CandidType, Deserialize, Debug, PartialEq)]
+enum LaunchStatus {
+    InProgress,
+    Completed,
+    Failed,
+    Refunded,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct LaunchRecord {
+    caller: Principal,
+    step: LaunchStep,
+    status: LaunchStatus,
+    swap_canister_id: Option<Principal>,
+    tokenomics_canister_id: Option<Principal>,
+    frontend_canister_id: Option<Principal>,
+    attempts: u32,
+    last_error: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Debug, PartialEq)]
+enum RefundStatus {
+    Pending,
+    Refunded,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct RefundRecord {
+    launch_id: u64,
+    caller: Principal,
+    status: RefundStatus,
+    fee_paid: u64,
+    deleted_canisters: Vec<Principal>,
+    cycles_reclaimed: Nat,
+    fees_spent: u64,
+    refunded_amount: u64,
+    block_index: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+struct Icrc2ApproveArgs {
+    from_subaccount: Option<[u8; 32]>,
+    spender: Account,
+    amount: Nat,
+    expected_allowance: Option<Nat>,
+    expires_at: Option<u64>,
+    fee: Option<Nat>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+// lbry_fun with an ICP ledger and a mock CMC. No DEX is deployed, so a launch that gets
+// through every install step stops at AddTokenToDex.
+pub struct LaunchTestEnvironment {
+    pub pic: PocketIc,
+    pub lbry_fun: Principal,
+    pub icp_ledger: Principal,
+    pub cmc: Principal,
+    pub creator: Principal,
+}
+
+impl LaunchTestEnvironment {
+    pub fn new(lbry_fun_cycles: u128) -> Self {
+        let pic = PocketIc::new();
+
+        let lbry_fun = pic.create_canister();
+        let icp_ledger = pic.create_canister();
+        let cmc = pic.create_canister();
+        pic.add_cycles(lbry_fun, lbry_fun_cycles);
+        for canister in &[icp_ledger, cmc] {
+            pic.add_cycles(*canister, 10_000_000_000_000);
+        }
+
+        let env = Self {
+            pic,
+            lbry_fun,
+            icp_ledger,
+            cmc,
+            creator: Principal::from_slice(&[4; 29]),
+        };
+
+        env.deploy_icp_ledger();
+        env.pic.install_canister(env.cmc, MOCK_CMC_WASM.to_vec(), vec![], None);
+        env.deploy_lbry_fun();
+
+        env
+    }
+
+    fn deploy_icp_ledger(&self) {
+        let init_args = Encode!(&LedgerArg::Init(
+            InitArgs {
+                decimals: Some(8),
+                token_symbol: "ICP".to_string(),
+                token_name: "Internet Computer".to_string(),
+                minting_account: Account {
+                    owner: Principal::anonymous(),
+                    subaccount: None,
+                },
+                initial_balances: vec![(
+                    Account {
+                        owner: self.creator,
+                        subaccount: None,
+                    },
+                    Nat::from(100 * E8S),
+                )],
+                metadata: vec![],
+                maximum_number_of_accounts: None,
+                accounts_overflow_trim_quantity: None,
+                fee_collector_account: None,
+                transfer_fee: Nat::from(ICP_TRANSFER_FEE),
+                feature_flags: Some(FeatureFlags { icrc2: true }),
+                max_memo_length: None,
+                archive_options: ArchiveOptions {
+                    num_blocks_to_archive: 10_000,
+                    max_transactions_per_response: None,
+                    trigger_threshold: 10_000,
+                    max_message_size_bytes: None,
+                    cycles_for_archive_creation: None,
+                    node_max_memory_size_bytes: None,
+                    controller_id: Principal::anonymous(),
+                },
+            }
+        ))
+        .expect("Failed to encode ICP ledger init args");
+
+        self.pic.install_canister(
+            self.icp_ledger,
+            ICP_LEDGER_WASM.to_vec(),
+            init_args,
+            Some(Principal::anonymous()),
+        );
+
+        println!("✓ ICP Ledger deployed at: {}", self.icp_ledger);
+    }
+
+    fn deploy_lbry_fun(&self) {
+        let init_args = Encode!(&Some(Configs {
+            icp_ledger_id: Some(self.icp_ledger),
+            cycles_minting_canister_id: Some(self.cmc),
+            lbry_swap_canister_id: None,
+            kong_backend_canister_id: None,
+            icpswap_factory_canister_id: None,
+            xrc_canister_id: None,
+        }))
+        .expect("Failed to encode lbry_fun init args");
+
+        self.pic.install_canister(
+            self.lbry_fun,
+            LBRY_FUN_WASM.to_vec(),
+            init_args,
+            Some(Principal::anonymous()),
+        );
+
+        println!("✓ lbry_fun deployed at: {}", self.lbry_fun);
+    }
+
+    // Pays the launch fee and starts a launch with the default options.
+    pub fn create_token(&self) -> Result<String, String> {
+        self.approve_icp(self.lbry_fun, LAUNCH_FEE + ICP_TRANSFER_FEE);
+
+        let args = Encode!(
+            &"Test Primary".to_string(),
+            &"TPT".to_string(),
+            &"A test primary token".to_string(),
+            &LOGO.to_string(),
+            &"Test Secondary".to_string(),
+            &"TST".to_string(),
+            &"A test secondary token".to_string(),
+            &LOGO.to_string(),
+            &(1_000_000 * E8S),
+            &(100_000 * E8S),
+            &(10_000 * E8S),
+            &(5_000 * E8S),
+            &50u64,
+            &100u64,
+            &None::<LaunchOptions>
+        )
+        .expect("Failed to encode create_token args");
+
+        self.update::<Result<String, String>>("create_token", args)
+    }
+
+    pub fn resume_launch(&self, launch_id: u64) -> Result<String, String> {
+        let args = Encode!(&launch_id).expect("Failed to encode resume_launch args");
+        self.update::<Result<String, String>>("resume_launch", args)
+    }
+
+    pub fn get_launch_status(&self, launch_id: u64) -> Option<LaunchRecord> {
+        let args = Encode!(&launch_id).expect("Failed to encode launch id");
+        let reply = self
+            .pic
+            .query_call(self.lbry_fun, self.creator, "get_launch_status", args)
+            .expect("get_launch_status failed");
+        decode_one(&reply).expect("Failed to decode launch record")
+    }
+
+    pub fn get_refund_status(&self, launch_id: u64) -> Option<RefundRecord> {
+        let args = Encode!(&launch_id).expect("Failed to encode launch id");
+        let reply = self
+            .pic
+            .query_call(self.lbry_fun, self.creator, "get_refund_status", args)
+            .expect("get_refund_status failed");
+        decode_one(&reply).expect("Failed to decode refund record")
+    }
+
+    pub fn get_icp_balance(&self, owner: Principal) -> u64 {
+        let args = Encode!(&Account {
+            owner,
+            subaccount: None,
+        })
+        .expect("Failed to encode balance args");
+        let reply = self
+            .pic
+            .query_call(self.icp_ledger, Principal::anonymous(), "icrc1_balance_of", args)
+            .expect("icrc1_balance_of failed");
+        let balance: Nat = decode_one(&reply).expect("Failed to decode balance");
+        balance.0.to_u64().unwrap_or(0)
+    }
+
+    // Lets the launch retry timer fire and gives its calls time to complete.
+    pub fn run_launch_timers(&self) {
+        self.pic.advance_time(LAUNCH_RETRY_INTERVAL + Duration::from_secs(1));
+        for _ in 0..50 {
+            self.pic.tick();
+        }
+    }
+
+    fn update<T: for<'a> Deserialize<'a> + CandidType>(&self, method: &str, args: Vec<u8>) -> T {
+        let reply = self
+            .pic
+            .update_call(self.lbry_fun, self.creator, method, args)
+            .unwrap_or_else(|e| panic!("{} call failed: {}", method, e));
+        decode_one(&reply).unwrap_or_else(|e| panic!("Failed to decode {} reply: {}", method, e))
+    }
+
+    fn approve_icp(&self, spender: Principal, amount: u64) {
+        let args = Encode!(&Icrc2ApproveArgs {
+            from_subaccount: None,
+            spender: Account {
+                owner: spender,
+                subaccount: None,
+            },
+            amount: Nat::from(amount),
+            expected_allowance: None,
+            expires_at: None,
+            fee: None,
+            memo: None,
+            created_at_time: None,
+        })
+        .expect("Failed to encode approve args");
+
+        match self.pic.update_call(self.icp_ledger, self.creator, "icrc2_approve", args) {
+            Ok(_) => println!("✓ Creator approved {} ICP to {}", amount, spender),
+            Err(e) => panic!("Failed to approve ICP: {}", e),
+        }
+    }
+}
+
+// A launch that runs out of cycles halfway keeps what it created and carries on from
+// the failed step once resumed.
+#[test]
+pub fn test_resume_launch_after_failure() {
+    let env = LaunchTestEnvironment::new(STARVED_LBRY_FUN_CYCLES);
+
+    let err = env.create_token().expect_err("Launch should stop without enough cycles");
+    assert!(err.contains("resume_launch(1)"), "Unexpected error: {}", err);
+
+    let stalled = env.get_launch_status(1).expect("Launch should be recorded");
+    assert_eq!(stalled.caller, env.creator);
+    assert_eq!(stalled.status, LaunchStatus::InProgress);
+    assert_eq!(stalled.step, LaunchStep::CreateFrontendCanister);
+    assert_eq!(stalled.attempts, 1);
+    assert!(stalled.last_error.is_some());
+    assert!(stalled.frontend_canister_id.is_none());
+    let swap_canister_id = stalled.swap_canister_id.expect("Swap canister should exist");
+    let tokenomics_canister_id = stalled.tokenomics_canister_id.expect("Tokenomics canister should exist");
+
+    env.pic.add_cycles(env.lbry_fun, 30_000_000_000_000);
+    let err = env.resume_launch(1).expect_err("No DEX is deployed, so the launch should stop there");
+    println!("Resumed launch stopped with: {}", err);
+
+    let resumed = env.get_launch_status(1).expect("Launch should be recorded");
+    assert_eq!(resumed.status, LaunchStatus::InProgress);
+    assert_eq!(resumed.step, LaunchStep::AddTokenToDex);
+    assert_eq!(resumed.attempts, 2);
+    // The canisters created before the failure are reused, not created again.
+    assert_eq!(resumed.swap_canister_id, Some(swap_canister_id));
+    assert_eq!(resumed.tokenomics_canister_id, Some(tokenomics_canister_id));
+    assert!(resumed.frontend_canister_id.is_some());
+    assert!(resumed
+        .last_error
+        .expect("The DEX failure should be recorded")
+        .contains("Failed to add token to DEX"));
+
+    println!("✓ Launch resumed from {:?} to {:?}", stalled.step, resumed.step);
+}
+
+// A launch that keeps failing is rolled back: its canisters are deleted, their cycles
+// reclaimed and the fee minus what was spent goes back to the creator.
+#[test]
+pub fn test_refund_failed_launch() {
+    let env = LaunchTestEnvironment::new(STARVED_LBRY_FUN_CYCLES);
+
+    env.create_token().expect_err("Launch should stop without enough cycles");
+    for _ in 1..MAX_LAUNCH_ATTEMPTS {
+        env.resume_launch(1).expect_err("Launch should keep failing without cycles");
+    }
+    let failed = env.get_launch_status(1).expect("Launch should be recorded");
+    assert_eq!(failed.status, LaunchStatus::Failed);
+    assert_eq!(failed.attempts, MAX_LAUNCH_ATTEMPTS);
+    let created = vec![
+        failed.tokenomics_canister_id.expect("Tokenomics canister should exist"),
+        failed.swap_canister_id.expect("Swap canister should exist"),
+    ];
+
+    let balance_before = env.get_icp_balance(env.creator);
+    env.run_launch_timers();
+
+    let refund = env.get_refund_status(1).expect("Refund should be recorded");
+    assert_eq!(refund.launch_id, 1);
+    assert_eq!(refund.caller, env.creator);
+    assert_eq!(refund.status, RefundStatus::Refunded);
+    assert_eq!(refund.fee_paid, LAUNCH_FEE);
+    assert_eq!(refund.deleted_canisters, created);
+    assert!(refund.cycles_reclaimed > Nat::from(0u64), "No cycles were reclaimed");
+    assert!(refund.fees_spent >= ICP_TRANSFER_FEE);
+    assert_eq!(refund.refunded_amount, refund.fee_paid - refund.fees_spent);
+    assert!(refund.block_index.is_some());
+
+    assert_eq!(env.get_icp_balance(env.creator), balance_before + refund.refunded_amount);
+    for canister_id in &created {
+        assert!(!env.pic.canister_exists(*canister_id), "{} was not deleted", canister_id);
+    }
+    let refunded = env.get_launch_status(1).expect("Launch should be recorded");
+    assert_eq!(refunded.status, LaunchStatus::Refunded);
+    // Refunded launches cannot be resumed.
+    assert!(env.resume_launch(1).is_err());
+
+    println!("✓ Refunded {} of {} e8s", refund.refunded_amount, refund.fee_paid);
+}
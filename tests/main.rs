@@ -16,6 +16,7 @@ mod individual_canister_tests;
 mod integrated_token_tests;
 mod phase1_environment_tests;
 mod phase2_token_operations;
+mod launch_recovery_tests;
 
 fn main() {
     println!("\n=== Running Token Environment Tests ===");
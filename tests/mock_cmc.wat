@@ -0,0 +1,20 @@
+;; Stand-in for the cycles minting canister in PocketIC tests. `get_icp_xdr_conversion_rate`
+;; always replies with a rate of 10 XDR per ICP (`xdr_permyriad_per_icp = 100_000`) and an
+;; empty certificate, which is all lbry_fun reads when pricing a refund.
+;;
+;; Rebuild with any WAT assembler, e.g. `wat2wasm mock_cmc.wat -o mock_cmc.wasm`.
+(module
+  (import "ic0" "msg_reply_data_append" (func $msg_reply_data_append (param i32 i32)))
+  (import "ic0" "msg_reply" (func $msg_reply))
+
+  (memory 1)
+
+  ;; Candid encoding of the IcpXdrConversionRateResponse reply.
+  (data (i32.const 0)
+    "\44\49\44\4c\03\6c\03\97\92\8b\da\01\01\aa\ac\8d\93\04\02\af\f5\99\8c\0a\01\6d\7b\6c\02\df\f5\81\a0\08\78\d6\d5\da\c6\0f\78\01\00\00\a0\86\01\00\00\00\00\00\00\00\00\00\00\00\00\00\00")
+
+  (func $get_icp_xdr_conversion_rate
+    (call $msg_reply_data_append (i32.const 0) (i32.const 61))
+    (call $msg_reply))
+
+  (export "canister_update get_icp_xdr_conversion_rate" (func $get_icp_xdr_conversion_rate)))